@@ -0,0 +1,780 @@
+use crate::{dawg::DawgSet, Checksum, FlatMatches, Interned, Interner};
+
+/// Regex iterators that can lend out each matching string without allocating it.
+///
+/// This is implemented by all the exhaustive iterators in this crate, and is the base for
+/// adaptors that don't need to own the strings, like [`BorrowNext::hashes`].
+pub trait BorrowNext {
+    /// Get the next matching string ref from this regex iterator
+    fn borrow_next(&mut self) -> Option<&[u8]>;
+
+    /// Turn this iterator into one that produces a stable 64-bit hash of each matching string,
+    /// without allocating the strings. See [`stable_hash`]
+    ///
+    /// ```
+    /// use regex_utils::{stable_hash, BorrowNext, DenseDfaIter};
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-c]{2}").unwrap();
+    /// let hashes: Vec<u64> = iter.hashes().collect();
+    /// assert_eq!(hashes.len(), 9);
+    /// assert!(hashes.contains(&stable_hash(b"ca")));
+    /// ```
+    fn hashes(self) -> Hashes<Self>
+    where
+        Self: Sized,
+    {
+        Hashes(self)
+    }
+
+    /// Only produce the matches that `validate` returns `true` for.
+    ///
+    /// This is for constraints that a regex can't express, or can't express simply,
+    /// like the number of days in a month. See [`presets::date`](crate::presets::date)
+    ///
+    /// ```
+    /// use regex_utils::{BorrowNext, DenseDfaIter};
+    ///
+    /// let iter = DenseDfaIter::new(r"[0-9]{2}").unwrap();
+    /// let x: Vec<Vec<u8>> = iter.with_validator(|s| s[0] != s[1]).take(3).collect();
+    /// assert_eq!(x, [b"01".to_vec(), b"02".to_vec(), b"03".to_vec()]);
+    /// ```
+    fn with_validator<F>(self, validate: F) -> Validated<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&[u8]) -> bool,
+    {
+        Validated {
+            iter: self,
+            validate,
+            str: vec![],
+        }
+    }
+
+    /// Only produce the matches whose last digit is the correct check digit. See [`Checksum`]
+    ///
+    /// ```
+    /// use regex_utils::{BorrowNext, Checksum, DenseDfaIter};
+    ///
+    /// let iter = DenseDfaIter::new(r"4[0-9]{3}( [0-9]{4}){3}").unwrap();
+    /// let cards: Vec<Vec<u8>> = iter.with_checksum(Checksum::Luhn).take(2).collect();
+    /// assert_eq!(cards, [b"4000 0000 0000 0002".to_vec(), b"4000 0000 0000 0010".to_vec()]);
+    /// ```
+    fn with_checksum(self, checksum: Checksum) -> Validated<Self, fn(&[u8]) -> bool>
+    where
+        Self: Sized,
+    {
+        self.with_validator(checksum.validator())
+    }
+
+    /// Write up to `n` of the next matches into `out`, replacing its contents, and return
+    /// how many were written. Fewer than `n` are written only once the matches run out.
+    ///
+    /// The strings already in `out` are reused, so reusing the same buffer for each batch
+    /// avoids allocating each match.
+    ///
+    /// ```
+    /// use regex_utils::{BorrowNext, DenseDfaIter};
+    ///
+    /// let mut iter = DenseDfaIter::new(r"[0-9]{2}").unwrap();
+    /// let mut batch = vec![];
+    /// let mut total = 0;
+    /// while iter.next_batch(&mut batch, 32) > 0 {
+    ///     // write the batch somewhere
+    ///     total += batch.len();
+    /// }
+    /// assert_eq!(total, 100);
+    /// ```
+    fn next_batch(&mut self, out: &mut Vec<Vec<u8>>, n: usize) -> usize {
+        let mut len = 0;
+        while len < n {
+            let Some(next) = self.borrow_next() else {
+                break;
+            };
+            match out.get_mut(len) {
+                Some(s) => {
+                    s.clear();
+                    s.extend_from_slice(next);
+                }
+                None => out.push(next.to_vec()),
+            }
+            len += 1;
+        }
+        out.truncate(len);
+        len
+    }
+
+    /// Like [`BorrowNext::next_batch`], but the matches are written one after another into `bytes`.
+    ///
+    /// `offsets` gets one more entry than the number of matches, starting with 0,
+    /// so match `i` is `bytes[offsets[i]..offsets[i + 1]]`, like the layout of Arrow string arrays.
+    ///
+    /// ```
+    /// use regex_utils::{BorrowNext, DenseDfaIter};
+    ///
+    /// let mut iter = DenseDfaIter::new(r"a{1,3}").unwrap();
+    /// let (mut bytes, mut offsets) = (vec![], vec![]);
+    /// assert_eq!(iter.next_batch_flat(&mut bytes, &mut offsets, 5), 3);
+    /// assert_eq!(bytes, b"aaaaaa");
+    /// assert_eq!(offsets, [0, 1, 3, 6]);
+    /// ```
+    fn next_batch_flat(
+        &mut self,
+        bytes: &mut Vec<u8>,
+        offsets: &mut Vec<usize>,
+        n: usize,
+    ) -> usize {
+        bytes.clear();
+        offsets.clear();
+        offsets.push(0);
+        for _ in 0..n {
+            let Some(next) = self.borrow_next() else {
+                break;
+            };
+            bytes.extend_from_slice(next);
+            offsets.push(bytes.len());
+        }
+        offsets.len() - 1
+    }
+
+    /// Write up to `limit` of the next matches to `w` as a JSON array of strings,
+    /// and return how many were written.
+    ///
+    /// Matches that aren't UTF-8 are written as `{"base64":"..."}` objects instead.
+    /// Nothing is buffered, so wrap `w` in a [`BufWriter`](std::io::BufWriter) if writes are costly.
+    ///
+    /// ```
+    /// use regex_utils::{BorrowNext, DenseDfaIter};
+    ///
+    /// let mut out = vec![];
+    /// let mut iter = DenseDfaIter::new(r#""[ab]"#).unwrap();
+    /// assert_eq!(iter.to_json_writer(&mut out, 10).unwrap(), 2);
+    /// assert_eq!(out, br#"["\"a","\"b"]"#);
+    /// ```
+    fn to_json_writer<W: std::io::Write>(&mut self, w: W, limit: usize) -> std::io::Result<usize>
+    where
+        Self: Sized,
+    {
+        crate::export::write_json(self, w, limit)
+    }
+
+    /// Collect every match into one buffer. See [`FlatMatches`]
+    ///
+    /// Like collecting, this never ends for regexes with infinitely many matches,
+    /// so limit the iterator first, eg with [`BorrowNext::while_len_le`].
+    fn collect_flat(mut self) -> FlatMatches
+    where
+        Self: Sized,
+    {
+        let mut matches = FlatMatches::new();
+        matches.extend_from(&mut self, usize::MAX);
+        matches
+    }
+
+    /// Store each distinct match once, and produce shared [`Arc`](std::sync::Arc)s of them,
+    /// or [`Symbol`](crate::Symbol)s with [`Interned::next_symbol`].
+    ///
+    /// This saves memory when keeping many matches with duplicates, like those from [`NfaIter`](crate::NfaIter).
+    ///
+    /// ```
+    /// use regex_utils::{BorrowNext, NfaIter};
+    ///
+    /// let mut iter = NfaIter::new(r"(?:a|ab)(?:c|bc)").unwrap().interned();
+    /// let x: Vec<_> = iter.by_ref().collect();
+    /// // `abc` is produced twice, but only stored once
+    /// assert_eq!(x.len(), 4);
+    /// assert_eq!(iter.interner().len(), 3);
+    /// ```
+    fn interned(self) -> Interned<Self>
+    where
+        Self: Sized,
+    {
+        Interned::new(self, Interner::new())
+    }
+
+    /// Like [`BorrowNext::interned`], but adding to an existing [`Interner`], to share the strings
+    /// and symbols with the matches of other iterators
+    fn with_interner(self, interner: Interner) -> Interned<Self>
+    where
+        Self: Sized,
+    {
+        Interned::new(self, interner)
+    }
+
+    /// Produce the matches as [`Bytes`](bytes::Bytes), sliced from a shared buffer.
+    /// See [`BytesIter`](crate::BytesIter)
+    ///
+    /// ```
+    /// use bytes::Bytes;
+    /// use regex_utils::{BorrowNext, DenseDfaIter};
+    ///
+    /// let x: Vec<Bytes> = DenseDfaIter::new(r"GET /[a-z]{1,3}").unwrap().into_bytes().take(2).collect();
+    /// assert_eq!(x, [Bytes::from("GET /a"), Bytes::from("GET /b")]);
+    /// ```
+    #[cfg(feature = "bytes")]
+    fn into_bytes(self) -> crate::BytesIter<Self>
+    where
+        Self: Sized,
+    {
+        crate::BytesIter::new(self)
+    }
+
+    /// Collect up to `n` matches, and whether they are all of the remaining matches.
+    ///
+    /// To tell whether the matches were truncated, this searches for one more match after
+    /// the first `n`, which is not returned.
+    ///
+    /// ```
+    /// use regex_utils::{BorrowNext, DenseDfaIter, Exhausted};
+    ///
+    /// let (x, exhausted) = DenseDfaIter::new(r"[ab]{2}").unwrap().collect_up_to(4);
+    /// assert_eq!(x.len(), 4);
+    /// assert_eq!(exhausted, Exhausted::Yes);
+    ///
+    /// let (x, exhausted) = DenseDfaIter::new(r"[ab]+").unwrap().collect_up_to(4);
+    /// assert_eq!(x.len(), 4);
+    /// assert_eq!(exhausted, Exhausted::No);
+    /// ```
+    fn collect_up_to(mut self, n: usize) -> (Vec<Vec<u8>>, Exhausted)
+    where
+        Self: Sized,
+    {
+        let mut matches = Vec::new();
+        while matches.len() < n {
+            match self.borrow_next() {
+                Some(next) => matches.push(next.to_vec()),
+                None => return (matches, Exhausted::Yes),
+            }
+        }
+        let exhausted = match self.borrow_next() {
+            Some(_) => Exhausted::No,
+            None => Exhausted::Yes,
+        };
+        (matches, exhausted)
+    }
+
+    /// Produce the matches of up to `n` bytes, and stop at the first longer match.
+    ///
+    /// The iterators in this crate produce the shorter matches first, so this is like
+    /// `take_while(|s| s.len() <= n)`, but without copying the matches, and it never asks the
+    /// iterator for another match after the first longer one.
+    /// [`DfaIter::max_depth`](crate::DfaIter::max_depth) and [`NfaIter::max_depth`](crate::NfaIter::max_depth)
+    /// go further, and stop before searching for the longer matches at all.
+    ///
+    /// ```
+    /// use regex_utils::{interleave, BorrowNext, DenseDfaIter, NfaIter};
+    ///
+    /// let a = DenseDfaIter::new(r"[ab]+").unwrap();
+    /// let b = NfaIter::new(r"[0-9]+").unwrap();
+    /// let x: Vec<Vec<u8>> = interleave(a, b).while_len_le(2).collect();
+    /// assert_eq!(x.len(), 2 + 10 + 4 + 100);
+    /// ```
+    fn while_len_le(self, n: usize) -> WhileLenLe<Self>
+    where
+        Self: Sized,
+    {
+        WhileLenLe {
+            iter: self,
+            n,
+            done: false,
+        }
+    }
+}
+
+impl<I: BorrowNext + ?Sized> BorrowNext for Box<I> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        I::borrow_next(self)
+    }
+}
+
+/// Merge two regex iterators, which can be of different types, into one. See [`interleave_all`]
+///
+/// ```
+/// use regex_utils::{interleave, DenseDfaIter, NfaIter};
+///
+/// let a = NfaIter::new(r"a+").unwrap();
+/// let b = DenseDfaIter::new(r"[0-9]{2}|x").unwrap();
+/// let x: Vec<Vec<u8>> = interleave(a, b).take(5).collect();
+/// assert_eq!(x, [
+///     b"a".to_vec(),
+///     b"x".to_vec(),
+///     b"aa".to_vec(),
+///     b"00".to_vec(),
+///     b"01".to_vec(),
+/// ]);
+/// ```
+pub fn interleave<'a, A, B>(a: A, b: B) -> Interleave<Box<dyn BorrowNext + 'a>>
+where
+    A: BorrowNext + 'a,
+    B: BorrowNext + 'a,
+{
+    interleave_all([
+        Box::new(a) as Box<dyn BorrowNext + 'a>,
+        Box::new(b) as Box<dyn BorrowNext + 'a>,
+    ])
+}
+
+/// Merge any number of regex iterators into one.
+///
+/// Shorter matches are always produced first, and matches of the same length are taken from
+/// each iterator in turn, so no iterator is starved by another with many matches of that length.
+/// Strings that match several of the iterators are produced once for each.
+pub fn interleave_all<I: BorrowNext>(iters: impl IntoIterator<Item = I>) -> Interleave<I> {
+    let iters = iters
+        .into_iter()
+        .filter_map(|mut iter| {
+            let next = iter.borrow_next()?.to_vec();
+            Some((iter, next))
+        })
+        .collect();
+    Interleave {
+        iters,
+        turn: 0,
+        str: vec![],
+    }
+}
+
+/// An iterator over the matches of several regex iterators. See [`interleave_all`]
+pub struct Interleave<I> {
+    // (iterator, its next match) for each iterator with matches left
+    iters: Vec<(I, Vec<u8>)>,
+    // the iterator that is first in line for ties
+    turn: usize,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl<I: BorrowNext> Interleave<I> {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        let n = self.iters.len();
+        // the shortest next match, with ties going to the iterator whose turn is next
+        let (_, _, i) = self
+            .iters
+            .iter()
+            .enumerate()
+            .map(|(i, (_, next))| (next.len(), (i + n - self.turn % n) % n, i))
+            .min()?;
+
+        let (iter, next) = &mut self.iters[i];
+        std::mem::swap(&mut self.str, next);
+        match iter.borrow_next() {
+            Some(s) => {
+                next.clear();
+                next.extend_from_slice(s);
+                self.turn = i + 1;
+            }
+            // this iterator has no more matches, and the next one moves into its place
+            None => {
+                self.iters.remove(i);
+                self.turn = i;
+            }
+        }
+        Some(&self.str)
+    }
+}
+
+impl<I: BorrowNext> BorrowNext for Interleave<I> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        Interleave::borrow_next(self)
+    }
+}
+
+impl<I: BorrowNext> Iterator for Interleave<I> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+/// Produce all the matches of each regex iterator in turn, skipping any string that was
+/// already produced.
+///
+/// This also removes the repeats from iterators that don't guarantee unique outputs, like
+/// [`NfaIter`](crate::NfaIter). Every string produced is kept in memory, in a minimal automaton
+/// that shares the common prefixes and suffixes of the strings.
+///
+/// ```
+/// use regex_utils::{chain_dedup, NfaIter};
+///
+/// let a = NfaIter::new(r"a|b|a").unwrap();
+/// let b = NfaIter::new(r"[b-d]").unwrap();
+/// let x: Vec<Vec<u8>> = chain_dedup([a, b]).collect();
+/// assert_eq!(x, [b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]);
+/// ```
+pub fn chain_dedup<I: BorrowNext>(iters: impl IntoIterator<Item = I>) -> ChainDedup<I> {
+    ChainDedup {
+        iters: iters.into_iter().collect(),
+        current: 0,
+        seen: DawgSet::new(),
+        str: vec![],
+    }
+}
+
+/// An iterator over the unique matches of several regex iterators. See [`chain_dedup`]
+pub struct ChainDedup<I> {
+    iters: Vec<I>,
+    // the iterator that is producing matches
+    current: usize,
+    // every string produced so far
+    seen: DawgSet,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl<I: BorrowNext> ChainDedup<I> {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        while let Some(iter) = self.iters.get_mut(self.current) {
+            match iter.borrow_next() {
+                Some(s) if !self.seen.insert(s) => {}
+                Some(s) => {
+                    self.str.clear();
+                    self.str.extend_from_slice(s);
+                    return Some(&self.str);
+                }
+                None => self.current += 1,
+            }
+        }
+        None
+    }
+}
+
+impl<I: BorrowNext> BorrowNext for ChainDedup<I> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        ChainDedup::borrow_next(self)
+    }
+}
+
+impl<I: BorrowNext> Iterator for ChainDedup<I> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+/// An iterator over the matches that pass a validator. See [`BorrowNext::with_validator`]
+pub struct Validated<I, F> {
+    iter: I,
+    validate: F,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl<I, F> Validated<I, F> {
+    /// Recover the regex iterator that this validates the matches of
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I: BorrowNext, F: FnMut(&[u8]) -> bool> Validated<I, F> {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            let next = self.iter.borrow_next()?;
+            if (self.validate)(next) {
+                self.str.clear();
+                self.str.extend_from_slice(next);
+                return Some(&self.str);
+            }
+        }
+    }
+}
+
+impl<I: BorrowNext, F: FnMut(&[u8]) -> bool> BorrowNext for Validated<I, F> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        Validated::borrow_next(self)
+    }
+}
+
+impl<I: BorrowNext, F: FnMut(&[u8]) -> bool> Iterator for Validated<I, F> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+/// A regex iterator that only searches for matches up to a maximum length, so it is always finite
+/// and safe to collect. See [`DfaIter::max_depth`](crate::DfaIter::max_depth) and
+/// [`NfaIter::max_depth`](crate::NfaIter::max_depth)
+pub struct BoundedIter<I> {
+    iter: I,
+    max_depth: usize,
+}
+
+impl<I> BoundedIter<I> {
+    /// Wrap an iterator that has already been limited to `max_depth` internally
+    pub(crate) fn new(iter: I, max_depth: usize) -> Self {
+        Self { iter, max_depth }
+    }
+
+    /// The maximum length of the matches
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Recover the regex iterator, which is still limited to the maximum length
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I: BorrowNext> BoundedIter<I> {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        self.iter.borrow_next()
+    }
+}
+
+impl<I: BorrowNext> BorrowNext for BoundedIter<I> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        BoundedIter::borrow_next(self)
+    }
+}
+
+impl<I: BorrowNext> Iterator for BoundedIter<I> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+impl<I: BorrowNext> std::iter::FusedIterator for BoundedIter<I> {}
+
+/// Whether [`BorrowNext::collect_up_to`] collected every match
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exhausted {
+    /// These are all of the matches
+    Yes,
+    /// There are more matches that were not collected
+    No,
+}
+
+/// An iterator over the matches up to a length. See [`BorrowNext::while_len_le`]
+pub struct WhileLenLe<I> {
+    iter: I,
+    n: usize,
+    // whether a longer match has been seen
+    done: bool,
+}
+
+impl<I> WhileLenLe<I> {
+    /// Recover the regex iterator that this limits the matches of
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I: BorrowNext> WhileLenLe<I> {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        if self.done {
+            return None;
+        }
+        match self.iter.borrow_next() {
+            Some(next) if next.len() <= self.n => Some(next),
+            _ => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl<I: BorrowNext> BorrowNext for WhileLenLe<I> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        WhileLenLe::borrow_next(self)
+    }
+}
+
+impl<I: BorrowNext> Iterator for WhileLenLe<I> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+impl<I: BorrowNext> std::iter::FusedIterator for WhileLenLe<I> {}
+
+/// An iterator over the hashes of matching strings. See [`BorrowNext::hashes`]
+pub struct Hashes<I>(I);
+
+impl<I> Hashes<I> {
+    /// Recover the regex iterator that this hashes the matches of
+    pub fn into_inner(self) -> I {
+        self.0
+    }
+}
+
+impl<I: BorrowNext> Iterator for Hashes<I> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.borrow_next().map(stable_hash)
+    }
+}
+
+/// The 64-bit [FNV-1a](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+/// hash of the bytes, as produced by [`BorrowNext::hashes`].
+///
+/// Unlike [`std::hash::Hash`], this is stable across platforms, processes and versions of this crate,
+/// so it is safe to store.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes
+        .iter()
+        .fold(OFFSET, |hash, &b| (hash ^ b as u64).wrapping_mul(PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DenseDfaIter, NfaIter};
+
+    use super::*;
+
+    #[test]
+    fn stable() {
+        assert_eq!(stable_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(stable_hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn interleave() {
+        let a = DenseDfaIter::new(r"[ab]{1,2}").unwrap();
+        let b = DenseDfaIter::new(r"[xy]{2}").unwrap();
+        let c = DenseDfaIter::new(r"z*").unwrap();
+        let x: Vec<Vec<u8>> = interleave_all([a, b, c]).take(11).collect();
+        assert_eq!(
+            x,
+            [
+                b"".to_vec(),
+                // ties are taken in turn
+                b"a".to_vec(),
+                b"z".to_vec(),
+                b"b".to_vec(),
+                // and the turns carry on to the next length
+                b"xx".to_vec(),
+                b"zz".to_vec(),
+                b"aa".to_vec(),
+                b"xy".to_vec(),
+                b"ab".to_vec(),
+                b"yx".to_vec(),
+                b"ba".to_vec(),
+            ]
+        );
+
+        let empty: Vec<NfaIter> = vec![];
+        assert_eq!(interleave_all(empty).next(), None);
+    }
+
+    #[test]
+    fn chain_dedup() {
+        let a = NfaIter::new(r"(a|ab)(c|bcd)").unwrap();
+        let b = NfaIter::new(r"abcd|ac|x").unwrap();
+        let c = NfaIter::new(r"[^\s\S]").unwrap();
+        let d = NfaIter::new(r"x|y").unwrap();
+        let x: Vec<Vec<u8>> = super::chain_dedup([a, b, c, d]).collect();
+        assert_eq!(
+            x,
+            [
+                b"ac".to_vec(),
+                b"abc".to_vec(),
+                b"abcd".to_vec(),
+                b"abbcd".to_vec(),
+                b"x".to_vec(),
+                b"y".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn hashes() {
+        let x: Vec<u64> = NfaIter::new(r"a+b").unwrap().hashes().take(10).collect();
+        let y: Vec<u64> = NfaIter::new(r"a+b")
+            .unwrap()
+            .take(10)
+            .map(|s| stable_hash(&s))
+            .collect();
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn while_len_le() {
+        // counts how many matches were asked for
+        struct Counted<I>(I, usize);
+        impl<I: BorrowNext> BorrowNext for Counted<I> {
+            fn borrow_next(&mut self) -> Option<&[u8]> {
+                self.1 += 1;
+                self.0.borrow_next()
+            }
+        }
+
+        let mut iter = Counted(NfaIter::new(r"a*").unwrap(), 0).while_len_le(3);
+        let x: Vec<Vec<u8>> = iter.by_ref().collect();
+        assert_eq!(x.len(), 4);
+        assert_eq!(iter.next(), None);
+        // only the first longer match is asked for
+        assert_eq!(iter.into_inner().1, 5);
+
+        let x: Vec<Vec<u8>> = NfaIter::new(r"[ab]{4}").unwrap().while_len_le(3).collect();
+        assert!(x.is_empty());
+    }
+
+    #[test]
+    fn collect_up_to() {
+        let (x, exhausted) = NfaIter::new(r"[a-c]").unwrap().collect_up_to(3);
+        assert_eq!(x, [b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert_eq!(exhausted, Exhausted::Yes);
+
+        let (x, exhausted) = NfaIter::new(r"[a-c]").unwrap().collect_up_to(2);
+        assert_eq!(x, [b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(exhausted, Exhausted::No);
+
+        let (x, exhausted) = NfaIter::new(r"[a-c]").unwrap().collect_up_to(0);
+        assert!(x.is_empty());
+        assert_eq!(exhausted, Exhausted::No);
+
+        let (x, exhausted) = DenseDfaIter::new(r"[^\s\S]").unwrap().collect_up_to(0);
+        assert!(x.is_empty());
+        assert_eq!(exhausted, Exhausted::Yes);
+    }
+
+    #[test]
+    fn next_batch() {
+        let mut iter = NfaIter::new(r"[a-e]").unwrap();
+        let mut batch = vec![b"reused".to_vec(); 5];
+        assert_eq!(iter.next_batch(&mut batch, 2), 2);
+        assert_eq!(batch, [b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(iter.next_batch(&mut batch, 2), 2);
+        assert_eq!(batch, [b"c".to_vec(), b"d".to_vec()]);
+        assert_eq!(iter.next_batch(&mut batch, 2), 1);
+        assert_eq!(batch, [b"e".to_vec()]);
+        assert_eq!(iter.next_batch(&mut batch, 2), 0);
+        assert!(batch.is_empty());
+
+        let mut iter = NfaIter::new(r"x|[a-e]{2}").unwrap();
+        let (mut bytes, mut offsets) = (vec![], vec![]);
+        assert_eq!(iter.next_batch_flat(&mut bytes, &mut offsets, 3), 3);
+        assert_eq!(bytes, b"xaaab");
+        assert_eq!(offsets, [0, 1, 3, 5]);
+        let rest = iter.collect::<Vec<_>>().len();
+        assert_eq!(rest, 25 - 2);
+
+        assert_eq!(
+            NfaIter::new(r"x")
+                .unwrap()
+                .next_batch_flat(&mut bytes, &mut offsets, 0),
+            0
+        );
+        assert!(bytes.is_empty());
+        assert_eq!(offsets, [0]);
+    }
+}
@@ -0,0 +1,253 @@
+#![allow(clippy::result_large_err)]
+
+use regex_syntax::hir::Hir;
+
+use crate::{
+    term::{DerivBuildError, Reachable, TermId, Terms, NOTHING},
+    BorrowNext,
+};
+
+/// `BrzozowskiIter` will produce every possible string value that will match with the given regex,
+/// without building an automaton.
+///
+/// ```
+/// use regex_utils::BrzozowskiIter;
+///
+/// let iter = BrzozowskiIter::new(r"[ab]+c").unwrap();
+/// let x: Vec<Vec<u8>> = iter.take(4).collect();
+/// assert_eq!(x, [
+///     b"ac".to_vec(),
+///     b"bc".to_vec(),
+///     b"aac".to_vec(),
+///     b"abc".to_vec(),
+/// ]);
+/// ```
+///
+/// # Note
+///
+/// Regexes can be infinite (eg `a*`). Either use this iterator lazily, or limit the number
+/// of iterations.
+///
+/// Unicode word boundaries are not supported. Use ASCII word boundaries (`(?-u:\b)`) instead.
+///
+/// # Implementation Details
+///
+/// Instead of compiling the regex, this walks the
+/// [Brzozowski derivatives](https://en.wikipedia.org/wiki/Brzozowski_derivative) of the parsed regex,
+/// computing each derivative the first time it is needed. Derivatives are simplified so that
+/// there are finitely many of them, which makes this a lazily built DFA, and like [`DfaIter`](crate::DfaIter),
+/// output strings are unique and produced in lexicographical byte ordering, shortest first.
+///
+/// This has almost no up-front cost, so it is well suited for quick one-off generations.
+/// Since it does not share any code with the automaton based iterators, it is also useful to cross-check them.
+///
+/// Every string that matches the whole regex is produced, including those that a leftmost-first
+/// DFA would not reach, such as `cc` in `(a)?|cc`.
+///
+/// Terms are pruned by the length of their shortest match, assuming every look-around holds.
+/// Once a search depth finds no matches, every derivative is taken, to prune the terms whose
+/// look-arounds can never hold, like `a*$b`.
+pub struct BrzozowskiIter {
+    // the derivatives of the regex
+    terms: Terms,
+    // the term for the whole regex
+    start: TermId,
+    // the max depth we currently want to search
+    depth: usize,
+    // the max depth observed in the graph
+    max_depth: usize,
+    // (term, edge, depth)
+    stack: Vec<(TermId, u8, usize)>,
+    // the current path, with a placeholder first byte for the start
+    str: Vec<u8>,
+    // whether the current search depth found a match
+    found: bool,
+    // every derivative of the regex, once a search depth finds no matches
+    reachable: Option<Reachable>,
+}
+
+impl TryFrom<&Hir> for BrzozowskiIter {
+    type Error = DerivBuildError;
+
+    fn try_from(hir: &Hir) -> Result<Self, Self::Error> {
+        let mut terms = Terms::new();
+        let start = terms.hir(hir)?;
+        Ok(Self {
+            terms,
+            start,
+            depth: 0,
+            max_depth: 0,
+            stack: vec![(start, 0, 0)],
+            str: vec![],
+            found: false,
+            reachable: None,
+        })
+    }
+}
+
+impl BrzozowskiIter {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `BrzozowskiIter`.
+    ///
+    /// If you want a non-default configuration, then use the
+    /// [`regex_syntax::ParserBuilder`] to parse the [`Hir`] yourself.
+    pub fn new(pattern: &str) -> Result<Self, DerivBuildError> {
+        let hir = regex_syntax::parse(pattern)?;
+        Self::try_from(&hir)
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            let Some((current, b, depth)) = self.stack.pop() else {
+                // we didn't get any deeper. no more search space
+                if self.max_depth < self.depth {
+                    break None;
+                }
+                if !self.found && self.reachable.is_none() {
+                    // the min lengths can keep growing forever if the look-arounds never hold.
+                    // there are finitely many derivatives, so find which can still match
+                    self.reachable = Some(self.terms.reachable(self.start, None, false));
+                    self.max_depth = 0;
+                }
+
+                self.found = false;
+                self.depth += 1;
+                self.stack.clear();
+                self.stack.push((self.start, 0, 0));
+                continue;
+            };
+
+            // update recorded max depth
+            self.max_depth = usize::max(self.max_depth, depth);
+            self.str.truncate(depth);
+            self.str.push(b);
+            let prev = self.str[1..].last().copied();
+
+            // check we can explore deeper
+            if depth < self.depth {
+                let first = self.terms.first(current);
+                for b in (0..=255).rev().filter(|&b| first.contains(b)) {
+                    let next = self.terms.deriv(current, prev, b);
+                    if next == NOTHING {
+                        continue;
+                    }
+                    let min_len = match &self.reachable {
+                        Some(reachable) => reachable.dist(&self.terms, next, Some(b)),
+                        None => self.terms.min_len(next),
+                    };
+                    if min_len == usize::MAX {
+                        continue;
+                    }
+                    // check if the next term could still match within the current depth
+                    let min_len = depth + 1 + min_len;
+                    if min_len <= self.depth {
+                        self.stack.push((next, b, depth + 1));
+                    } else {
+                        // there are longer matches to find in a later search
+                        self.max_depth = usize::max(self.max_depth, min_len);
+                    }
+                }
+            } else if self.terms.nullable(current, prev, None) {
+                self.found = true;
+                break Some(&self.str[1..]);
+            }
+        }
+    }
+}
+
+impl BorrowNext for BrzozowskiIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        BrzozowskiIter::borrow_next(self)
+    }
+}
+
+impl Iterator for BrzozowskiIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::DenseDfaIter;
+
+    use super::*;
+
+    #[test]
+    fn set() {
+        let iter = BrzozowskiIter::new(r"^b|(a)?|cc").unwrap();
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(
+            x,
+            [b"".to_vec(), b"a".to_vec(), b"b".to_vec(), b"cc".to_vec()]
+        );
+    }
+
+    #[test]
+    fn finite() {
+        // every repetition can be empty, so no term is ever pruned
+        let iter = BrzozowskiIter::new(r"(?:a?){3}").unwrap();
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(
+            x,
+            [b"".to_vec(), b"a".to_vec(), b"aa".to_vec(), b"aaa".to_vec()]
+        );
+    }
+
+    #[test]
+    fn matches_dfa() {
+        for pattern in [
+            r"[a-c]{1,3}x?|foo",
+            r"a+(0|1)",
+            r"(?:a*)*b",
+            r"[а-яё0-9]{2}",
+            r"(?:foo|ba[rz])?quux",
+        ] {
+            let x: Vec<Vec<u8>> = BrzozowskiIter::new(pattern).unwrap().take(60).collect();
+            let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().take(60).collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn looks() {
+        let iter = BrzozowskiIter::new(r"(?m)[ab\n]{0,2}^b$(?-u:\b)").unwrap();
+        let x: HashSet<Vec<u8>> = iter.collect();
+        assert_eq!(
+            x,
+            HashSet::from([
+                b"b".to_vec(),
+                b"\nb".to_vec(),
+                b"a\nb".to_vec(),
+                b"b\nb".to_vec(),
+                b"\n\nb".to_vec(),
+            ])
+        );
+
+        // the look-arounds never hold
+        assert_eq!(BrzozowskiIter::new(r"a*$b").unwrap().next(), None);
+        let iter = BrzozowskiIter::new(r"(?:aa)+|b*$c|d").unwrap();
+        let x: Vec<Vec<u8>> = iter.take(4).collect();
+        assert_eq!(
+            x,
+            [
+                b"d".to_vec(),
+                b"aa".to_vec(),
+                b"aaaa".to_vec(),
+                b"aaaaaa".to_vec()
+            ]
+        );
+        let iter = BrzozowskiIter::new(r"x|(?:a|b)*$c").unwrap();
+        assert_eq!(iter.collect::<Vec<_>>(), [b"x".to_vec()]);
+
+        assert!(matches!(
+            BrzozowskiIter::new(r"\b"),
+            Err(DerivBuildError::UnicodeWordBoundary)
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+#![allow(clippy::result_large_err)]
+
+use std::borrow::Cow;
+
+use regex_automata::{dfa::dense, nfa::thompson::BuildError, util::syntax};
+use regex_syntax::hir::{Capture, Hir, HirKind, Repetition};
+
+use crate::{DenseDfaIter, NfaIter};
+
+/// A builder for regex iterators, with options that rewrite the regex before it is compiled.
+///
+/// ```
+/// use regex_utils::Builder;
+///
+/// // `+` would usually make this an infinite language
+/// let iter = Builder::new().max_repetitions(2).build_dense(r"a+b?").unwrap();
+///
+/// let x: Vec<Vec<u8>> = iter.collect();
+/// assert_eq!(x, [
+///     b"a".to_vec(),
+///     b"aa".to_vec(),
+///     b"ab".to_vec(),
+///     b"aab".to_vec(),
+/// ]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    // the upper bound for unbounded repetitions
+    max_repetitions: Option<u32>,
+    // the upper bound for unbounded repetitions at each nesting level
+    levels: Vec<u32>,
+}
+
+impl Builder {
+    /// Create a new builder with the default configuration, which does not rewrite the regex
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rewrite all unbounded repetitions (`*`, `+`, `{n,}`) to repeat at most `max` times.
+    ///
+    /// This turns infinite languages into finite ones, so that collecting every match is safe.
+    /// Repetitions with a minimum above `max` are repeated exactly their minimum number of times.
+    pub fn max_repetitions(mut self, max: u32) -> Self {
+        self.max_repetitions = Some(max);
+        self
+    }
+
+    /// Rewrite unbounded repetitions with a different upper bound depending on how deeply nested they are.
+    ///
+    /// The first bound applies to the outermost unbounded repetitions, the second bound to
+    /// unbounded repetitions inside those, and so on. So with `[3, 5]`, the regex `(?:a+b)*` is rewritten
+    /// to `(?:a{1,5}b){0,3}`. Levels without a bound fall back to [`Builder::max_repetitions`].
+    pub fn max_repetitions_per_level(mut self, levels: impl IntoIterator<Item = u32>) -> Self {
+        self.levels = levels.into_iter().collect();
+        self
+    }
+
+    /// Build an [`NfaIter`] for the rewritten pattern
+    pub fn build_nfa(&self, pattern: &str) -> Result<NfaIter, BuildError> {
+        NfaIter::new(&self.rewrite(pattern))
+    }
+
+    /// Build a [`DenseDfaIter`] for the rewritten pattern
+    pub fn build_dense(&self, pattern: &str) -> Result<DenseDfaIter<Vec<u32>>, dense::BuildError> {
+        DenseDfaIter::new(&self.rewrite(pattern))
+    }
+
+    /// Apply the configured rewrites to the pattern.
+    ///
+    /// If the pattern fails to parse, it is returned as is so the engine can report the error.
+    fn rewrite<'a>(&self, pattern: &'a str) -> Cow<'a, str> {
+        if self.max_repetitions.is_none() && self.levels.is_empty() {
+            return Cow::Borrowed(pattern);
+        }
+        match syntax::parse(pattern) {
+            Ok(hir) => Cow::Owned(self.rewrite_hir(&hir, 0).to_string()),
+            Err(_) => Cow::Borrowed(pattern),
+        }
+    }
+
+    fn rewrite_hir(&self, hir: &Hir, level: usize) -> Hir {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => {
+                hir.clone()
+            }
+            HirKind::Repetition(rep) if rep.max.is_none() => {
+                let cap = self.levels.get(level).copied().or(self.max_repetitions);
+                Hir::repetition(Repetition {
+                    min: rep.min,
+                    max: cap.map(|cap| u32::max(rep.min, cap)),
+                    greedy: rep.greedy,
+                    sub: Box::new(self.rewrite_hir(&rep.sub, level + 1)),
+                })
+            }
+            HirKind::Repetition(rep) => Hir::repetition(Repetition {
+                min: rep.min,
+                max: rep.max,
+                greedy: rep.greedy,
+                sub: Box::new(self.rewrite_hir(&rep.sub, level)),
+            }),
+            HirKind::Capture(cap) => Hir::capture(Capture {
+                index: cap.index,
+                name: cap.name.clone(),
+                sub: Box::new(self.rewrite_hir(&cap.sub, level)),
+            }),
+            HirKind::Concat(subs) => {
+                Hir::concat(subs.iter().map(|h| self.rewrite_hir(h, level)).collect())
+            }
+            HirKind::Alternation(subs) => {
+                Hir::alternation(subs.iter().map(|h| self.rewrite_hir(h, level)).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finite() {
+        let iter = Builder::new()
+            .max_repetitions(3)
+            .build_nfa(r"(?:a|bc)*")
+            .unwrap();
+
+        // 1 + 2 + 4 + 8 paths, some of which end up the same length
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(x.len(), 15);
+        assert!(x.iter().all(|s| s.len() <= 6));
+    }
+
+    #[test]
+    fn min_above_cap() {
+        let iter = Builder::new()
+            .max_repetitions(2)
+            .build_dense(r"a{4,}")
+            .unwrap();
+
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(x, [b"aaaa".to_vec()]);
+    }
+
+    #[test]
+    fn bounded_unchanged() {
+        let builder = Builder::new().max_repetitions(1);
+        let x: Vec<Vec<u8>> = builder.build_dense(r"[ab]{2,3}").unwrap().collect();
+        assert_eq!(x.len(), 12);
+    }
+
+    #[test]
+    fn per_level() {
+        let builder = Builder::new().max_repetitions_per_level([2, 3]);
+        assert_eq!(builder.rewrite(r"(?:a+b)*"), r"(?:a{1,3}b){0,2}");
+
+        let x: Vec<Vec<u8>> = builder.build_dense(r"(?:a+b)*").unwrap().collect();
+        // "" + 3 with one repetition + 9 with two repetitions
+        assert_eq!(x.len(), 13);
+    }
+
+    #[test]
+    fn per_level_fallback() {
+        let builder = Builder::new()
+            .max_repetitions(1)
+            .max_repetitions_per_level([4]);
+        assert_eq!(builder.rewrite(r"(?:a*b)+|c+"), r"(?:(?:a?b){1,4}|c{1,4})");
+    }
+
+    #[test]
+    fn syntax_error() {
+        let builder = Builder::new().max_repetitions(1);
+        assert!(builder.build_nfa(r"a(").is_err());
+        assert!(builder.build_dense(r"a(").is_err());
+    }
+}
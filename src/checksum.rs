@@ -0,0 +1,124 @@
+/// Check digit schemes, for [`BorrowNext::with_checksum`](crate::BorrowNext::with_checksum).
+///
+/// Spaces and hyphens are ignored, so grouped numbers like `4111-1111-1111-1111` can be checked.
+/// Strings with any other non-digit bytes fail the check, except where noted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Checksum {
+    /// The [Luhn algorithm](https://en.wikipedia.org/wiki/Luhn_algorithm), used by payment card numbers
+    Luhn,
+    /// The GS1 check digit, used by EAN-8, EAN-13, UPC-A and other GTINs
+    Gtin,
+    /// The ISBN-10 check digit, which can also be `X`
+    Isbn10,
+}
+
+impl Checksum {
+    /// Whether the last digit of `str` is the correct check digit for the digits before it
+    ///
+    /// ```
+    /// use regex_utils::Checksum;
+    ///
+    /// assert!(Checksum::Luhn.is_valid(b"4111 1111 1111 1111"));
+    /// assert!(!Checksum::Luhn.is_valid(b"4111 1111 1111 1112"));
+    /// assert!(Checksum::Gtin.is_valid(b"4006381333931"));
+    /// assert!(Checksum::Isbn10.is_valid(b"0-8044-2957-X"));
+    /// ```
+    pub fn is_valid(self, str: &[u8]) -> bool {
+        self.validator()(str)
+    }
+
+    pub(crate) fn validator(self) -> fn(&[u8]) -> bool {
+        match self {
+            Checksum::Luhn => luhn,
+            Checksum::Gtin => gtin,
+            Checksum::Isbn10 => isbn10,
+        }
+    }
+}
+
+/// The digits of `str` from last to first, or `None` if there is a byte that is not a digit or separator
+fn digits_rev(str: &[u8]) -> impl Iterator<Item = Option<u32>> + '_ {
+    str.iter()
+        .rev()
+        .filter(|&&b| b != b' ' && b != b'-')
+        .map(|&b| b.is_ascii_digit().then(|| (b - b'0') as u32))
+}
+
+fn luhn(str: &[u8]) -> bool {
+    let mut len = 0;
+    let mut sum = 0;
+    for (i, d) in digits_rev(str).enumerate() {
+        let Some(d) = d else { return false };
+        sum += match i % 2 {
+            0 => d,
+            _ if d < 5 => d * 2,
+            _ => d * 2 - 9,
+        };
+        len += 1;
+    }
+    len > 1 && sum % 10 == 0
+}
+
+fn gtin(str: &[u8]) -> bool {
+    let mut len = 0;
+    let mut sum = 0;
+    for (i, d) in digits_rev(str).enumerate() {
+        let Some(d) = d else { return false };
+        sum += if i % 2 == 0 { d } else { d * 3 };
+        len += 1;
+    }
+    len > 1 && sum % 10 == 0
+}
+
+fn isbn10(str: &[u8]) -> bool {
+    let (check, rest) = match str.iter().rposition(|&b| b != b' ' && b != b'-') {
+        Some(i) if str[i] == b'X' => (10, &str[..i]),
+        Some(_) => (0, str),
+        None => return false,
+    };
+    let mut len = 0;
+    let mut sum = check;
+    for (i, d) in digits_rev(rest).enumerate() {
+        let Some(d) = d else { return false };
+        // the check digit has weight 1, and the next weight 2, and so on
+        let weight = i as u32 + 1 + (check != 0) as u32;
+        sum += d * weight;
+        len += 1;
+    }
+    len + (check != 0) as usize == 10 && sum % 11 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luhn() {
+        assert!(Checksum::Luhn.is_valid(b"79927398713"));
+        for wrong in 0..10 {
+            let wrong = format!("7992739871{wrong}");
+            assert_eq!(
+                Checksum::Luhn.is_valid(wrong.as_bytes()),
+                wrong.ends_with('3')
+            );
+        }
+        assert!(!Checksum::Luhn.is_valid(b"0"));
+        assert!(!Checksum::Luhn.is_valid(b"79927a98713"));
+    }
+
+    #[test]
+    fn gtin() {
+        assert!(Checksum::Gtin.is_valid(b"036000291452"));
+        assert!(Checksum::Gtin.is_valid(b"96385074"));
+        assert!(!Checksum::Gtin.is_valid(b"036000291453"));
+    }
+
+    #[test]
+    fn isbn10() {
+        assert!(Checksum::Isbn10.is_valid(b"0306406152"));
+        assert!(Checksum::Isbn10.is_valid(b"080442957X"));
+        assert!(!Checksum::Isbn10.is_valid(b"0306406153"));
+        assert!(!Checksum::Isbn10.is_valid(b"306406152"));
+        assert!(!Checksum::Isbn10.is_valid(b"03064061X2"));
+    }
+}
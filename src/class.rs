@@ -0,0 +1,108 @@
+use core::fmt;
+
+/// A set of bytes
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ByteClass([u128; 2]);
+
+impl ByteClass {
+    /// The class with no bytes
+    pub fn empty() -> Self {
+        Self([0; 2])
+    }
+
+    /// The class with every byte
+    pub fn full() -> Self {
+        Self([u128::MAX; 2])
+    }
+
+    /// Add the byte to the class
+    pub fn insert(&mut self, b: u8) {
+        self.0[b as usize / 128] |= 1 << (b % 128);
+    }
+
+    /// Whether the class contains the byte
+    pub fn contains(&self, b: u8) -> bool {
+        self.0[b as usize / 128] & (1 << (b % 128)) != 0
+    }
+
+    /// The number of bytes in the class
+    pub fn len(&self) -> usize {
+        (self.0[0].count_ones() + self.0[1].count_ones()) as usize
+    }
+
+    /// Whether the class has no bytes
+    pub fn is_empty(&self) -> bool {
+        self.0 == [0; 2]
+    }
+
+    /// The class with the bytes of both classes
+    pub fn union(&self, other: &Self) -> Self {
+        Self([self.0[0] | other.0[0], self.0[1] | other.0[1]])
+    }
+
+    /// The bytes in the class, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255).filter(|&b| self.contains(b))
+    }
+
+    /// The contiguous runs of bytes in the class, in ascending order
+    pub fn ranges(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        let mut bytes = self.iter().peekable();
+        std::iter::from_fn(move || {
+            let start = bytes.next()?;
+            let mut end = start;
+            while bytes.next_if(|&b| b == end.wrapping_add(1)).is_some() {
+                end += 1;
+            }
+            Some((start, end))
+        })
+    }
+}
+
+impl FromIterator<u8> for ByteClass {
+    fn from_iter<T: IntoIterator<Item = u8>>(iter: T) -> Self {
+        let mut class = Self::empty();
+        for b in iter {
+            class.insert(b);
+        }
+        class
+    }
+}
+
+impl fmt::Debug for ByteClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (start, end) in self.ranges() {
+            write!(f, "{}", start.escape_ascii())?;
+            if start != end {
+                write!(f, "-{}", end.escape_ascii())?;
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class() {
+        let class: ByteClass = b"abcxz\xff".iter().copied().collect();
+        assert_eq!(class.len(), 6);
+        assert!(class.contains(b'x') && class.contains(0xff));
+        assert!(!class.contains(b'y'));
+        assert_eq!(
+            class.ranges().collect::<Vec<_>>(),
+            [(b'a', b'c'), (b'x', b'x'), (b'z', b'z'), (0xff, 0xff)]
+        );
+        assert_eq!(format!("{class:?}"), r"[a-cxz\xff]");
+
+        assert_eq!(ByteClass::full().len(), 256);
+        assert_eq!(ByteClass::full().ranges().collect::<Vec<_>>(), [(0, 255)]);
+        assert!(ByteClass::empty().is_empty());
+
+        let other: ByteClass = b"dy".iter().copied().collect();
+        assert_eq!(format!("{:?}", class.union(&other)), r"[a-dx-z\xff]");
+    }
+}
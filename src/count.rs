@@ -0,0 +1,180 @@
+use crate::graph::Graph;
+
+/// The number of matching strings of each length, from each state of a [`Graph`].
+///
+/// Counts saturate at [`u128::MAX`]. Languages that big can still be unranked,
+/// but the saturated ranks will not all be reachable.
+pub(crate) struct Counts {
+    // [len][state]: the number of strings of exactly `len` bytes from `state` to an accepting state
+    table: Vec<Vec<u128>>,
+}
+
+impl Counts {
+    /// Count all the matching strings up to `max_len` bytes long
+    pub(crate) fn new(graph: &Graph, max_len: usize) -> Self {
+        let mut table = Vec::with_capacity(max_len + 1);
+        table.push(graph.accept.iter().map(|&a| a as u128).collect::<Vec<_>>());
+        let mut counts = Self { table };
+        counts.extend_to(graph, max_len);
+        counts
+    }
+
+    /// Also count the matching strings up to `max_len` bytes long, if they haven't been already
+    pub(crate) fn extend_to(&mut self, graph: &Graph, max_len: usize) {
+        let n = graph.states.len();
+        for len in self.table.len()..=max_len {
+            let prev = &self.table[len - 1];
+            let row = (0..n)
+                .map(|s| {
+                    graph.edges[s].iter().fold(0u128, |acc, &(start, end, t)| {
+                        let bytes = (end - start) as u128 + 1;
+                        acc.saturating_add(bytes.saturating_mul(prev[t]))
+                    })
+                })
+                .collect();
+            self.table.push(row);
+        }
+    }
+
+    /// The number of strings of exactly `len` bytes from `state` to an accepting state
+    pub(crate) fn starting_at(&self, len: usize, state: usize) -> u128 {
+        self.table[len][state]
+    }
+
+    /// The longest length that was counted
+    pub(crate) fn max_len(&self) -> usize {
+        self.table.len() - 1
+    }
+
+    /// The number of matching strings of exactly `len` bytes
+    pub(crate) fn of_len(&self, len: usize) -> u128 {
+        self.table[len][0]
+    }
+
+    /// The number of matching strings of up to `max_len` bytes
+    pub(crate) fn total(&self) -> u128 {
+        self.table
+            .iter()
+            .fold(0u128, |acc, row| acc.saturating_add(row[0]))
+    }
+
+    /// Write the matching string with the given rank into `out`.
+    ///
+    /// Strings are ranked shortest first, then in lexicographic byte order, the same order
+    /// that [`DfaIter`](crate::DfaIter) produces them in.
+    ///
+    /// # Panics
+    ///
+    /// If `rank` is not less than [`Counts::total`]
+    pub(crate) fn unrank(&self, graph: &Graph, mut rank: u128, out: &mut Vec<u8>) {
+        for len in 0..=self.max_len() {
+            let count = self.of_len(len);
+            if rank < count {
+                return self.unrank_len(graph, len, rank, out);
+            }
+            rank -= count;
+        }
+        panic!("rank out of range");
+    }
+
+    /// Write the matching string of length `len` with the given rank into `out`.
+    ///
+    /// # Panics
+    ///
+    /// If `rank` is not less than [`Counts::of_len`]
+    pub(crate) fn unrank_len(&self, graph: &Graph, len: usize, mut rank: u128, out: &mut Vec<u8>) {
+        out.clear();
+        let mut current = 0;
+        for remaining in (0..len).rev() {
+            let (b, next) = graph.edges[current]
+                .iter()
+                .find_map(|&(start, end, t)| {
+                    let each = self.table[remaining][t];
+                    let bytes = (end - start) as u128 + 1;
+                    let block = bytes.saturating_mul(each);
+                    if rank < block {
+                        let b = start + (rank / each) as u8;
+                        rank %= each;
+                        Some((b, t))
+                    } else {
+                        rank -= block;
+                        None
+                    }
+                })
+                .expect("rank out of range");
+            out.push(b);
+            current = next;
+        }
+    }
+
+    /// The rank of `str` among the matching strings of the same length, the inverse of [`Counts::unrank_len`].
+    ///
+    /// # Panics
+    ///
+    /// If `str` is longer than [`Counts::max_len`], or is not a match
+    pub(crate) fn rank_len(&self, graph: &Graph, str: &[u8]) -> u128 {
+        let mut rank = 0u128;
+        let mut current = 0;
+        for (i, &b) in str.iter().enumerate() {
+            let remaining = str.len() - i - 1;
+            for &(start, end, t) in &graph.edges[current] {
+                let each = self.table[remaining][t];
+                if b > end {
+                    let bytes = (end - start) as u128 + 1;
+                    rank = rank.saturating_add(bytes.saturating_mul(each));
+                } else {
+                    assert!(b >= start, "string is not a match");
+                    rank = rank.saturating_add(((b - start) as u128).saturating_mul(each));
+                    current = t;
+                    break;
+                }
+            }
+        }
+        assert!(graph.accept[current], "string is not a match");
+        rank
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::dfa::dense;
+
+    use crate::{dfa::anchored_start, DfaIter};
+
+    use super::*;
+
+    fn graph(pattern: &str) -> Graph {
+        let dfa = dense::DFA::new(pattern).unwrap();
+        Graph::new(&dfa, anchored_start(&dfa))
+    }
+
+    #[test]
+    fn count() {
+        let counts = Counts::new(&graph(r"[ab]*c"), 3);
+        assert_eq!(counts.of_len(0), 0);
+        assert_eq!(counts.of_len(1), 1);
+        assert_eq!(counts.of_len(3), 4);
+        assert_eq!(counts.total(), 7);
+    }
+
+    #[test]
+    fn unrank() {
+        let pattern = r"(?:foo|[a-c]{2}|x*)[0-2]";
+        let graph = graph(pattern);
+        let counts = Counts::new(&graph, 4);
+
+        // ranks are in the same order as the exhaustive iterator
+        let x: Vec<Vec<u8>> = DfaIter::from(dense::DFA::new(pattern).unwrap())
+            .take_while(|s| s.len() <= 4)
+            .collect();
+        assert_eq!(x.len() as u128, counts.total());
+        for (rank, s) in x.iter().enumerate() {
+            let mut out = vec![];
+            counts.unrank(&graph, rank as u128, &mut out);
+            assert_eq!(&out, s);
+
+            let shorter: u128 = (0..s.len()).map(|len| counts.of_len(len)).sum();
+            assert_eq!(shorter + counts.rank_len(&graph, s), rank as u128);
+        }
+    }
+}
@@ -0,0 +1,596 @@
+use core::fmt;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    error,
+    hash::{Hash, Hasher},
+};
+
+use regex_automata::dfa::Automaton;
+
+use crate::DfaIter;
+
+const MAGIC: &[u8; 4] = b"DAWG";
+const VERSION: u32 = 1;
+
+/// A minimal acyclic automaton (also known as a DAWG or MA-FSA) that accepts every string
+/// of up to a maximum length that matches a regex. See [`DfaIter::build_dawg`]
+///
+/// Strings that share prefixes or suffixes share nodes, so this is usually far smaller than
+/// the list of strings, and it can be written out with [`Dawg::to_bytes`] to ship elsewhere.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dawg {
+    // every node comes after the nodes it has edges to
+    nodes: Vec<Node>,
+    // the index of the start node
+    root: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Node {
+    // whether the input can end at this node
+    accept: bool,
+    // (start byte, end byte, target index) runs of bytes that share a target
+    edges: Vec<(u8, u8, u32)>,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Build the minimal acyclic automaton of all the matches of up to `max_len` bytes.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[0-9]+px").unwrap();
+    /// let dawg = iter.build_dawg(6);
+    /// assert_eq!(dawg.len(), 10 + 100 + 1000 + 10000);
+    /// // a node for the start, each number of digits so far, `x` and the end
+    /// assert_eq!(dawg.node_count(), 7);
+    /// assert!(dawg.contains(b"120px"));
+    /// assert!(!dawg.contains(b"12000px"));
+    /// ```
+    pub fn build_dawg(&self, max_len: usize) -> Dawg {
+        let graph = &self.graph;
+        let n = graph.states.len();
+
+        // the states that can be reached with exactly `depth` bytes, and still reach a match in time
+        let mut reachable = vec![vec![false; n]; max_len + 1];
+        reachable[0][0] = self.dist[0] <= max_len;
+        for depth in 0..max_len {
+            for state in 0..n {
+                if !reachable[depth][state] {
+                    continue;
+                }
+                for &(_, _, next) in &graph.edges[state] {
+                    if self.dist[next] < max_len - depth {
+                        reachable[depth + 1][next] = true;
+                    }
+                }
+            }
+        }
+
+        // nodes are minimized from the end of the strings back to the start.
+        // two nodes accept the same strings exactly when they are both accepting or not,
+        // and have edges on the same bytes to nodes that accept the same strings.
+        let mut nodes = vec![];
+        let mut register = HashMap::new();
+        // the node for each state, after the next depth of bytes
+        let mut below: Vec<Option<u32>> = vec![None; n];
+        for depth in (0..=max_len).rev() {
+            let mut here = vec![None; n];
+            for (state, id) in here.iter_mut().enumerate() {
+                if !reachable[depth][state] {
+                    continue;
+                }
+
+                let mut edges: Vec<(u8, u8, u32)> = vec![];
+                for &(start, end, next) in &graph.edges[state] {
+                    let Some(target) = below[next] else {
+                        continue;
+                    };
+                    match edges.last_mut() {
+                        Some((_, e, t)) if *t == target && *e as u16 + 1 == start as u16 => {
+                            *e = end
+                        }
+                        _ => edges.push((start, end, target)),
+                    }
+                }
+                let node = Node {
+                    accept: graph.accept[state],
+                    edges,
+                };
+                *id = Some(*register.entry(node).or_insert_with_key(|node: &Node| {
+                    nodes.push(node.clone());
+                    nodes.len() as u32 - 1
+                }));
+            }
+            below = here;
+        }
+
+        match below[0] {
+            Some(root) => Dawg {
+                nodes,
+                root: root as usize,
+            },
+            // the empty language
+            None => Dawg {
+                nodes: vec![Node {
+                    accept: false,
+                    edges: vec![],
+                }],
+                root: 0,
+            },
+        }
+    }
+}
+
+impl Dawg {
+    /// Whether the string is accepted
+    pub fn contains(&self, str: &[u8]) -> bool {
+        let mut node = &self.nodes[self.root];
+        for &b in str {
+            let Some(&(_, _, next)) = node
+                .edges
+                .iter()
+                .find(|&&(start, end, _)| (start..=end).contains(&b))
+            else {
+                return false;
+            };
+            node = &self.nodes[next as usize];
+        }
+        node.accept
+    }
+
+    /// The number of strings that are accepted, saturating at [`u128::MAX`]
+    pub fn len(&self) -> u128 {
+        // children come before their parents
+        let mut counts: Vec<u128> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let count = node
+                .edges
+                .iter()
+                .fold(node.accept as u128, |acc, &(s, e, t)| {
+                    let bytes = (e - s) as u128 + 1;
+                    acc.saturating_add(bytes.saturating_mul(counts[t as usize]))
+                });
+            counts.push(count);
+        }
+        counts[self.root]
+    }
+
+    /// Whether no strings are accepted
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of nodes in the automaton
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Serialize the automaton into a portable byte format, which can be read back with
+    /// [`Dawg::from_bytes`]
+    ///
+    /// ```
+    /// use regex_utils::{Dawg, DenseDfaIter};
+    ///
+    /// let dawg = DenseDfaIter::new(r"[0-9]{1,3}").unwrap().build_dawg(3);
+    /// let bytes = dawg.to_bytes();
+    /// assert_eq!(Dawg::from_bytes(&bytes).unwrap(), dawg);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // all integers are little endian u32s
+        let mut out = vec![];
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.nodes.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.root as u32).to_le_bytes());
+        for node in &self.nodes {
+            out.push(node.accept as u8);
+            out.extend_from_slice(&(node.edges.len() as u32).to_le_bytes());
+            for &(start, end, target) in &node.edges {
+                out.push(start);
+                out.push(end);
+                out.extend_from_slice(&target.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Deserialize an automaton written by [`Dawg::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidDawg> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != MAGIC || reader.u32()? != VERSION {
+            return Err(InvalidDawg);
+        }
+        let len = reader.u32()? as usize;
+        let root = reader.u32()? as usize;
+        if root >= len {
+            return Err(InvalidDawg);
+        }
+
+        let mut nodes = Vec::with_capacity(len.min(bytes.len()));
+        for i in 0..len {
+            let accept = match reader.take(1)? {
+                [0] => false,
+                [1] => true,
+                _ => return Err(InvalidDawg),
+            };
+            let edges = (0..reader.u32()?)
+                .map(|_| {
+                    let &[start, end] = reader.take(2)? else {
+                        unreachable!()
+                    };
+                    let target = reader.u32()?;
+                    // edges must lead to earlier nodes, so the automaton is acyclic
+                    if start > end || target as usize >= i {
+                        return Err(InvalidDawg);
+                    }
+                    Ok((start, end, target))
+                })
+                .collect::<Result<_, _>>()?;
+            nodes.push(Node { accept, edges });
+        }
+        if !reader.0.is_empty() {
+            return Err(InvalidDawg);
+        }
+        Ok(Self { nodes, root })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], InvalidDawg> {
+        if self.0.len() < n {
+            return Err(InvalidDawg);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, InvalidDawg> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+#[derive(Debug)]
+/// The bytes provided to [`Dawg::from_bytes`] were not a serialized [`Dawg`]
+pub struct InvalidDawg;
+
+impl fmt::Display for InvalidDawg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid serialized DAWG")
+    }
+}
+
+impl error::Error for InvalidDawg {}
+
+/// A set of strings stored as a minimal acyclic automaton, which is kept minimal as strings are
+/// added in any order.
+///
+/// Highly structured sets, like the matches of a regex, share most of their prefixes and
+/// suffixes, so this uses far less memory than a `HashSet<Vec<u8>>`.
+///
+/// Adding a string follows the incremental algorithm for unsorted data from
+/// [Daciuk et al. (2000)](https://aclanthology.org/J00-1002/): the path of the new string is
+/// made unshared by cloning from its first shared node, extended with the new suffix, and then
+/// each node on the path is merged into an equivalent node if there is one, from the end back.
+pub(crate) struct DawgSet {
+    // the root is node 0, and is never shared or merged
+    nodes: Vec<SetNode>,
+    // the indices of deleted nodes, to reuse
+    free: Vec<u32>,
+    // the nodes that can be merged into, by the hash of their accept flag and edges
+    register: HashMap<u64, Vec<u32>>,
+}
+
+#[derive(Default)]
+struct SetNode {
+    // whether the input can end at this node
+    accept: bool,
+    // (byte, target index), sorted by byte
+    edges: Vec<(u8, u32)>,
+    // the number of edges to this node
+    refs: u32,
+}
+
+impl DawgSet {
+    pub(crate) fn new() -> Self {
+        Self {
+            nodes: vec![SetNode::default()],
+            free: vec![],
+            register: HashMap::new(),
+        }
+    }
+
+    /// The number of nodes in use
+    #[cfg(test)]
+    fn node_count(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    fn next(&self, node: u32, b: u8) -> Option<u32> {
+        let edges = &self.nodes[node as usize].edges;
+        let i = edges.binary_search_by_key(&b, |&(b, _)| b).ok()?;
+        Some(edges[i].1)
+    }
+
+    #[cfg(test)]
+    fn contains(&self, str: &[u8]) -> bool {
+        let mut node = 0;
+        for &b in str {
+            match self.next(node, b) {
+                Some(next) => node = next,
+                None => return false,
+            }
+        }
+        self.nodes[node as usize].accept
+    }
+
+    /// Add the string to the set. Returns whether it was not already in the set.
+    pub(crate) fn insert(&mut self, str: &[u8]) -> bool {
+        // the longest prefix of the string that is already in the automaton
+        let mut path = vec![0];
+        for &b in str {
+            match self.next(*path.last().unwrap(), b) {
+                Some(next) => path.push(next),
+                None => break,
+            }
+        }
+        if path.len() == str.len() + 1 && self.nodes[*path.last().unwrap() as usize].accept {
+            return false;
+        }
+
+        // the nodes on the path are about to change, so can't be merged into until they are done.
+        // from the first node that is shared with other strings, the path is cloned instead
+        let shared = (1..path.len())
+            .find(|&i| self.nodes[path[i] as usize].refs > 1)
+            .unwrap_or(path.len());
+        for &node in &path[1..shared] {
+            self.unregister(node);
+        }
+        for i in shared..path.len() {
+            let node = &self.nodes[path[i] as usize];
+            let clone = SetNode {
+                accept: node.accept,
+                edges: node.edges.clone(),
+                refs: 0,
+            };
+            for &(_, child) in &clone.edges {
+                self.nodes[child as usize].refs += 1;
+            }
+            let clone = self.alloc(clone);
+            self.set_edge(path[i - 1], str[i - 1], clone);
+            path[i] = clone;
+        }
+
+        for &b in &str[path.len() - 1..] {
+            let next = self.alloc(SetNode::default());
+            self.set_edge(*path.last().unwrap(), b, next);
+            path.push(next);
+        }
+        self.nodes[*path.last().unwrap() as usize].accept = true;
+
+        // each node only changed because its children did, so merge from the end back
+        for i in (1..path.len()).rev() {
+            match self.equivalent(path[i]) {
+                Some(node) => self.set_edge(path[i - 1], str[i - 1], node),
+                None => self.register(path[i]),
+            }
+        }
+        true
+    }
+
+    fn alloc(&mut self, node: SetNode) -> u32 {
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i as usize] = node;
+                i
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() as u32 - 1
+            }
+        }
+    }
+
+    /// Point the edge for `b` at `target`, deleting the previous target if nothing else uses it
+    fn set_edge(&mut self, node: u32, b: u8, target: u32) {
+        self.nodes[target as usize].refs += 1;
+        let edges = &mut self.nodes[node as usize].edges;
+        match edges.binary_search_by_key(&b, |&(b, _)| b) {
+            Ok(i) => {
+                let old = std::mem::replace(&mut edges[i].1, target);
+                self.release(old);
+            }
+            Err(i) => edges.insert(i, (b, target)),
+        }
+    }
+
+    fn release(&mut self, node: u32) {
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            self.nodes[node as usize].refs -= 1;
+            if self.nodes[node as usize].refs == 0 {
+                self.unregister(node);
+                let edges = std::mem::take(&mut self.nodes[node as usize].edges);
+                stack.extend(edges.into_iter().map(|(_, child)| child));
+                self.free.push(node);
+            }
+        }
+    }
+
+    fn signature(&self, node: u32) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let node = &self.nodes[node as usize];
+        (node.accept, &node.edges).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Another node with the same accept flag and edges, which accepts the same strings
+    fn equivalent(&self, node: u32) -> Option<u32> {
+        let n = &self.nodes[node as usize];
+        self.register
+            .get(&self.signature(node))?
+            .iter()
+            .copied()
+            .find(|&other| {
+                let o = &self.nodes[other as usize];
+                other != node && o.accept == n.accept && o.edges == n.edges
+            })
+    }
+
+    fn register(&mut self, node: u32) {
+        let signature = self.signature(node);
+        self.register.entry(signature).or_default().push(node);
+    }
+
+    fn unregister(&mut self, node: u32) {
+        let signature = self.signature(node);
+        if let Some(nodes) = self.register.get_mut(&signature) {
+            nodes.retain(|&n| n != node);
+            if nodes.is_empty() {
+                self.register.remove(&signature);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{DenseDfaIter, NfaIter};
+
+    use super::*;
+
+    #[test]
+    fn matches_iter() {
+        for pattern in [r"(ab|b)*c?", r"[a-c]{2}x|y[0-1]*", r"(?:foo|bar)+"] {
+            let iter = DenseDfaIter::new(pattern).unwrap();
+            let dawg = iter.build_dawg(6);
+
+            let x: HashSet<Vec<u8>> = NfaIter::new(pattern)
+                .unwrap()
+                .take_while(|s| s.len() <= 6)
+                .collect();
+            assert_eq!(dawg.len(), x.len() as u128, "{pattern}");
+            for s in &x {
+                assert!(dawg.contains(s), "{pattern}");
+            }
+            assert!(!dawg.contains(b"q"), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn minimal() {
+        // every string of length 1 to 4 over `[ab]` ends in the same node
+        let dawg = DenseDfaIter::new(r"[ab]+").unwrap().build_dawg(4);
+        assert_eq!(dawg.node_count(), 5);
+        assert_eq!(dawg.len(), 2 + 4 + 8 + 16);
+
+        // the `s?` endings are shared by all the words
+        let dawg = DenseDfaIter::new(r"(?:cat|dog|bird)s?")
+            .unwrap()
+            .build_dawg(5);
+        assert_eq!(dawg.len(), 6);
+        assert_eq!(dawg.node_count(), 10);
+    }
+
+    #[test]
+    fn empty() {
+        let dawg = DenseDfaIter::new(r"abc").unwrap().build_dawg(2);
+        assert!(dawg.is_empty());
+        assert!(!dawg.contains(b""));
+
+        let dawg = DenseDfaIter::new(r"a*").unwrap().build_dawg(0);
+        assert_eq!(dawg.len(), 1);
+        assert!(dawg.contains(b""));
+    }
+
+    #[test]
+    fn set() {
+        let mut set = DawgSet::new();
+        let words: Vec<Vec<u8>> = DenseDfaIter::new(r"[a-c]{3}|x[a-c]*y")
+            .unwrap()
+            .take(200)
+            .collect();
+        // insert in an order that shares and unshares paths
+        for w in words.iter().rev().step_by(2).chain(words.iter().step_by(2)) {
+            assert!(set.insert(w));
+        }
+        for w in &words {
+            assert!(set.contains(w));
+            assert!(!set.insert(w));
+        }
+        assert!(!set.contains(b"ab"));
+        assert!(!set.contains(b"xy "));
+        assert!(!set.contains(b""));
+
+        // the automaton is minimal, like the one built from the regex
+        let dawg = DenseDfaIter::new(r"[a-c]{3}|x[a-c]{0,4}y")
+            .unwrap()
+            .build_dawg(6);
+        let mut set = DawgSet::new();
+        for w in DenseDfaIter::new(r"[a-c]{3}|x[a-c]{0,4}y").unwrap() {
+            set.insert(&w);
+        }
+        assert_eq!(set.node_count(), dawg.node_count());
+
+        assert!(set.insert(b""));
+        assert!(set.contains(b""));
+    }
+
+    #[test]
+    fn set_random() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut set = DawgSet::new();
+        let mut x = HashSet::new();
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..6);
+            let w: Vec<u8> = (0..len).map(|_| rng.gen_range(b'a'..=b'c')).collect();
+            assert_eq!(set.insert(&w), x.insert(w));
+        }
+        for w in &x {
+            assert!(set.contains(w));
+        }
+        assert!(!set.contains(b"aaaaaa"));
+
+        // the minimal automaton doesn't depend on the order
+        let mut sorted: Vec<_> = x.into_iter().collect();
+        sorted.sort();
+        let mut other = DawgSet::new();
+        for w in &sorted {
+            other.insert(w);
+        }
+        assert_eq!(set.node_count(), other.node_count());
+    }
+
+    #[test]
+    fn serialize() {
+        let dawg = DenseDfaIter::new(r"(?:cat|dog|bird)s?")
+            .unwrap()
+            .build_dawg(5);
+        let bytes = dawg.to_bytes();
+        assert_eq!(Dawg::from_bytes(&bytes).unwrap(), dawg);
+
+        // truncated
+        assert!(Dawg::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        // trailing bytes
+        let mut long = bytes.clone();
+        long.push(0);
+        assert!(Dawg::from_bytes(&long).is_err());
+        // cycles
+        let looped = Dawg {
+            nodes: vec![Node {
+                accept: true,
+                edges: vec![(b'a', b'a', 0)],
+            }],
+            root: 0,
+        };
+        assert!(Dawg::from_bytes(&looped.to_bytes()).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+#![allow(clippy::result_large_err)]
+
+use regex_syntax::hir::Hir;
+
+use crate::{
+    term::{DerivBuildError, Reachable, TermId, Terms},
+    BorrowNext,
+};
+
+/// `DerivIter` will produce every possible string value that will match with the given regex,
+/// using very little memory.
+///
+/// ```
+/// use regex_utils::DerivIter;
+///
+/// let iter = DerivIter::new(r"(?:ab|a)c").unwrap();
+/// let x: Vec<Vec<u8>> = iter.collect();
+/// assert_eq!(x, [b"ac".to_vec(), b"abc".to_vec()]);
+/// ```
+///
+/// # Note
+///
+/// Regexes can be infinite (eg `a*`). Either use this iterator lazily, or limit the number
+/// of iterations.
+///
+/// Like [`NfaIter`](crate::NfaIter), output strings are not guaranteed to be unique.
+///
+/// Unicode word boundaries are not supported. Use ASCII word boundaries (`(?-u:\b)`) instead.
+///
+/// # Implementation Details
+///
+/// This walks the Antimirov partial derivatives of the parsed regex. Where the
+/// [`BrzozowskiIter`](crate::BrzozowskiIter) keeps every alternative together in one derivative,
+/// a partial derivative is a set of smaller terms, one for each way the regex can continue.
+/// This makes an NFA with at most one state per position in the regex, but without the Thompson
+/// construction: states are built as they are visited, and equal alternatives are shared
+/// rather than explored twice.
+///
+/// Like the [`BrzozowskiIter`](crate::BrzozowskiIter), once a search depth finds no matches,
+/// every partial derivative is taken to prune the terms whose look-arounds can never hold.
+pub struct DerivIter {
+    // the partial derivatives of the regex
+    terms: Terms,
+    // the term for the whole regex
+    start: TermId,
+    // the max depth we currently want to search
+    depth: usize,
+    // the max depth observed in the graph
+    max_depth: usize,
+    // (term, edge, depth)
+    stack: Vec<(TermId, u8, usize)>,
+    // the current path, with a placeholder first byte for the start
+    str: Vec<u8>,
+    // whether the current search depth found a match
+    found: bool,
+    // every partial derivative of the regex, once a search depth finds no matches
+    reachable: Option<Reachable>,
+}
+
+impl TryFrom<&Hir> for DerivIter {
+    type Error = DerivBuildError;
+
+    fn try_from(hir: &Hir) -> Result<Self, Self::Error> {
+        let mut terms = Terms::new();
+        let start = terms.hir(hir)?;
+        Ok(Self {
+            terms,
+            start,
+            depth: 0,
+            max_depth: 0,
+            stack: vec![(start, 0, 0)],
+            str: vec![],
+            found: false,
+            reachable: None,
+        })
+    }
+}
+
+impl DerivIter {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `DerivIter`.
+    ///
+    /// If you want a non-default configuration, then use the
+    /// [`regex_syntax::ParserBuilder`] to parse the [`Hir`] yourself.
+    pub fn new(pattern: &str) -> Result<Self, DerivBuildError> {
+        let hir = regex_syntax::parse(pattern)?;
+        Self::try_from(&hir)
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            let Some((current, b, depth)) = self.stack.pop() else {
+                // we didn't get any deeper. no more search space
+                if self.max_depth < self.depth {
+                    break None;
+                }
+                if !self.found && self.reachable.is_none() {
+                    // the min lengths can keep growing forever if the look-arounds never hold.
+                    // there are finitely many partial derivatives, so find which can still match
+                    self.reachable = Some(self.terms.reachable(self.start, None, true));
+                    self.max_depth = 0;
+                }
+
+                self.found = false;
+                self.depth += 1;
+                self.stack.clear();
+                self.stack.push((self.start, 0, 0));
+                continue;
+            };
+
+            // update recorded max depth
+            self.max_depth = usize::max(self.max_depth, depth);
+            self.str.truncate(depth);
+            self.str.push(b);
+            let prev = self.str[1..].last().copied();
+
+            // check we can explore deeper
+            if depth < self.depth {
+                let first = self.terms.first(current);
+                for b in (0..=255).rev().filter(|&b| first.contains(b)) {
+                    let pd = self.terms.partial_derivs(current, prev, b).to_vec();
+                    for next in pd.into_iter().rev() {
+                        let min_len = match &self.reachable {
+                            Some(reachable) => reachable.dist(&self.terms, next, Some(b)),
+                            None => self.terms.min_len(next),
+                        };
+                        if min_len == usize::MAX {
+                            continue;
+                        }
+                        // check if the next term could still match within the current depth
+                        let min_len = depth + 1 + min_len;
+                        if min_len <= self.depth {
+                            self.stack.push((next, b, depth + 1));
+                        } else {
+                            // there are longer matches to find in a later search
+                            self.max_depth = usize::max(self.max_depth, min_len);
+                        }
+                    }
+                }
+            } else if self.terms.nullable(current, prev, None) {
+                self.found = true;
+                break Some(&self.str[1..]);
+            }
+        }
+    }
+}
+
+impl BorrowNext for DerivIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        DerivIter::borrow_next(self)
+    }
+}
+
+impl Iterator for DerivIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::BrzozowskiIter;
+
+    use super::*;
+
+    #[test]
+    fn set() {
+        let iter = DerivIter::new(r"^b|(a)?|cc").unwrap();
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(
+            x,
+            [b"".to_vec(), b"a".to_vec(), b"b".to_vec(), b"cc".to_vec()]
+        );
+    }
+
+    #[test]
+    fn matches_brzozowski() {
+        for pattern in [
+            r"[a-c]{1,3}x?|foo",
+            r"(?:a?){3}",
+            r"(?:a*)*b",
+            r"[а-яё0-9]{2}",
+            r"(?m)[ab\n]{0,2}^b$(?-u:\b)",
+        ] {
+            let x: HashSet<Vec<u8>> = DerivIter::new(pattern)
+                .unwrap()
+                .take_while(|s| s.len() <= 6)
+                .collect();
+            let y: HashSet<Vec<u8>> = BrzozowskiIter::new(pattern)
+                .unwrap()
+                .take_while(|s| s.len() <= 6)
+                .collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn repeated() {
+        // both alternatives match `aa`
+        let iter = DerivIter::new(r"a*|(?:aa)*").unwrap();
+        let x: Vec<Vec<u8>> = iter.take(4).collect();
+        assert_eq!(
+            x,
+            [b"".to_vec(), b"a".to_vec(), b"aa".to_vec(), b"aa".to_vec()]
+        );
+    }
+
+    #[test]
+    fn looks_never_hold() {
+        assert_eq!(DerivIter::new(r"a*$b").unwrap().next(), None);
+        let iter = DerivIter::new(r"x|(?:a|b)*$c").unwrap();
+        assert_eq!(iter.collect::<Vec<_>>(), [b"x".to_vec()]);
+    }
+}
@@ -1,9 +1,21 @@
 #![allow(clippy::result_large_err)]
 
+use core::fmt;
+use std::{error, io, path::Path, sync::Arc};
+
 use regex_automata::{
     dfa::{dense, sparse, Automaton},
+    nfa::thompson::{self, WhichCaptures, NFA},
     util::primitives::StateID,
-    Input,
+    Input, MatchError,
+};
+
+use crate::{
+    diagnostic::build_many_checked,
+    graph::Graph,
+    profile::{ProfileReport, Profiler},
+    spill::Spill,
+    BorrowNext, BoundedIter, ByteClass, InfiniteLanguage, PatternError, SyntaxConfig,
 };
 
 /// A [`DfaIter`] using [`dense::DFA`] representation
@@ -20,48 +32,163 @@ pub type SparseDfaIter<T> = DfaIter<sparse::DFA<T>>;
 ///
 /// Because this uses a DFA, search space memory can be quite large and unbounded.
 ///
+/// Any dense or sparse DFA can be iterated, as long as it supports anchored searches (see [`DfaIter::try_new`]).
+/// That includes DFAs with acceleration enabled, so pre-built DFAs don't need to be rebuilt.
+/// The constructors in this crate only turn acceleration off because it makes the DFA slower to build
+/// and doesn't help iteration. Specialized start states and prefilters only change how searches
+/// start, so DFAs with those can be iterated too, like the forward DFA of an existing [`Regex`](regex_automata::dfa::regex::Regex).
+///
+/// A DFA with quit bytes, like one built with [`dense::Config::unicode_word_boundary`], gives up
+/// on any input that contains them, so it can't say whether those inputs match.
+/// They are skipped, and only the matches without quit bytes are produced.
+///
+/// ```
+/// use regex_automata::dfa::regex::Regex;
+/// use regex_utils::DfaIter;
+///
+/// let re = Regex::new(r"[0-9]{2}|x").unwrap();
+/// let x: Vec<Vec<u8>> = DfaIter::from(re.forward()).take(2).collect();
+/// assert_eq!(x, [b"x".to_vec(), b"00".to_vec()]);
+/// ```
+///
 /// # Implementation Details
 ///
 /// Given a `DFA` (Deterministic Finite Automaton), the iterator walks the graph
 /// of states using [`IDDFS`](https://en.wikipedia.org/wiki/Iterative_deepening_depth-first_search)
 /// to traverse through every possible state path. At each depth, if we find a match, it is returned.
+/// Paths that cannot reach a match within the current depth are not explored.
 ///
 /// The order of matches is not guaranteed, but it currently returns all strings in lexicographical byte ordering.
 pub struct DfaIter<A> {
     // the graph to search
     pub(crate) regex: A,
     // the start node of the graph
-    start: StateID,
+    pub(crate) start: StateID,
     // the max depth we currently want to search
-    depth: usize,
+    pub(crate) depth: usize,
     // the max depth observed in the graph
     max_depth: usize,
-    // (state, edge, depth)
-    stack: Vec<(StateID, u8, usize)>,
-    // the current path
+    // the max depth to search, see [`DfaIter::max_depth`]
+    limit: usize,
+    // the reachable states of the graph, and their transitions
+    pub(crate) graph: Arc<Graph>,
+    // the fewest bytes needed to reach a match from each state in the graph
+    pub(crate) dist: Arc<[usize]>,
+    // the bytes that matches must start with.
+    // if set, then the empty match is only produced when the class is empty
+    first: Option<ByteClass>,
+    // (graph index, edge, depth)
+    stack: Vec<(usize, u8, usize)>,
+    // the current path.
+    // each frame only records the byte it adds, so backtracking is a truncate and a single push,
+    // and matches can be borrowed directly without first copying the path into one buffer
     str: Vec<u8>,
+    // prefixes that the search should not continue from
+    pub(crate) prune: Option<Prune>,
+    // the bottom of the stack, if it has been moved to disk
+    spill: Option<Spill>,
+    // records the work done per depth, if profiling
+    profile: Option<Box<Profiler>>,
 }
 
-impl<A: Automaton> From<A> for DfaIter<A> {
-    fn from(dfa: A) -> Self {
-        // anchored because if we didn't anchor our search we would have an infinite amount of prefixes that were valid
-        // and that isn't very interesting
-        let start = dfa
-            .start_state_forward(&Input::new("").anchored(regex_automata::Anchored::Yes))
-            .unwrap();
+/// A predicate on prefixes, see [`DfaIter::prune_if`]
+pub(crate) type Prune = Arc<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+/// The anchored start state of the DFA
+fn try_anchored_start<A: Automaton>(dfa: &A) -> Result<StateID, MatchError> {
+    // anchored because if we didn't anchor our search we would have an infinite amount of prefixes that were valid
+    // and that isn't very interesting
+    dfa.start_state_forward(&Input::new("").anchored(regex_automata::Anchored::Yes))
+}
+
+/// The anchored start state of the DFA
+///
+/// # Panics
+///
+/// If the DFA doesn't support anchored searches
+pub(crate) fn anchored_start<A: Automaton>(dfa: &A) -> StateID {
+    try_anchored_start(dfa).expect("the DFA does not support anchored searches")
+}
 
+impl<A: Automaton> DfaIter<A> {
+    /// Create an iterator over the matches of a DFA, or return an error if the DFA can't
+    /// start an anchored search, eg because it was built with [`StartKind::Unanchored`](regex_automata::dfa::StartKind::Unanchored).
+    ///
+    /// The DFAs that this crate builds always support anchored searches,
+    /// so this is only needed for DFAs that were configured elsewhere.
+    ///
+    /// ```
+    /// use regex_automata::dfa::{dense, StartKind};
+    /// use regex_utils::DfaIter;
+    ///
+    /// let dfa = dense::Builder::new()
+    ///     .configure(dense::Config::new().start_kind(StartKind::Unanchored))
+    ///     .build(r"[a-z]")
+    ///     .unwrap();
+    /// assert!(DfaIter::try_new(dfa).is_err());
+    /// ```
+    pub fn try_new(dfa: A) -> Result<Self, MatchError> {
+        let start = try_anchored_start(&dfa)?;
+
+        let graph = Graph::new(&dfa, start);
+        let dist = graph.distances().into();
+        Ok(Self::from_parts(dfa, start, Arc::new(graph), dist))
+    }
+
+    /// Create an iterator over a graph that has already been built from `dfa`, starting at `start`
+    pub(crate) fn from_parts(
+        dfa: A,
+        start: StateID,
+        graph: Arc<Graph>,
+        dist: Arc<[usize]>,
+    ) -> Self {
         Self {
             regex: dfa,
             start,
             depth: 0,
             max_depth: 0,
-            stack: vec![(start, 0, 0)],
+            limit: usize::MAX,
+            graph,
+            dist,
+            first: None,
+            stack: vec![(0, 0, 0)],
             str: vec![],
+            prune: None,
+            spill: None,
+            profile: None,
         }
     }
 }
 
+/// # Panics
+///
+/// If the DFA doesn't support anchored searches. See [`DfaIter::try_new`]
+impl<A: Automaton> From<A> for DfaIter<A> {
+    fn from(dfa: A) -> Self {
+        Self::try_new(dfa).expect("the DFA does not support anchored searches")
+    }
+}
+
 impl DenseDfaIter<Vec<u32>> {
+    /// A builder for dense or sparse `DfaIter`s with a non-default DFA or syntax configuration.
+    ///
+    /// Unlike [`dense::Builder`], acceleration is off by default, since it doesn't help iteration.
+    ///
+    /// ```
+    /// use regex_utils::{DenseDfaIter, SyntaxConfig};
+    ///
+    /// let iter = DenseDfaIter::builder()
+    ///     .minimize(true)
+    ///     .syntax(SyntaxConfig::new().case_insensitive(true))
+    ///     .build(r"no")
+    ///     .unwrap();
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x, [b"NO".to_vec(), b"No".to_vec(), b"nO".to_vec(), b"no".to_vec()]);
+    /// ```
+    pub fn builder() -> DfaIterBuilder {
+        DfaIterBuilder::new()
+    }
+
     /// Parse the given regular expression using a default configuration and
     /// return the corresponding dense `DfaIter`.
     ///
@@ -89,6 +216,89 @@ impl DenseDfaIter<Vec<u32>> {
             .build_many(patterns)
             .map(Self::from)
     }
+
+    /// Like [`DenseDfaIter::new_many`], but if the patterns fail to compile, the error says
+    /// which pattern is to blame.
+    ///
+    /// That is the first pattern that fails on its own, or else the pattern that makes the set
+    /// fail, eg by going over a size limit. Finding it compiles the patterns again, so this is
+    /// slower to fail than [`DenseDfaIter::new_many`].
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let err = DenseDfaIter::new_many_checked(&["[a-z]+", "[0-9", "x"]).err().unwrap();
+    /// assert_eq!(err.index(), Some(1));
+    /// assert_eq!(err.pattern(), "[0-9");
+    /// ```
+    pub fn new_many_checked<P: AsRef<str>>(
+        patterns: &[P],
+    ) -> Result<Self, PatternError<dense::BuildError>> {
+        build_many_checked(patterns, Self::new_many)
+    }
+
+    /// Parse the given regular expressions using a default configuration and
+    /// return a dense multi-`DfaIter` over the strings that each pattern finds a match in,
+    /// respecting the anchors of each pattern.
+    ///
+    /// [`DenseDfaIter::new_many`] only produces strings that a pattern matches in full,
+    /// so `^` and `$` make no difference there. Here, a pattern that doesn't start with `^`
+    /// can have any text before its match, and a pattern that doesn't end with `$` can have
+    /// any text after it. The surrounding text is always valid UTF-8.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new_many_with_anchors(&[r"^[a-b]+$", r"[0-1]$"]).unwrap();
+    /// let x: Vec<Vec<u8>> = iter.take(5).collect();
+    /// assert_eq!(x, [
+    ///     b"0".to_vec(),
+    ///     b"1".to_vec(),
+    ///     b"a".to_vec(),
+    ///     b"b".to_vec(),
+    ///     b"\x000".to_vec(),
+    /// ]);
+    /// ```
+    pub fn new_many_with_anchors<P: AsRef<str>>(patterns: &[P]) -> Result<Self, dense::BuildError> {
+        Self::new_many(&with_anchors(patterns))
+    }
+
+    /// Parse the given regular expression with the given syntax options, like multi-line mode,
+    /// and return the corresponding dense `DfaIter`.
+    ///
+    /// ```
+    /// use regex_utils::{DenseDfaIter, SyntaxConfig};
+    ///
+    /// let syntax = SyntaxConfig::new().multi_line(true).dot_matches_new_line(true);
+    /// let iter = DenseDfaIter::new_with_syntax(r"^a.$", syntax).unwrap();
+    /// let x: Vec<Vec<u8>> = iter.take(2).collect();
+    /// assert_eq!(x, [b"a\x00".to_vec(), b"a\x01".to_vec()]);
+    /// ```
+    pub fn new_with_syntax(pattern: &str, syntax: SyntaxConfig) -> Result<Self, dense::BuildError> {
+        dense::DFA::builder()
+            .configure(dense::Config::new().accelerate(false))
+            .syntax(syntax)
+            .build(pattern)
+            .map(Self::from)
+    }
+
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding dense `DfaIter`, as long as the DFA uses at most `bytes` bytes.
+    ///
+    /// This lets you fall back to a different engine, like [`NfaIter`](crate::NfaIter), instead of
+    /// using too much memory.
+    ///
+    /// ```
+    /// use regex_utils::{DenseDfaIter, DfaBuildError};
+    ///
+    /// let err = DenseDfaIter::new_with_limit(r"\w{20}", 1 << 20).err().unwrap();
+    /// assert!(matches!(err, DfaBuildError::LimitExceeded { .. }));
+    ///
+    /// let iter = DenseDfaIter::new_with_limit(r"[a-z]{20}", 1 << 20).unwrap();
+    /// ```
+    pub fn new_with_limit(pattern: &str, bytes: usize) -> Result<Self, DfaBuildError> {
+        build_with_limit(pattern, bytes).map(Self::from)
+    }
 }
 
 impl SparseDfaIter<Vec<u8>> {
@@ -123,42 +333,281 @@ impl SparseDfaIter<Vec<u8>> {
             .and_then(|dense| dense.to_sparse())
             .map(Self::from)
     }
+
+    /// Like [`SparseDfaIter::new_many`], but if the patterns fail to compile, the error says
+    /// which pattern is to blame.
+    ///
+    /// See [`DenseDfaIter::new_many_checked`] for details
+    pub fn new_many_checked<P: AsRef<str>>(
+        patterns: &[P],
+    ) -> Result<Self, PatternError<dense::BuildError>> {
+        build_many_checked(patterns, Self::new_many)
+    }
+
+    /// Parse the given regular expressions using a default configuration and
+    /// return a sparse multi-`DfaIter` over the strings that each pattern finds a match in,
+    /// respecting the anchors of each pattern.
+    ///
+    /// See [`DenseDfaIter::new_many_with_anchors`] for details
+    pub fn new_many_with_anchors<P: AsRef<str>>(patterns: &[P]) -> Result<Self, dense::BuildError> {
+        Self::new_many(&with_anchors(patterns))
+    }
+
+    /// Parse the given regular expression with the given syntax options, like multi-line mode,
+    /// and return the corresponding sparse `DfaIter`.
+    ///
+    /// See [`DenseDfaIter::new_with_syntax`] for details
+    pub fn new_with_syntax(pattern: &str, syntax: SyntaxConfig) -> Result<Self, dense::BuildError> {
+        dense::DFA::builder()
+            .configure(dense::Config::new().accelerate(false))
+            .syntax(syntax)
+            .build(pattern)
+            .and_then(|dense| dense.to_sparse())
+            .map(Self::from)
+    }
+
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding sparse `DfaIter`, as long as the dense DFA it is
+    /// converted from uses at most `bytes` bytes.
+    ///
+    /// See [`DenseDfaIter::new_with_limit`] for details
+    pub fn new_with_limit(pattern: &str, bytes: usize) -> Result<Self, DfaBuildError> {
+        build_with_limit(pattern, bytes)?
+            .to_sparse()
+            .map(Self::from)
+            .map_err(DfaBuildError::Build)
+    }
+}
+
+/// Surround each pattern with any text where its anchors allow it.
+///
+/// `^` and `$` still only match at the edges of the string, so a pattern that starts or ends with
+/// them can only match with empty text around it.
+fn with_anchors<P: AsRef<str>>(patterns: &[P]) -> Vec<String> {
+    patterns
+        .iter()
+        .map(|p| format!("(?s:.)*?(?:{})(?s:.)*", p.as_ref()))
+        .collect()
+}
+
+/// A builder for [`DfaIter`]s, see [`DenseDfaIter::builder`]
+#[derive(Clone, Debug)]
+pub struct DfaIterBuilder {
+    // the DFA builder, with acceleration off
+    builder: dense::Builder,
+}
+
+impl Default for DfaIterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DfaIterBuilder {
+    /// Create a new builder with the default configuration, without acceleration
+    pub fn new() -> Self {
+        let mut builder = dense::Builder::new();
+        builder.configure(dense::Config::new().accelerate(false));
+        Self { builder }
+    }
+
+    /// Whether to group bytes that always lead to the same states into classes. See [`dense::Config::byte_classes`]
+    ///
+    /// This doesn't change the strings produced, only the size of the DFA.
+    pub fn byte_classes(mut self, yes: bool) -> Self {
+        self.builder
+            .configure(dense::Config::new().byte_classes(yes));
+        self
+    }
+
+    /// Whether to give the start states their own range of state IDs. See [`dense::Config::specialize_start_states`]
+    pub fn specialize_start_states(mut self, yes: bool) -> Self {
+        self.builder
+            .configure(dense::Config::new().specialize_start_states(yes));
+        self
+    }
+
+    /// Whether to minimize the DFA, which is slower to build but can have far fewer states.
+    /// See [`dense::Config::minimize`]
+    pub fn minimize(mut self, yes: bool) -> Self {
+        self.builder.configure(dense::Config::new().minimize(yes));
+        self
+    }
+
+    /// Apply a dense DFA configuration. Only the options that are set in `config` are changed
+    pub fn configure(mut self, config: dense::Config) -> Self {
+        self.builder.configure(config);
+        self
+    }
+
+    /// Set the syntax options, like case insensitivity
+    pub fn syntax(mut self, syntax: SyntaxConfig) -> Self {
+        self.builder.syntax(syntax);
+        self
+    }
+
+    /// Build a [`DenseDfaIter`] for the pattern
+    pub fn build(&self, pattern: &str) -> Result<DenseDfaIter<Vec<u32>>, dense::BuildError> {
+        self.builder.build(pattern).map(DfaIter::from)
+    }
+
+    /// Build a multi-pattern [`DenseDfaIter`]
+    pub fn build_many<P: AsRef<str>>(
+        &self,
+        patterns: &[P],
+    ) -> Result<DenseDfaIter<Vec<u32>>, dense::BuildError> {
+        self.builder.build_many(patterns).map(DfaIter::from)
+    }
+
+    /// Build a [`SparseDfaIter`] for the pattern
+    pub fn build_sparse(&self, pattern: &str) -> Result<SparseDfaIter<Vec<u8>>, dense::BuildError> {
+        self.builder
+            .build(pattern)
+            .and_then(|dense| dense.to_sparse())
+            .map(DfaIter::from)
+    }
+}
+
+/// The regex could not be compiled into a DFA within a size limit
+#[derive(Debug)]
+pub enum DfaBuildError {
+    /// The pattern could not be compiled into an NFA
+    Nfa(thompson::BuildError),
+    /// The NFA could not be determinized, eg because it has Unicode word boundaries
+    Build(dense::BuildError),
+    /// The DFA, or the memory needed to determinize it, would be larger than the limit
+    LimitExceeded {
+        /// The limit in bytes
+        limit: usize,
+    },
+}
+
+impl fmt::Display for DfaBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DfaBuildError::Nfa(err) => err.fmt(f),
+            DfaBuildError::Build(err) => err.fmt(f),
+            DfaBuildError::LimitExceeded { limit } => {
+                write!(f, "DFA would be larger than the limit of {limit} bytes")
+            }
+        }
+    }
+}
+
+impl error::Error for DfaBuildError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DfaBuildError::Nfa(err) => Some(err),
+            DfaBuildError::Build(err) => Some(err),
+            DfaBuildError::LimitExceeded { .. } => None,
+        }
+    }
+}
+
+/// Build a dense DFA that uses at most `bytes` bytes, both for the DFA and while determinizing it
+fn build_with_limit(pattern: &str, bytes: usize) -> Result<dense::DFA<Vec<u32>>, DfaBuildError> {
+    // DFAs don't support captures, so don't compile them
+    let nfa = NFA::compiler()
+        .configure(NFA::config().which_captures(WhichCaptures::None))
+        .build(pattern)
+        .map_err(DfaBuildError::Nfa)?;
+
+    let config = dense::Config::new()
+        .accelerate(false)
+        .dfa_size_limit(Some(bytes))
+        .determinize_size_limit(Some(bytes));
+    dense::DFA::builder()
+        .configure(config)
+        .build_from_nfa(&nfa)
+        .map_err(|err| {
+            // with a single pattern, the only other errors from determinizing are
+            // running out of states or memory
+            if nfa.look_set_any().contains_word_unicode() {
+                DfaBuildError::Build(err)
+            } else {
+                DfaBuildError::LimitExceeded { limit: bytes }
+            }
+        })
 }
 
 impl<A: Automaton> DfaIter<A> {
     /// Get the next matching string ref from this regex iterator
     pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        if let Some(profile) = &mut self.profile {
+            profile.start();
+        }
         loop {
             let Some((current, b, depth)) = self.stack.pop() else {
+                // the rest of the stack might be on disk
+                if let Some(spill) = &mut self.spill {
+                    if spill.unspill(&mut self.stack) {
+                        continue;
+                    }
+                }
+
+                if let Some(profile) = &mut self.profile {
+                    profile.lap(self.depth);
+                }
                 // we didn't get any deeper. no more search space
-                if self.max_depth < self.depth {
+                if self.max_depth < self.depth || self.depth >= self.limit {
                     break None;
                 }
 
                 self.depth += 1;
                 self.stack.clear();
-                self.stack.push((self.start, 0, 0));
+                self.stack.push((0, 0, 0));
                 continue;
             };
 
-            // update recorded max depth
-            self.max_depth = usize::max(self.max_depth, depth);
             self.str.truncate(depth);
             self.str.push(b);
+            if self
+                .prune
+                .as_ref()
+                .is_some_and(|prune| prune(&self.str[1..]))
+            {
+                continue;
+            }
+
+            // update recorded max depth
+            self.max_depth = usize::max(self.max_depth, depth);
+            if let Some(profile) = &mut self.profile {
+                profile.expand(self.depth);
+            }
 
             // check we can explore deeper
             if depth < self.depth {
-                for b in (0..=255).rev() {
-                    let next_state = self.regex.next_state(current, b);
-                    // check if the next state is valid
-                    if !self.regex.is_dead_state(next_state) {
-                        self.stack.push((next_state, b, depth + 1));
+                for &(start, end, next) in self.graph.edges[current].iter().rev() {
+                    // check if the next state can still lead to a match
+                    let dist = self.dist[next];
+                    if dist == usize::MAX {
+                        continue;
+                    }
+                    if depth + 1 + dist > self.depth {
+                        // there are longer matches to find in a later search
+                        self.max_depth = usize::max(self.max_depth, depth + 1 + dist);
+                        continue;
                     }
+                    for b in (start..=end).rev() {
+                        if depth == 0 && self.first.is_some_and(|first| !first.contains(b)) {
+                            continue;
+                        }
+                        self.stack.push((next, b, depth + 1));
+                    }
+                }
+                if let Some(spill) = &mut self.spill {
+                    spill.spill(&mut self.stack);
                 }
             } else {
                 // test that this state is final
-                let eoi_state = self.regex.next_eoi_state(current);
-                if self.regex.is_match_state(eoi_state) {
+                if depth == 0 && self.first.is_some_and(|first| !first.is_empty()) {
+                    continue;
+                }
+                if self.graph.accept[current] {
+                    if let Some(profile) = &mut self.profile {
+                        profile.matched(self.depth);
+                        profile.lap(self.depth);
+                    }
                     break Some(&self.str[1..]);
                 }
             }
@@ -166,6 +615,344 @@ impl<A: Automaton> DfaIter<A> {
     }
 }
 
+impl<A: Automaton> DfaIter<A> {
+    /// The DFA that this iterator searches, to run other queries with
+    ///
+    /// ```
+    /// use regex_automata::{dfa::Automaton, Input};
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[0-9]{3}").unwrap();
+    /// let dfa = iter.as_automaton();
+    /// assert!(dfa.try_search_fwd(&Input::new("123")).unwrap().is_some());
+    /// ```
+    pub fn as_automaton(&self) -> &A {
+        &self.regex
+    }
+
+    /// Recover the DFA that this iterator searches, to reuse it elsewhere
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let mut iter = DenseDfaIter::new(r"[0-9]{3}").unwrap();
+    /// assert_eq!(iter.by_ref().count(), 1000);
+    ///
+    /// // start again without recompiling the regex
+    /// let iter = DenseDfaIter::from(iter.into_inner());
+    /// assert_eq!(iter.count(), 1000);
+    /// ```
+    pub fn into_inner(self) -> A {
+        self.regex
+    }
+
+    /// Don't search any further from the prefixes that `prune` returns `true` for, and don't
+    /// produce them. This restarts the iteration.
+    ///
+    /// Unlike filtering the matches, this skips searching the strings that start with the
+    /// prefix, which can end an infinite iteration.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// // stop once the number has more than 2 digits
+    /// let iter = DenseDfaIter::new(r"[0-9]+").unwrap().prune_if(|prefix| prefix.len() > 2);
+    /// assert_eq!(iter.count(), 110);
+    /// ```
+    pub fn prune_if(mut self, prune: impl Fn(&[u8]) -> bool + Send + Sync + 'static) -> Self {
+        self.prune = Some(Arc::new(prune));
+        self.depth = 0;
+        self.max_depth = 0;
+        self.stack.clear();
+        self.stack.push((0, 0, 0));
+        if let Some(spill) = &mut self.spill {
+            spill.clear();
+        }
+        self
+    }
+
+    /// Only produce matches whose length is congruent to `residue` modulo `modulus`.
+    /// This restarts the iteration.
+    ///
+    /// The length is tracked in the search itself, so prefixes that can't be completed to
+    /// a match of the right length aren't explored, and the iteration still ends when the regex
+    /// has no more matches of the right length.
+    ///
+    /// # Panics
+    ///
+    /// If `residue` is not less than `modulus`
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// // whole base64 blocks
+    /// let iter = DenseDfaIter::new(r"[A-Za-z0-9+/]{0,8}").unwrap().length_mod(4, 0);
+    /// assert!(iter.take(1000).all(|s| s.len() % 4 == 0));
+    ///
+    /// let iter = DenseDfaIter::new(r"a{1,7}").unwrap().length_mod(2, 0);
+    /// assert_eq!(iter.count(), 3);
+    /// ```
+    pub fn length_mod(mut self, modulus: usize, residue: usize) -> Self {
+        assert!(
+            residue < modulus,
+            "residue {residue} is not less than modulus {modulus}"
+        );
+        let graph = self.graph.length_mod(modulus, residue);
+        self.dist = graph.distances().into();
+        self.graph = Arc::new(graph);
+        self.depth = 0;
+        self.max_depth = 0;
+        self.stack.clear();
+        self.stack.push((0, 0, 0));
+        if let Some(spill) = &mut self.spill {
+            spill.clear();
+        }
+        self
+    }
+
+    /// Continue the iteration from just after the given match, as if it had just been produced.
+    /// Returns `None` if the string is not a match.
+    ///
+    /// Matches are produced in a fixed order, so this lets a long iteration be stopped and later
+    /// resumed from the last match it produced, without producing the earlier matches again.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let x: Vec<Vec<u8>> = DenseDfaIter::new(r"[ab]+").unwrap().take(7).collect();
+    ///
+    /// let iter = DenseDfaIter::new(r"[ab]+").unwrap().resume_after(b"ab").unwrap();
+    /// let y: Vec<Vec<u8>> = iter.take(3).collect();
+    /// assert_eq!(y, x[4..]);
+    /// ```
+    pub fn resume_after(mut self, last: &[u8]) -> Option<Self> {
+        // the states along the match
+        let mut path = vec![0];
+        for &b in last {
+            let current = *path.last().unwrap();
+            let &(_, _, next) = self.graph.edges[current]
+                .iter()
+                .find(|&&(start, end, _)| (start..=end).contains(&b))?;
+            path.push(next);
+        }
+        let in_partition = match (self.first, last.first()) {
+            (None, _) => true,
+            (Some(first), None) => first.is_empty(),
+            (Some(first), Some(&b)) => first.contains(b),
+        };
+        if !self.graph.accept[*path.last().unwrap()] || !in_partition {
+            return None;
+        }
+
+        // the stack is what the search has left to explore after the match:
+        // at each depth along it, the greater bytes that can still reach a match in time
+        let depth = last.len();
+        self.depth = depth;
+        self.max_depth = depth;
+        self.stack.clear();
+        self.str.clear();
+        self.str.push(0);
+        for (i, (&current, &taken)) in path.iter().zip(last).enumerate() {
+            self.str.push(taken);
+            for &(start, end, next) in self.graph.edges[current].iter().rev() {
+                let dist = self.dist[next];
+                if dist == usize::MAX || i + 1 + dist > depth {
+                    continue;
+                }
+                for b in (start..=end).rev().filter(|&b| b > taken) {
+                    if i == 0 && self.first.is_some_and(|first| !first.contains(b)) {
+                        continue;
+                    }
+                    self.stack.push((next, b, i + 1));
+                }
+            }
+        }
+        if let Some(spill) = &mut self.spill {
+            spill.clear();
+        }
+        Some(self)
+    }
+
+    /// Keep at most `max_frames` frames of the search stack in memory, and move the rest to
+    /// a temporary file in `dir`. The file is deleted when the iterator is dropped.
+    ///
+    /// The stack grows with the length of the matches and the number of bytes that can follow
+    /// each prefix, so this bounds the memory of searching for very long matches of wide classes.
+    ///
+    /// # Panics
+    ///
+    /// Iterating panics if the file can't be written to or read from
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]{3}")
+    ///     .unwrap()
+    ///     .spill_frontier(16, std::env::temp_dir())
+    ///     .unwrap();
+    /// assert_eq!(iter.count(), 26 * 26 * 26);
+    /// ```
+    pub fn spill_frontier(mut self, max_frames: usize, dir: impl AsRef<Path>) -> io::Result<Self> {
+        self.spill = Some(Spill::new(max_frames, dir.as_ref())?);
+        Ok(self)
+    }
+
+    /// Only search for matches of up to `n` bytes, so the iteration is always finite.
+    ///
+    /// The search stops once it has searched the matches of length `n`, rather than
+    /// filtering out the longer matches after searching for them.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[ab]+").unwrap().max_depth(2);
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x.len(), 2 + 4);
+    /// ```
+    pub fn max_depth(mut self, n: usize) -> BoundedIter<Self> {
+        self.limit = n;
+        if self.depth > n {
+            // already past the bound
+            self.stack.clear();
+            if let Some(spill) = &mut self.spill {
+                spill.clear();
+            }
+        }
+        BoundedIter::new(self, n)
+    }
+
+    /// Collect the rest of the matches, or return an error instead of looping forever if the regex
+    /// matches infinitely many strings.
+    ///
+    /// Finiteness is checked on the DFA before searching, using [`DfaIter::analysis`], so languages
+    /// that [`DfaIter::prune_if`] would have made finite are still refused.
+    /// A limit set with [`DfaIter::max_depth`] always makes the iteration finite.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let x = DenseDfaIter::new(r"[ab]{1,2}").unwrap().try_collect_all().unwrap();
+    /// assert_eq!(x.len(), 2 + 4);
+    ///
+    /// assert!(DenseDfaIter::new(r"[ab]+").unwrap().try_collect_all().is_err());
+    /// ```
+    pub fn try_collect_all(self) -> Result<Vec<Vec<u8>>, InfiniteLanguage> {
+        if self.limit == usize::MAX && self.analysis().infinite {
+            return Err(InfiniteLanguage);
+        }
+        Ok(self.collect())
+    }
+
+    /// Record the time spent and the states expanded while searching for each length of match,
+    /// to see where an iteration grows faster than expected. See [`DfaIter::profile_report`]
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let mut iter = DenseDfaIter::new(r"[ab]{0,3}").unwrap().profile(true);
+    /// assert_eq!(iter.by_ref().count(), 15);
+    ///
+    /// let report = iter.profile_report().unwrap();
+    /// let matches: Vec<u64> = report.depths().iter().map(|d| d.matches).collect();
+    /// assert_eq!(matches, [1, 2, 4, 8, 0]);
+    /// // every prefix is searched again for each longer length
+    /// let states: Vec<u64> = report.depths().iter().map(|d| d.states_expanded).collect();
+    /// assert_eq!(states, [1, 3, 7, 15, 15]);
+    /// ```
+    pub fn profile(mut self, yes: bool) -> Self {
+        self.profile = yes.then(Box::default);
+        self
+    }
+
+    /// The work done per length of match so far, if [`DfaIter::profile`] is set
+    pub fn profile_report(&self) -> Option<&ProfileReport> {
+        self.profile.as_deref().map(Profiler::report)
+    }
+
+    /// Split the matches into disjoint iterators by their first byte.
+    ///
+    /// The bytes in each class lead to the same state, so each iterator continues
+    /// through the same part of the regex. If the regex matches the empty string,
+    /// then that is produced by its own iterator, with an empty class.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[0-9]+|[a-z]").unwrap();
+    /// let parts = iter.partition_by_first();
+    /// assert_eq!(parts.len(), 2);
+    ///
+    /// let (class, digits) = parts.into_iter().next().unwrap();
+    /// assert!(class.contains(b'7'));
+    /// assert_eq!(digits.take(2).collect::<Vec<_>>(), [b"0".to_vec(), b"1".to_vec()]);
+    /// ```
+    pub fn partition_by_first(&self) -> Vec<(ByteClass, DfaIter<&A>)> {
+        let mut classes: Vec<(usize, ByteClass)> = vec![];
+        for &(start, end, next) in &self.graph.edges[0] {
+            if self.dist[next] == usize::MAX {
+                continue;
+            }
+            match classes.iter_mut().find(|(s, _)| *s == next) {
+                Some((_, class)) => (start..=end).for_each(|b| class.insert(b)),
+                None => classes.push((next, ByteClass::from_iter(start..=end))),
+            }
+        }
+
+        if self.graph.accept[0] {
+            classes.insert(0, (0, ByteClass::empty()));
+        }
+
+        classes
+            .into_iter()
+            .map(|(_, class)| {
+                let iter = DfaIter {
+                    first: Some(class),
+                    ..self.restarted()
+                };
+                (class, iter)
+            })
+            .collect()
+    }
+
+    /// A new iterator over the same matches as this one, from the start
+    pub(crate) fn restarted(&self) -> DfaIter<&A> {
+        DfaIter {
+            regex: &self.regex,
+            start: self.start,
+            depth: 0,
+            max_depth: 0,
+            limit: self.limit,
+            graph: Arc::clone(&self.graph),
+            dist: Arc::clone(&self.dist),
+            first: self.first,
+            stack: vec![(0, 0, 0)],
+            str: vec![],
+            prune: self.prune.clone(),
+            spill: None,
+            profile: None,
+        }
+    }
+
+    /// The graph that this iterator searches and its distances, with the restriction on the
+    /// first byte from [`DfaIter::partition_by_first`] built into the graph
+    pub(crate) fn search_graph(&self) -> (Arc<Graph>, Arc<[usize]>) {
+        match &self.first {
+            None => (Arc::clone(&self.graph), Arc::clone(&self.dist)),
+            Some(first) => {
+                let graph = self.graph.restrict_first(first);
+                let dist = graph.distances().into();
+                (Arc::new(graph), dist)
+            }
+        }
+    }
+}
+
+impl<A: Automaton> BorrowNext for DfaIter<A> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        DfaIter::borrow_next(self)
+    }
+}
+
 impl<A: Automaton> Iterator for DfaIter<A> {
     type Item = Vec<u8>;
 
@@ -199,6 +986,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn limit() {
+        let err = SparseDfaIter::new_with_limit(r"\w{20}", 1 << 20)
+            .err()
+            .unwrap();
+        assert!(matches!(err, DfaBuildError::LimitExceeded { limit } if limit == 1 << 20));
+
+        let err = DenseDfaIter::new_with_limit(r"(", 1 << 20).err().unwrap();
+        assert!(matches!(err, DfaBuildError::Nfa(_)));
+
+        let err = DenseDfaIter::new_with_limit(r"\b", 1 << 20).err().unwrap();
+        assert!(matches!(err, DfaBuildError::Build(_)));
+
+        let iter = SparseDfaIter::new_with_limit(r"[0-9]{2}", 1 << 20).unwrap();
+        assert_eq!(iter.count(), 100);
+    }
+
     #[test]
     fn finite() {
         let dfa = DFA::new(r"[0-1]{4}-[0-1]{2}-[0-1]{2}").unwrap();
@@ -297,4 +1101,345 @@ mod tests {
         ];
         assert_eq!(x, y);
     }
+
+    #[test]
+    fn partition() {
+        let iter = DenseDfaIter::new(r"(?:[a-c][0-9]|[d-f]x)?").unwrap();
+        let parts = iter.partition_by_first();
+
+        let classes: Vec<String> = parts.iter().map(|(c, _)| format!("{c:?}")).collect();
+        assert_eq!(classes, ["[]", "[a-c]", "[d-f]"]);
+
+        let mut x: Vec<Vec<u8>> = vec![];
+        for (class, part) in parts {
+            let part: Vec<Vec<u8>> = part.collect();
+            assert!(part
+                .iter()
+                .all(|s| s.first().map_or(class.is_empty(), |&b| class.contains(b))));
+            x.extend(part);
+        }
+        x.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+        assert_eq!(x, iter.collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn prune() {
+        // pruned prefixes end an infinite search
+        let iter = DenseDfaIter::new(r"(ab|b)*")
+            .unwrap()
+            .prune_if(|p| p.len() > 3);
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(x.len(), 7);
+        assert!(x.iter().all(|s| s.len() <= 3));
+
+        // matches that go through a pruned prefix are not produced
+        let iter = DenseDfaIter::new(r"[ab]{3}")
+            .unwrap()
+            .prune_if(|p| p == b"ab");
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(x.len(), 6);
+        assert!(!x.iter().any(|s| s.starts_with(b"ab")));
+
+        let mut iter = DenseDfaIter::new(r"a*").unwrap().prune_if(|_| true);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn accelerated() {
+        // bytes instead of utf8, so that states can loop on almost every byte
+        let syntax = SyntaxConfig::new().unicode(false).utf8(false);
+        for pattern in [r"[^a]*a[0-9]?", r"(?:foo|[^x]+x)*y", r"[^\n]*\n"] {
+            let dfa = DFA::builder().syntax(syntax).build(pattern).unwrap();
+            let iter = DfaIter::from(&dfa);
+            // the traversal sees accelerated states like any other
+            assert!(iter
+                .graph
+                .states
+                .iter()
+                .any(|&id| !dfa.accelerator(id).is_empty()));
+            let x: Vec<Vec<u8>> = iter.take(2000).collect();
+
+            let dense = DenseDfaIter::builder().syntax(syntax).build(pattern);
+            let y: Vec<Vec<u8>> = dense.unwrap().take(2000).collect();
+            assert_eq!(x, y, "{pattern}");
+            let sparse = dfa.to_sparse().unwrap();
+            let y: Vec<Vec<u8>> = DfaIter::from(&sparse).take(2000).collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn specialized_starts() {
+        use regex_automata::{dfa::regex::Regex, util::prefilter::Prefilter, MatchKind};
+
+        for pattern in [r"foo[0-9]{1,2}|bar", r"(?m)^ab$\n?", r"[a-c]+x?"] {
+            let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().take(500).collect();
+
+            let prefilter = Prefilter::new(MatchKind::LeftmostFirst, &["foo", "bar", "a"]);
+            let config = dense::Config::new()
+                .specialize_start_states(true)
+                .prefilter(prefilter);
+            let dfa = DFA::builder().configure(config).build(pattern).unwrap();
+            assert!(dfa.get_prefilter().is_some());
+            let y: Vec<Vec<u8>> = DfaIter::from(&dfa).take(500).collect();
+            assert_eq!(x, y, "{pattern}");
+            let sparse = dfa.to_sparse().unwrap();
+            let y: Vec<Vec<u8>> = DfaIter::from(&sparse).take(500).collect();
+            assert_eq!(x, y, "{pattern}");
+
+            let re = Regex::new(pattern).unwrap();
+            let y: Vec<Vec<u8>> = DfaIter::from(re.forward()).take(500).collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn quit() {
+        // the unicode word boundary heuristic quits on every non-ascii byte
+        let config = dense::Config::new().unicode_word_boundary(true);
+        let dfa = DFA::builder()
+            .configure(config)
+            .build(r"\b[aé]{1,2}\b")
+            .unwrap();
+        let x: Vec<Vec<u8>> = DfaIter::from(&dfa).collect();
+        assert_eq!(x, [b"a".to_vec(), b"aa".to_vec()]);
+
+        let config = dense::Config::new().quit(b'x', true);
+        let dfa = DFA::builder().configure(config).build(r"ax*|y").unwrap();
+        let x: Vec<Vec<u8>> = DfaIter::from(&dfa).collect();
+        assert_eq!(x, [b"a".to_vec(), b"y".to_vec()]);
+        let x: Vec<Vec<u8>> = DfaIter::from(dfa.to_sparse().unwrap()).collect();
+        assert_eq!(x, [b"a".to_vec(), b"y".to_vec()]);
+    }
+
+    #[test]
+    fn try_new() {
+        let config = dense::Config::new().start_kind(regex_automata::dfa::StartKind::Unanchored);
+        let dfa = dense::Builder::new().configure(config).build(r"a").unwrap();
+        assert!(DfaIter::try_new(&dfa).is_err());
+        assert!(DfaIter::try_new(dfa.to_sparse().unwrap()).is_err());
+
+        let dfa = dense::Builder::new().build(r"a").unwrap();
+        let x: Vec<Vec<u8>> = DfaIter::try_new(&dfa).unwrap().collect();
+        assert_eq!(x, [b"a".to_vec()]);
+    }
+
+    #[test]
+    #[should_panic = "the DFA does not support anchored searches"]
+    fn from_unanchored() {
+        let config = dense::Config::new().start_kind(regex_automata::dfa::StartKind::Unanchored);
+        let dfa = dense::Builder::new().configure(config).build(r"a").unwrap();
+        let _ = DfaIter::from(dfa);
+    }
+
+    #[test]
+    fn builder() {
+        let pattern = r"(?:ab|b)+c|[0-9a-f]{2}";
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern)
+            .unwrap()
+            .take_while(|s| s.len() <= 4)
+            .collect();
+
+        let builder = DenseDfaIter::builder()
+            .byte_classes(false)
+            .specialize_start_states(true)
+            .minimize(true);
+        let dense = builder.build(pattern).unwrap();
+        let default_size = DenseDfaIter::new(pattern)
+            .unwrap()
+            .as_automaton()
+            .memory_usage();
+        let minimized = DenseDfaIter::builder()
+            .minimize(true)
+            .build(pattern)
+            .unwrap();
+        assert!(minimized.as_automaton().memory_usage() < default_size);
+        let y: Vec<Vec<u8>> = dense.take_while(|s| s.len() <= 4).collect();
+        assert_eq!(x, y);
+
+        let sparse = builder.build_sparse(pattern).unwrap();
+        let y: Vec<Vec<u8>> = sparse.take_while(|s| s.len() <= 4).collect();
+        assert_eq!(x, y);
+
+        let many = DenseDfaIter::builder().build_many(&["b", "a"]).unwrap();
+        assert_eq!(many.collect::<Vec<_>>(), [b"a".to_vec(), b"b".to_vec()]);
+    }
+
+    #[test]
+    fn with_syntax() {
+        let syntax = SyntaxConfig::new().unicode(false).utf8(false);
+        let dense = DenseDfaIter::new_with_syntax(r".", syntax).unwrap();
+        let x: Vec<Vec<u8>> = dense.collect();
+        assert_eq!(x.len(), 255);
+        assert!(x.contains(&b"\xff".to_vec()));
+        assert!(!x.contains(&b"\n".to_vec()));
+
+        let syntax = syntax.case_insensitive(true).dot_matches_new_line(true);
+        let dense = DenseDfaIter::new_with_syntax(r"a.", syntax).unwrap();
+        let x: Vec<Vec<u8>> = dense.collect();
+        let sparse = SparseDfaIter::new_with_syntax(r"a.", syntax).unwrap();
+        let y: Vec<Vec<u8>> = sparse.collect();
+        assert_eq!(x, y);
+        assert_eq!(x.len(), 512);
+        assert!(x.contains(&b"A\n".to_vec()));
+
+        assert!(DenseDfaIter::new_with_syntax(r"\w", SyntaxConfig::new().unicode(false)).is_ok());
+        assert!(DenseDfaIter::new_with_syntax(r"(", SyntaxConfig::new()).is_err());
+    }
+
+    #[test]
+    fn with_anchors() {
+        let patterns = [r"^[a-b]+", r"x$", r"(?m:^)y"];
+        let dense = DenseDfaIter::new_many_with_anchors(&patterns).unwrap();
+        let x: Vec<Vec<u8>> = dense.take_while(|s| s.len() <= 2).collect();
+        let sparse = SparseDfaIter::new_many_with_anchors(&patterns).unwrap();
+        let y: Vec<Vec<u8>> = sparse.take_while(|s| s.len() <= 2).collect();
+        assert_eq!(x, y);
+
+        let starts = |s: &[u8]| s[0] == b'a' || s[0] == b'b';
+        let ends = |s: &[u8]| s.ends_with(b"x");
+        let line = |s: &[u8]| s.starts_with(b"y") || s.windows(2).any(|w| w == b"\ny");
+        assert!(x.iter().all(|s| starts(s) || ends(s) || line(s)));
+        // the text around the matches is utf8
+        assert!(!x.contains(&b"a\xff".to_vec()));
+        assert!(x.contains(&b"a\x00".to_vec()));
+        assert!(x.contains(&b"\x00x".to_vec()));
+        assert!(x.contains(&b"\ny".to_vec()));
+        assert!(!x.contains(&b"\x00a".to_vec()));
+        assert!(!x.contains(&b"x\x00".to_vec()));
+    }
+
+    #[test]
+    fn length_mod() {
+        for pattern in [r"(ab|b)*c?", r"[a-c]{1,5}|x"] {
+            let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern)
+                .unwrap()
+                .take_while(|s| s.len() <= 8)
+                .filter(|s| s.len() % 3 == 2)
+                .collect();
+            let iter = DenseDfaIter::new(pattern).unwrap().length_mod(3, 2);
+            let y: Vec<Vec<u8>> = iter.take_while(|s| s.len() <= 8).collect();
+            assert_eq!(x, y);
+        }
+
+        // no match has an odd length
+        let mut iter = DenseDfaIter::new(r"(ab)*").unwrap().length_mod(2, 1);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn spill() {
+        let dir = std::env::temp_dir();
+        for pattern in [r"[a-c]{1,4}x?", r"(ab|b)*c", r"[^\s\S]"] {
+            let iter = DenseDfaIter::new(pattern).unwrap();
+            let x: Vec<Vec<u8>> = iter.take(500).collect();
+            let iter = DenseDfaIter::new(pattern)
+                .unwrap()
+                .spill_frontier(3, &dir)
+                .unwrap();
+            let y: Vec<Vec<u8>> = iter.take(500).collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+
+        // the file is removed
+        let dir = dir.join(format!("regex-utils-spill-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut iter = DenseDfaIter::new(r"[a-z]+")
+            .unwrap()
+            .spill_frontier(4, &dir)
+            .unwrap();
+        iter.by_ref().take(100).for_each(drop);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        drop(iter);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn resume_after() {
+        for pattern in [r"[a-c]{1,3}x?", r"(ab|b)*c?", r"[^\s\S]|q"] {
+            let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().take(100).collect();
+            for (i, last) in x.iter().enumerate() {
+                let iter = DenseDfaIter::new(pattern)
+                    .unwrap()
+                    .resume_after(last)
+                    .unwrap();
+                let y: Vec<Vec<u8>> = iter.take(100 - i - 1).collect();
+                assert_eq!(y, x[i + 1..], "{pattern} after {last:?}");
+            }
+        }
+
+        let iter = DenseDfaIter::new(r"[a-c]+").unwrap();
+        assert!(iter.resume_after(b"ax").is_none());
+    }
+
+    #[test]
+    fn profile() {
+        let iter = DenseDfaIter::new(r"[a-z]+").unwrap();
+        assert!(iter.profile_report().is_none());
+
+        let mut iter = iter.profile(true);
+        assert_eq!(iter.by_ref().take(30).count(), 30);
+        let report = iter.profile_report().unwrap();
+        let matches: Vec<u64> = report.depths().iter().map(|d| d.matches).collect();
+        assert_eq!(matches, [0, 26, 4]);
+        // the second length has only been searched until the 4th match
+        let states: Vec<u64> = report.depths().iter().map(|d| d.states_expanded).collect();
+        assert_eq!(states, [1, 27, 6]);
+        assert_eq!(
+            report.elapsed(),
+            report.depths().iter().map(|d| d.elapsed).sum()
+        );
+        assert!(report
+            .to_string()
+            .starts_with("depth  elapsed  states  matches\n0  "));
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut iter = DenseDfaIter::new(r"[a-z]*")
+            .unwrap()
+            .profile(true)
+            .max_depth(2);
+        assert_eq!(iter.max_depth(), 2);
+        assert_eq!(iter.by_ref().count(), 1 + 26 + 26 * 26);
+        assert_eq!(iter.next(), None);
+
+        // nothing longer was searched
+        let iter = iter.into_inner();
+        assert_eq!(iter.profile_report().unwrap().depths().len(), 3);
+
+        // already past the bound
+        let mut iter = DenseDfaIter::new(r"[a-z]*").unwrap();
+        assert_eq!(iter.by_ref().take(30).last().unwrap(), b"ac");
+        assert_eq!(iter.max_depth(1).next(), None);
+
+        let mut iter = DenseDfaIter::new(r"[a-z]*").unwrap();
+        assert_eq!(iter.by_ref().take(30).last().unwrap(), b"ac");
+        assert_eq!(iter.max_depth(2).count(), 26 * 26 - 3);
+    }
+
+    #[test]
+    fn try_collect_all() {
+        let iter = DenseDfaIter::new(r"(?:foo|bar){1,2}").unwrap();
+        assert_eq!(iter.try_collect_all().unwrap().len(), 2 + 4);
+
+        // the rest of the matches
+        let mut iter = DenseDfaIter::new(r"[0-9]{2}").unwrap();
+        assert_eq!(iter.by_ref().take(10).count(), 10);
+        assert_eq!(iter.try_collect_all().unwrap().len(), 90);
+
+        // a cycle that can't reach a match doesn't count
+        let iter = DenseDfaIter::new(r"a|b(?:c*)(?-u:\b)d").unwrap();
+        assert_eq!(iter.try_collect_all().unwrap(), [b"a".to_vec()]);
+
+        let iter = DenseDfaIter::new(r"x(?:ab)*y").unwrap();
+        assert!(iter.try_collect_all().is_err());
+        let iter = DenseDfaIter::new(r"x(?:ab)*y").unwrap().max_depth(6);
+        assert_eq!(iter.into_inner().try_collect_all().unwrap().len(), 3);
+
+        // empty languages are finite
+        let iter = DenseDfaIter::new(r"[^\s\S]").unwrap();
+        assert!(iter.try_collect_all().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,242 @@
+#![allow(clippy::result_large_err)]
+
+use core::fmt;
+use std::{error, ops::Range};
+
+/// A regex that failed to compile, along with the pattern that caused it.
+///
+/// If the pattern has a syntax error, then [`PatternError::span`] is where in the pattern it is,
+/// and the error is displayed with the line of the pattern and a caret under the span.
+///
+/// ```
+/// use regex_utils::{DenseDfaIter, PatternError};
+///
+/// let pattern = r"[a-z]+(\d";
+/// let err = DenseDfaIter::new(pattern)
+///     .map_err(|err| PatternError::new(pattern, err))
+///     .err()
+///     .unwrap();
+/// assert_eq!(err.span(), Some(6..7));
+/// assert_eq!(
+///     err.to_string(),
+///     "regex parse error: unclosed group\n    [a-z]+(\\d\n          ^",
+/// );
+/// ```
+#[derive(Debug)]
+pub struct PatternError<E> {
+    // the pattern that failed to compile
+    pattern: String,
+    // the index of the pattern, if it was compiled along with others
+    index: Option<usize>,
+    // the error from compiling it
+    error: E,
+}
+
+impl<E: error::Error + 'static> PatternError<E> {
+    /// Attach the pattern that failed to compile to the error
+    pub fn new(pattern: impl Into<String>, error: E) -> Self {
+        Self {
+            pattern: pattern.into(),
+            index: None,
+            error,
+        }
+    }
+
+    /// Record that the pattern is at `index` in a set of patterns that were compiled together
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// The index of the pattern, if it was compiled along with others
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// The pattern that failed to compile
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The error from compiling the pattern
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Recover the error from compiling the pattern
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+
+    /// The syntax error in the pattern, if that is why it failed to compile
+    pub fn syntax_error(&self) -> Option<&regex_syntax::Error> {
+        let mut source: Option<&(dyn error::Error + 'static)> = Some(&self.error);
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<regex_syntax::Error>() {
+                return Some(err);
+            }
+            source = err.source();
+        }
+        None
+    }
+
+    /// The byte range of the syntax error in the pattern, if that is why it failed to compile
+    pub fn span(&self) -> Option<Range<usize>> {
+        let span = match self.syntax_error()? {
+            regex_syntax::Error::Parse(err) => err.span(),
+            regex_syntax::Error::Translate(err) => err.span(),
+            _ => return None,
+        };
+        Some(span.start.offset..span.end.offset)
+    }
+}
+
+impl<E: error::Error + 'static> fmt::Display for PatternError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.syntax_error() {
+            Some(regex_syntax::Error::Parse(err)) => err.kind().to_string(),
+            Some(regex_syntax::Error::Translate(err)) => err.kind().to_string(),
+            _ => {
+                write!(f, "failed to compile regex ")?;
+                if let Some(index) = self.index {
+                    write!(f, "{index} ")?;
+                }
+                return write!(f, "`{}`: {}", self.pattern, self.error);
+            }
+        };
+        write!(f, "regex parse error")?;
+        if let Some(index) = self.index {
+            write!(f, " in pattern {index}")?;
+        }
+        write!(f, ": {kind}")?;
+
+        let Some(span) = self.span() else {
+            return Ok(());
+        };
+        // the line of the pattern that the error starts on
+        let line_start = self.pattern[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.pattern[span.start..]
+            .find('\n')
+            .map_or(self.pattern.len(), |i| span.start + i);
+        let line = &self.pattern[line_start..line_end];
+
+        let column = self.pattern[line_start..span.start].chars().count();
+        let width = self.pattern[span.start..span.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        write!(
+            f,
+            "\n    {line}\n    {}{}",
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for PatternError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Build a set of patterns, and if that fails, find the pattern to blame.
+///
+/// That is the first pattern that fails on its own, or else the pattern that makes the set fail,
+/// eg by going over a size limit.
+pub(crate) fn build_many_checked<P: AsRef<str>, T, E: error::Error + 'static>(
+    patterns: &[P],
+    build: impl Fn(&[P]) -> Result<T, E>,
+) -> Result<T, PatternError<E>> {
+    let mut err = match build(patterns) {
+        Ok(built) => return Ok(built),
+        Err(err) => err,
+    };
+    for (i, pattern) in patterns.iter().enumerate() {
+        if let Err(err) = build(std::slice::from_ref(pattern)) {
+            return Err(PatternError::new(pattern.as_ref(), err).with_index(i));
+        }
+    }
+    if patterns.is_empty() {
+        return Err(PatternError::new("", err));
+    }
+
+    // every pattern builds on its own, so find the shortest prefix of the set that fails
+    let (mut lo, mut hi) = (1, patterns.len());
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        match build(&patterns[..mid]) {
+            Ok(_) => lo = mid,
+            Err(e) => (hi, err) = (mid, e),
+        }
+    }
+    Err(PatternError::new(patterns[hi - 1].as_ref(), err).with_index(hi - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::nfa::thompson::NFA;
+
+    use super::*;
+
+    #[test]
+    fn span() {
+        let pattern = "ab\nc{2,1}d";
+        let err = PatternError::new(pattern, NFA::new(pattern).unwrap_err());
+        assert_eq!(err.span(), Some(4..9));
+        assert_eq!(
+            err.to_string(),
+            "regex parse error: invalid repetition count range, the start must be <= the end\n    c{2,1}d\n     ^^^^^"
+        );
+
+        // an error from translating the parsed regex
+        let pattern = r"é(?-u:\xFF)";
+        let err = PatternError::new(pattern, NFA::new(pattern).unwrap_err());
+        assert_eq!(err.span(), Some(7..11));
+        assert!(err
+            .to_string()
+            .ends_with("\n    é(?-u:\\xFF)\n          ^^^^"));
+    }
+
+    #[test]
+    fn not_syntax() {
+        let config = NFA::config().nfa_size_limit(Some(10));
+        let pattern = r"\w{10}";
+        let err = NFA::compiler()
+            .configure(config)
+            .build(pattern)
+            .unwrap_err();
+        let err = PatternError::new(pattern, err);
+        assert!(err.syntax_error().is_none());
+        assert_eq!(err.span(), None);
+        assert!(err
+            .to_string()
+            .starts_with(r"failed to compile regex `\w{10}`: "));
+    }
+
+    #[test]
+    fn build_many_checked() {
+        let build = |patterns: &[&str]| NFA::new_many(patterns);
+        let err = super::build_many_checked(&["a", "b", "(c", "d)"], build).unwrap_err();
+        assert_eq!(err.index(), Some(2));
+        assert_eq!(err.pattern(), "(c");
+        assert!(err
+            .to_string()
+            .starts_with("regex parse error in pattern 2: unclosed group\n"));
+
+        // no pattern fails on its own, but the set is too large
+        let build = |patterns: &[&str]| {
+            NFA::compiler()
+                .configure(NFA::config().nfa_size_limit(Some(2200)))
+                .build_many(patterns)
+        };
+        let patterns = ["a", "b", "[0-9]{20}", "c", r"[a-z]{20}", "d"];
+        let err = super::build_many_checked(&patterns, build).unwrap_err();
+        assert_eq!(err.index(), Some(4));
+        assert!(err
+            .to_string()
+            .starts_with("failed to compile regex 4 `[a-z]{20}`: "));
+
+        assert!(super::build_many_checked(&["a", "b"], build).is_ok());
+    }
+}
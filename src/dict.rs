@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use regex_automata::{dfa::Automaton, util::primitives::StateID};
+
+use crate::{graph::Graph, BorrowNext, DfaIter};
+
+/// A state is considered permissive if it loops back to itself on at least this many bytes.
+/// `\w`, `.` and similar wide classes easily clear this, while small classes like `[01]` do not.
+const PERMISSIVE_LOOP_BYTES: usize = 16;
+
+#[derive(Clone, Copy)]
+enum Step {
+    Start,
+    Byte(u8),
+    Word(usize),
+}
+
+/// `DictIter` will produce matching strings like [`DfaIter`], but with dictionary words
+/// substituted into permissive parts of the regex.
+///
+/// When the traversal reaches a permissive sub-language (eg `\w+` or `.*`), rather than
+/// producing `a`, `aa`, `aaa`, ..., it will produce each dictionary word that fits.
+/// If no dictionary word fits, the permissive sub-language is explored as normal.
+///
+/// # Note
+///
+/// The iteration depth counts dictionary words as a single step, so short and long words
+/// are produced together.
+pub struct DictIter<A> {
+    // the graph to search
+    regex: A,
+    // the start node of the graph
+    start: StateID,
+    // the words to substitute into permissive states
+    words: Vec<Vec<u8>>,
+    // the states that make up the permissive sub-languages
+    wildcard: HashSet<StateID>,
+    // the max depth we currently want to search
+    depth: usize,
+    // the max depth observed in the graph
+    max_depth: usize,
+    // (state, step, byte depth, search depth, entered via a word)
+    stack: Vec<(StateID, Step, usize, usize, bool)>,
+    // the current path
+    str: Vec<u8>,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Substitute the given dictionary words into permissive parts of the regex.
+    ///
+    /// See [`DictIter`] for details
+    pub fn with_dictionary<W: AsRef<[u8]>>(
+        self,
+        words: impl IntoIterator<Item = W>,
+    ) -> DictIter<A> {
+        // a permissive sub-language is the whole cycle around a permissive state,
+        // which for unicode classes includes the states midway through a multi-byte char
+        let graph = Graph::new(&self.regex, self.start);
+        let components = graph.components();
+        let permissive: HashSet<usize> = (0..graph.states.len())
+            .filter(|&i| is_permissive(&self.regex, graph.states[i]))
+            .map(|i| components[i])
+            .collect();
+        let wildcard = (0..graph.states.len())
+            .filter(|&i| permissive.contains(&components[i]))
+            .map(|i| graph.states[i])
+            .collect();
+
+        DictIter {
+            words: words.into_iter().map(|w| w.as_ref().to_vec()).collect(),
+            wildcard,
+            start: self.start,
+            regex: self.regex,
+            depth: 0,
+            max_depth: 0,
+            stack: vec![(self.start, Step::Start, 0, 0, false)],
+            str: vec![],
+        }
+    }
+}
+
+fn is_permissive<A: Automaton>(dfa: &A, state: StateID) -> bool {
+    (0..=255)
+        .filter(|&b| dfa.next_state(state, b) == state)
+        .nth(PERMISSIVE_LOOP_BYTES - 1)
+        .is_some()
+}
+
+impl<A: Automaton> DictIter<A> {
+    /// Recover the DFA that this iterator searches, to reuse it elsewhere
+    pub fn into_inner(self) -> A {
+        self.regex
+    }
+
+    /// Walk the word from the given state, staying inside the permissive sub-language
+    fn fit_word(&self, mut state: StateID, word: &[u8]) -> Option<StateID> {
+        if word.is_empty() {
+            return None;
+        }
+        for &b in word {
+            state = self.regex.next_state(state, b);
+            if !self.wildcard.contains(&state) {
+                return None;
+            }
+        }
+        Some(state)
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            let Some((current, step, byte_depth, depth, via_word)) = self.stack.pop() else {
+                // we didn't get any deeper. no more search space
+                if self.max_depth < self.depth {
+                    break None;
+                }
+
+                self.depth += 1;
+                self.stack.clear();
+                self.stack.push((self.start, Step::Start, 0, 0, false));
+                continue;
+            };
+
+            // update recorded max depth
+            self.max_depth = usize::max(self.max_depth, depth);
+            self.str.truncate(byte_depth);
+            match step {
+                Step::Start => {}
+                Step::Byte(b) => self.str.push(b),
+                Step::Word(w) => self.str.extend_from_slice(&self.words[w]),
+            }
+            let byte_depth = self.str.len();
+
+            // check we can explore deeper
+            if depth < self.depth {
+                let mut fitted = false;
+                // a dictionary word replaces the whole permissive run, so don't chain them
+                if !via_word {
+                    for w in (0..self.words.len()).rev() {
+                        if let Some(next) = self.fit_word(current, &self.words[w]) {
+                            fitted = true;
+                            self.stack
+                                .push((next, Step::Word(w), byte_depth, depth + 1, true));
+                        }
+                    }
+                }
+
+                // push these after the words so they are explored first
+                for b in (0..=255).rev() {
+                    let next_state = self.regex.next_state(current, b);
+                    // check if the next state is valid
+                    if self.regex.is_dead_state(next_state) {
+                        continue;
+                    }
+                    // wildcard bytes are replaced with the dictionary words
+                    if (fitted || via_word) && self.wildcard.contains(&next_state) {
+                        continue;
+                    }
+                    self.stack
+                        .push((next_state, Step::Byte(b), byte_depth, depth + 1, false));
+                }
+            } else {
+                // test that this state is final
+                let eoi_state = self.regex.next_eoi_state(current);
+                if self.regex.is_match_state(eoi_state) {
+                    break Some(&self.str);
+                }
+            }
+        }
+    }
+}
+
+impl<A: Automaton> BorrowNext for DictIter<A> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        DictIter::borrow_next(self)
+    }
+}
+
+impl<A: Automaton> Iterator for DictIter<A> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DenseDfaIter;
+
+    #[test]
+    fn words() {
+        let iter = DenseDfaIter::new(r"\w+@example\.com")
+            .unwrap()
+            .with_dictionary(["alice", "bob", "not a word"]);
+
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(
+            x,
+            [b"alice@example.com".to_vec(), b"bob@example.com".to_vec()]
+        );
+    }
+
+    #[test]
+    fn fallback() {
+        // the dictionary words don't fit into this class, so it is explored as normal
+        let iter = DenseDfaIter::new(r"[0-9]+")
+            .unwrap()
+            .with_dictionary(["foo"]);
+
+        let x: Vec<Vec<u8>> = iter.take(3).collect();
+        assert_eq!(x, [b"0".to_vec(), b"1".to_vec(), b"2".to_vec()]);
+    }
+
+    #[test]
+    fn multiple() {
+        let iter = DenseDfaIter::new(r"hello \w+, from .*")
+            .unwrap()
+            .with_dictionary(["world", "regex"]);
+
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(
+            x,
+            [
+                b"hello world, from ".to_vec(),
+                b"hello regex, from ".to_vec(),
+                b"hello world, from world".to_vec(),
+                b"hello world, from regex".to_vec(),
+                b"hello regex, from world".to_vec(),
+                b"hello regex, from regex".to_vec(),
+            ]
+        );
+    }
+}
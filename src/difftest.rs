@@ -0,0 +1,388 @@
+//! Differential testing of other regex engines, with regex-automata as the oracle.
+//!
+//! ```
+//! use regex_utils::{difftest::Cases, DenseDfaIter};
+//!
+//! let pattern = r"([0-9]{2})-([a-z]+)";
+//! let mut cases = Cases::new(DenseDfaIter::new(pattern).unwrap(), pattern).unwrap();
+//!
+//! let case = cases.find(|case| case.input == b"00-ab").unwrap();
+//! assert_eq!(case.expected_match, Some(0..5));
+//! assert_eq!(case.expected_captures, [Some(0..5), Some(0..2), Some(3..5)]);
+//! ```
+
+#![allow(clippy::result_large_err)]
+
+use std::{collections::HashSet, ops::Range, sync::Arc};
+
+use regex_automata::{
+    dfa::{dense, Automaton},
+    meta,
+    util::captures::Captures,
+};
+
+use crate::{
+    graph::Graph,
+    shrink::{is_match, step},
+    BorrowNext, DenseDfaIter, DfaIter, ShrinkIter,
+};
+
+/// A generated input along with how the oracle matches it. See [`Cases`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffCase {
+    /// The input to search
+    pub input: Vec<u8>,
+    /// The span of the leftmost-first match in the input, if there is one
+    pub expected_match: Option<Range<usize>>,
+    /// The span of each capture group of the match, starting with the whole match as group 0.
+    /// Empty if there is no match
+    pub expected_captures: Vec<Option<Range<usize>>>,
+}
+
+/// `Cases` pairs the strings of a regex iterator with how regex-automata matches them,
+/// to compare other regex engines against.
+///
+/// Each input is searched for the pattern with an unanchored leftmost-first search, as with the
+/// `regex` crate. The generated strings match the whole pattern, but the leftmost-first match can
+/// still be shorter, like `a` for `a|ab` in `ab`.
+pub struct Cases<I> {
+    iter: I,
+    // the oracle
+    regex: meta::Regex,
+    // the captures of the current input
+    caps: Captures,
+}
+
+impl<I: BorrowNext> Cases<I> {
+    /// Search the strings of `iter` for `pattern`, which should be the regex that `iter` produces
+    /// the strings of, or a regex to compare against it.
+    pub fn new(iter: I, pattern: &str) -> Result<Self, meta::BuildError> {
+        Ok(Self::with_regex(iter, meta::Regex::new(pattern)?))
+    }
+
+    /// Search the strings of `iter` with `regex`, like one with a non-default configuration
+    pub fn with_regex(iter: I, regex: meta::Regex) -> Self {
+        Self {
+            caps: regex.create_captures(),
+            iter,
+            regex,
+        }
+    }
+
+    /// The regex that computes the expected results
+    pub fn regex(&self) -> &meta::Regex {
+        &self.regex
+    }
+
+    /// Recover the regex iterator that this searches the strings of
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+/// The expected results of searching `input` with `regex`
+pub(crate) fn expect(regex: &meta::Regex, caps: &mut Captures, input: &[u8]) -> DiffCase {
+    regex.captures(input, caps);
+    DiffCase {
+        input: input.to_vec(),
+        expected_match: caps.get_match().map(|m| m.range()),
+        expected_captures: caps.iter().map(|g| g.map(|s| s.range())).collect(),
+    }
+}
+
+impl<I: BorrowNext> Iterator for Cases<I> {
+    type Item = DiffCase;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.iter.borrow_next()?;
+        Some(expect(&self.regex, &mut self.caps, input))
+    }
+}
+
+/// An input that two matchers disagree on. See [`Driver`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disagreement<T> {
+    /// The minimized input
+    pub input: Vec<u8>,
+    /// Whether the input matches the regex, or is a near-miss
+    pub is_match: bool,
+    /// The output of the left matcher for the input
+    pub left: T,
+    /// The output of the right matcher for the input
+    pub right: T,
+}
+
+/// `Driver` runs two matchers over the strings of a regex, and near-misses of them,
+/// and reports the inputs they disagree on.
+///
+/// Near-misses are strings that don't match, one edit away from a match: the last byte dropped
+/// or repeated, or a byte replaced by one just outside the bytes that the regex allows there.
+/// Each disagreement is minimized with [`DfaIter::shrink`] before it is reported, so the inputs are
+/// as simple as possible.
+///
+/// ```
+/// use regex_utils::difftest::Driver;
+///
+/// let oracle = regex_automata::meta::Regex::new(r"^[0-9]{1,3}$").unwrap();
+/// // an off-by-one in the digit range
+/// let buggy = |s: &[u8]| (1..=3).contains(&s.len()) && s.iter().all(|b| (b'0'..=b':').contains(b));
+///
+/// let found = Driver::new(r"[0-9]{1,3}")
+///     .unwrap()
+///     .run(|s| oracle.is_match(s), buggy);
+/// assert_eq!(found[0].input, b":");
+/// assert!(!found[0].is_match);
+/// assert!(!found[0].left && found[0].right);
+/// ```
+pub struct Driver<A> {
+    // the strings to test
+    iter: DfaIter<A>,
+    // the most matches to test
+    cases: usize,
+    // whether to test near-misses as well
+    near_misses: bool,
+}
+
+impl<A: Automaton> From<DfaIter<A>> for Driver<A> {
+    fn from(iter: DfaIter<A>) -> Self {
+        Self {
+            iter,
+            cases: 1000,
+            near_misses: true,
+        }
+    }
+}
+
+impl Driver<dense::DFA<Vec<u32>>> {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `Driver`.
+    ///
+    /// If you want a non-default configuration, then see [`DfaIterBuilder`](crate::DfaIterBuilder).
+    pub fn new(pattern: &str) -> Result<Self, dense::BuildError> {
+        DenseDfaIter::new(pattern).map(Self::from)
+    }
+}
+
+/// An edit that turns a match into a near-miss
+#[derive(Clone, Copy)]
+enum Mutation {
+    DropLast,
+    RepeatLast,
+    // replace the byte at the index
+    Replace(usize, u8),
+}
+
+impl Mutation {
+    fn apply(self, s: &[u8]) -> Option<Vec<u8>> {
+        let mut out = s.to_vec();
+        match self {
+            Mutation::DropLast => {
+                out.pop()?;
+            }
+            Mutation::RepeatLast => out.push(*s.last()?),
+            Mutation::Replace(i, b) => *out.get_mut(i)? = b,
+        }
+        Some(out)
+    }
+}
+
+/// The near-misses of the match `s`
+fn mutations(graph: &Graph, s: &[u8]) -> Vec<Mutation> {
+    let mut out = vec![Mutation::DropLast, Mutation::RepeatLast];
+    let mut state = 0;
+    for (i, &b) in s.iter().enumerate() {
+        // the bytes just outside of each range of bytes that continue the match
+        let edges = &graph.edges[state];
+        let covered = |b: u8| edges.iter().any(|&(start, end, _)| start <= b && b <= end);
+        for &(start, end, _) in edges {
+            let below = start.checked_sub(1).filter(|&b| !covered(b));
+            let above = end.checked_add(1).filter(|&b| !covered(b));
+            out.extend(
+                below
+                    .into_iter()
+                    .chain(above)
+                    .map(|b| Mutation::Replace(i, b)),
+            );
+        }
+        state = step(graph, state, b).expect("s should be a match");
+    }
+    out
+}
+
+impl<A: Automaton> Driver<A> {
+    /// Test at most `n` matches, and their near-misses. Defaults to 1000
+    pub fn cases(mut self, n: usize) -> Self {
+        self.cases = n;
+        self
+    }
+
+    /// Whether to test near-misses of each match too. Defaults to `true`
+    pub fn near_misses(mut self, yes: bool) -> Self {
+        self.near_misses = yes;
+        self
+    }
+
+    /// Run `left` and `right` over the inputs, and return the minimized inputs that they disagree on.
+    ///
+    /// Matches are tested in shortlex order. Disagreements that minimize to the same input are only
+    /// reported once.
+    pub fn run<T, L, R>(self, mut left: L, mut right: R) -> Vec<Disagreement<T>>
+    where
+        T: PartialEq,
+        L: FnMut(&[u8]) -> T,
+        R: FnMut(&[u8]) -> T,
+    {
+        let Self {
+            mut iter,
+            cases,
+            near_misses,
+        } = self;
+        let graph = Arc::clone(&iter.graph);
+        let dist = Arc::clone(&iter.dist);
+
+        let mut seen = HashSet::new();
+        let mut found = vec![];
+        let mut report = |input: Vec<u8>, left: &mut L, right: &mut R| {
+            if seen.insert(input.clone()) {
+                found.push(Disagreement {
+                    is_match: is_match(&graph, &input),
+                    left: left(&input),
+                    right: right(&input),
+                    input,
+                });
+            }
+        };
+
+        for _ in 0..cases {
+            let Some(s) = iter.borrow_next() else { break };
+            let s = s.to_vec();
+
+            if left(&s) != right(&s) {
+                let input = minimize(&graph, &dist, s.clone(), None, &mut |s| left(s) != right(s));
+                report(input, &mut left, &mut right);
+            }
+            if !near_misses {
+                continue;
+            }
+            for mutation in mutations(&graph, &s) {
+                let Some(miss) = mutation.apply(&s) else {
+                    continue;
+                };
+                if is_match(&graph, &miss) || left(&miss) == right(&miss) {
+                    continue;
+                }
+                let input = minimize(&graph, &dist, s.clone(), Some(mutation), &mut |s| {
+                    !is_match(&graph, s) && left(s) != right(s)
+                });
+                report(input, &mut left, &mut right);
+            }
+        }
+        found
+    }
+}
+
+/// Shrink the match `source`, while its input still `differs`. Returns the minimized input
+fn minimize(
+    graph: &Arc<Graph>,
+    dist: &Arc<[usize]>,
+    mut source: Vec<u8>,
+    mutation: Option<Mutation>,
+    differs: &mut dyn FnMut(&[u8]) -> bool,
+) -> Vec<u8> {
+    let input = |s: &[u8]| match mutation {
+        Some(mutation) => mutation.apply(s),
+        None => Some(s.to_vec()),
+    };
+    loop {
+        let next = ShrinkIter::new(Arc::clone(graph), Arc::clone(dist), &source)
+            .expect("source should be a match")
+            .find(|s| input(s).is_some_and(|input| differs(&input)));
+        match next {
+            Some(next) => source = next,
+            None => return input(&source).expect("source should have differed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::{meta, util::syntax};
+
+    use crate::{DenseDfaIter, NfaIter};
+
+    use super::*;
+
+    #[test]
+    fn cases() {
+        let pattern = r"(?P<word>[a-c]+)(?:-([0-9]))?";
+        let cases: Vec<DiffCase> = Cases::new(NfaIter::new(pattern).unwrap(), pattern)
+            .unwrap()
+            .take(200)
+            .collect();
+        assert_eq!(cases.len(), 200);
+        for case in &cases {
+            let len = case.input.len();
+            assert_eq!(case.expected_match, Some(0..len));
+            assert_eq!(case.expected_captures.len(), 3);
+            assert_eq!(case.expected_captures[0], Some(0..len));
+            let dash = case.input.iter().position(|&b| b == b'-');
+            let word = dash.unwrap_or(len);
+            assert_eq!(case.expected_captures[1], Some(0..word));
+            assert_eq!(case.expected_captures[2], dash.map(|d| d + 1..len));
+        }
+    }
+
+    #[test]
+    fn other_regex() {
+        // the inputs of one regex, checked against another
+        let iter = DenseDfaIter::new(r"[a-z]{1,2}").unwrap();
+        let regex = meta::Regex::builder()
+            .syntax(syntax::Config::new().case_insensitive(true))
+            .build(r"B+")
+            .unwrap();
+        let cases: Vec<DiffCase> = Cases::with_regex(iter, regex).collect();
+        let case = cases.iter().find(|c| c.input == b"ab").unwrap();
+        assert_eq!(case.expected_match, Some(1..2));
+        let case = cases.iter().find(|c| c.input == b"aa").unwrap();
+        assert_eq!(case.expected_match, None);
+        assert!(case.expected_captures.is_empty());
+    }
+
+    #[test]
+    fn agree() {
+        let oracle = meta::Regex::new(r"^(?:[a-c]+|x{2,3})$").unwrap();
+        let found = Driver::new(r"[a-c]+|x{2,3}")
+            .unwrap()
+            .cases(200)
+            .run(|s| oracle.is_match(s), |s| oracle.is_match(s));
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn disagree() {
+        let oracle = meta::Regex::new(r"^[a-c]{2,4}[0-9]$").unwrap();
+
+        // rejects some matches
+        let found = Driver::new(r"[a-c]{2,4}[0-9]")
+            .unwrap()
+            .near_misses(false)
+            .run(
+                |s| oracle.is_match(s),
+                |s| oracle.is_match(s) && !s.contains(&b'c'),
+            );
+        // shrinking doesn't reorder bytes, so `ca0` can't reach `ac0`
+        let inputs: Vec<&[u8]> = found.iter().map(|d| &*d.input).collect();
+        assert_eq!(inputs, [&b"ac0"[..], b"ca0"]);
+        assert!(found[0].is_match);
+        assert!(found[0].left && !found[0].right);
+
+        // accepts some near-misses, which are shrunk through the match they came from
+        let found = Driver::new(r"[a-c]{2,4}[0-9]").unwrap().cases(2000).run(
+            |s| oracle.is_match(s),
+            |s| oracle.is_match(s) || (s.len() == 6 && s[5].is_ascii_digit()),
+        );
+        let inputs: Vec<&[u8]> = found.iter().map(|d| &*d.input).collect();
+        assert_eq!(inputs, [b"aaaa00"]);
+        assert!(!found[0].is_match);
+    }
+}
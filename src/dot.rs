@@ -0,0 +1,297 @@
+#![allow(clippy::result_large_err)]
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Write},
+};
+
+use regex_automata::{
+    dfa::Automaton,
+    nfa::thompson::{self, State, NFA},
+    util::primitives::StateID,
+    PatternID,
+};
+
+use crate::DfaIter;
+
+impl<A: Automaton> DfaIter<A> {
+    /// Write the states that can reach a match, and their transitions, as a
+    /// [Graphviz DOT](https://graphviz.org/doc/info/lang.html) graph.
+    ///
+    /// Each state is labelled with the label that `label` returns for its node, which is numbered
+    /// the same as in the output, or its [`StateID`] if there is none.
+    /// Accepting states are drawn with a double circle.
+    /// See [`DfaIter::group_labels`] for labels that relate the states back to the regex.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"a(b|c)").unwrap();
+    /// let labels = iter.group_labels(r"a(b|c)").unwrap();
+    ///
+    /// let mut dot = String::new();
+    /// iter.write_dot(&mut dot, |id| labels.get(&id).cloned()).unwrap();
+    /// assert!(dot.contains(r#"[label="in group 1"];"#));
+    /// assert!(dot.contains(r#"[label="b-c"];"#));
+    /// ```
+    pub fn write_dot<W: Write>(
+        &self,
+        out: &mut W,
+        mut label: impl FnMut(usize) -> Option<String>,
+    ) -> fmt::Result {
+        let graph = &self.graph;
+        let live = |i: usize| self.dist[i] != usize::MAX;
+
+        writeln!(out, "digraph regex {{")?;
+        writeln!(out, "    rankdir=LR;")?;
+        writeln!(out, "    node [shape=circle];")?;
+        for i in (0..graph.states.len()).filter(|&i| live(i)) {
+            let text = label(i).unwrap_or_else(|| format!("{}", graph.states[i].as_usize()));
+            write!(out, "    {i} [label=\"{}\"", Escape(text.as_bytes()))?;
+            if graph.accept[i] {
+                write!(out, " shape=doublecircle")?;
+            }
+            writeln!(out, "];")?;
+        }
+        for i in (0..graph.states.len()).filter(|&i| live(i)) {
+            // one edge for each target, labelled with all the bytes that lead to it
+            let mut targets: Vec<(usize, Vec<(u8, u8)>)> = vec![];
+            for &(start, end, next) in graph.edges[i].iter().filter(|e| live(e.2)) {
+                match targets.iter_mut().find(|(t, _)| *t == next) {
+                    Some((_, runs)) => runs.push((start, end)),
+                    None => targets.push((next, vec![(start, end)])),
+                }
+            }
+            for (next, runs) in targets {
+                write!(out, "    {i} -> {next} [label=\"")?;
+                for (j, &(start, end)) in runs.iter().enumerate() {
+                    if j > 0 {
+                        write!(out, ", ")?;
+                    }
+                    write!(out, "{}", Escape(&[start]))?;
+                    if end > start {
+                        write!(out, "-{}", Escape(&[end]))?;
+                    }
+                }
+                writeln!(out, "\"];")?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Describe each node that can reach a match by the capture groups of the regex that
+    /// the search is inside of there, like `in group 2`, or `in group "year"` for named groups.
+    ///
+    /// `pattern` must be the regex that this iterator was built from. It is compiled again
+    /// with its capture groups, and each state is labelled by running the shortest prefix that
+    /// leads to it through that. States that are only outside of groups are labelled `outside groups`.
+    ///
+    /// The labels are keyed by node, like in [`DfaIter::write_dot`], since one DFA state can be
+    /// several nodes, eg after [`DfaIter::length_mod`].
+    pub fn group_labels(
+        &self,
+        pattern: &str,
+    ) -> Result<HashMap<usize, String>, thompson::BuildError> {
+        let nfa = NFA::new(pattern)?;
+        let groups = enclosing_groups(&nfa);
+        let info = nfa.group_info();
+
+        let mut labels = HashMap::new();
+        for (i, prefix) in self.access_prefixes() {
+            // the innermost group of every NFA state the prefix can lead to
+            let mut inside = BTreeSet::new();
+            for id in simulate(&nfa, prefix.as_slice()) {
+                if matches!(nfa.state(id), State::Match { .. }) {
+                    continue;
+                }
+                inside.insert(groups[id.as_usize()]);
+            }
+
+            let names: Vec<String> = inside
+                .iter()
+                .flatten()
+                .map(|&(pid, group)| match info.to_name(pid, group) {
+                    Some(name) => format!("{name:?}"),
+                    None => group.to_string(),
+                })
+                .collect();
+            let label = match names.len() {
+                0 => "outside groups".to_owned(),
+                1 => format!("in group {}", names[0]),
+                _ => format!("in groups {}", names.join(", ")),
+            };
+            labels.insert(i, label);
+        }
+        Ok(labels)
+    }
+}
+
+/// The innermost capture group (pattern, group index) that each NFA state is inside of
+fn enclosing_groups(nfa: &NFA) -> Vec<Option<(PatternID, usize)>> {
+    let info = nfa.group_info();
+    let mut groups = vec![None; nfa.states().len()];
+    let mut seen = vec![false; nfa.states().len()];
+    let mut stack = vec![(nfa.start_anchored(), vec![])];
+    while let Some((id, open)) = stack.pop() {
+        if std::mem::replace(&mut seen[id.as_usize()], true) {
+            continue;
+        }
+        groups[id.as_usize()] = open.last().copied();
+        match nfa.state(id) {
+            State::ByteRange { trans } => stack.push((trans.next, open)),
+            State::Sparse(sparse) => {
+                stack.extend(sparse.transitions.iter().map(|t| (t.next, open.clone())))
+            }
+            State::Dense(dense) => stack.extend(
+                dense
+                    .transitions
+                    .iter()
+                    .filter(|&&next| next != StateID::ZERO)
+                    .map(|&next| (next, open.clone())),
+            ),
+            State::Look { next, .. } => stack.push((*next, open)),
+            State::Capture {
+                next,
+                pattern_id,
+                group_index,
+                slot,
+            } => {
+                let mut open = open;
+                let group = group_index.as_usize();
+                let start = info.slot(*pattern_id, group) == Some(slot.as_usize());
+                match (group, start) {
+                    // the whole match
+                    (0, _) => {}
+                    (_, true) => open.push((*pattern_id, group)),
+                    (_, false) => {
+                        open.pop();
+                    }
+                }
+                stack.push((*next, open));
+            }
+            State::Union { alternates } => {
+                stack.extend(alternates.iter().map(|&next| (next, open.clone())))
+            }
+            State::BinaryUnion { alt1, alt2 } => {
+                stack.extend([(*alt1, open.clone()), (*alt2, open)])
+            }
+            State::Fail | State::Match { .. } => {}
+        }
+    }
+    groups
+}
+
+/// The NFA states that consume a byte or match, after anchored searching for `prefix`.
+/// Look-around assertions are assumed to pass.
+fn simulate(nfa: &NFA, prefix: &[u8]) -> BTreeSet<StateID> {
+    let closure = |ids: Vec<StateID>| {
+        let mut out = BTreeSet::new();
+        let mut seen = BTreeSet::new();
+        let mut stack = ids;
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            match nfa.state(id) {
+                State::Look { next, .. } | State::Capture { next, .. } => stack.push(*next),
+                State::Union { alternates } => stack.extend_from_slice(alternates),
+                State::BinaryUnion { alt1, alt2 } => stack.extend([*alt1, *alt2]),
+                State::Fail => {}
+                _ => {
+                    out.insert(id);
+                }
+            }
+        }
+        out
+    };
+
+    let mut current = closure(vec![nfa.start_anchored()]);
+    for &b in prefix {
+        let next = current
+            .iter()
+            .filter_map(|&id| match nfa.state(id) {
+                State::ByteRange { trans } => trans.matches_byte(b).then_some(trans.next),
+                State::Sparse(sparse) => sparse.matches_byte(b),
+                State::Dense(dense) => dense.matches_byte(b),
+                _ => None,
+            })
+            .collect();
+        current = closure(next);
+    }
+    current
+}
+
+/// Bytes escaped for a DOT string
+struct Escape<'a>(&'a [u8]);
+
+impl fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &b in self.0 {
+            match b {
+                b'"' | b'\\' => write!(f, "\\{}", b as char)?,
+                b' '..=b'~' => f.write_char(b as char)?,
+                _ => write!(f, "\\\\x{b:02x}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DenseDfaIter;
+
+    #[test]
+    fn write_dot() {
+        let iter = DenseDfaIter::new(r#"[a-c"]\n?"#).unwrap();
+        let mut dot = String::new();
+        iter.write_dot(&mut dot, |_| None).unwrap();
+        assert!(dot.starts_with("digraph regex {\n"));
+        assert!(dot.contains(r#"0 -> 1 [label="\", a-c"];"#));
+        assert!(dot.contains(r#"1 -> 3 [label="\\x0a"];"#));
+        assert!(dot.contains("shape=doublecircle"));
+        assert!(dot.ends_with("}\n"));
+
+        let iter = DenseDfaIter::new(r"a(?-u:\b)b").unwrap();
+        let mut dot = String::new();
+        iter.write_dot(&mut dot, |_| None).unwrap();
+        assert!(!dot.contains("->"));
+    }
+
+    #[test]
+    fn group_labels() {
+        let pattern = r"x(?<year>[0-9]{2})(a(b|c))?";
+        let iter = DenseDfaIter::new(pattern).unwrap();
+        let labels = iter.group_labels(pattern).unwrap();
+
+        let prefixes = iter.access_prefixes();
+        let x: Vec<(&[u8], &str)> = prefixes
+            .iter()
+            .map(|(i, p)| (&p[..], labels[i].as_str()))
+            .collect();
+        assert_eq!(
+            x,
+            [
+                (&b""[..], "outside groups"),
+                (b"x", "in group \"year\""),
+                (b"x0", "in group \"year\""),
+                // `year` is group 1, the groups are numbered by their opening parens
+                (b"x00", "in group 2"),
+                (b"x00a", "in group 3"),
+                (b"x00ab", "outside groups"),
+            ]
+        );
+
+        // every copy of a state gets a label
+        let iter = DenseDfaIter::new(r"x(a)*").unwrap().length_mod(2, 0);
+        let labels = iter.group_labels(r"x(a)*").unwrap();
+        let states = &iter.graph.states;
+        let copies: Vec<usize> = (0..states.len())
+            .filter(|&i| labels.contains_key(&i) && states[..i].contains(&states[i]))
+            .collect();
+        assert!(!copies.is_empty());
+        assert_eq!(labels.len(), iter.access_prefixes().len());
+        assert_eq!(labels[&0], "outside groups");
+        assert!(copies.iter().all(|i| labels[i] == "in group 1"));
+    }
+}
@@ -0,0 +1,124 @@
+use encoding_rs::{EncoderResult, Encoding};
+
+use crate::{BorrowNext, Utf8Iter};
+
+impl<I: BorrowNext> Utf8Iter<I> {
+    /// Convert each match from UTF-8 into another text encoding, like Shift_JIS or windows-1252.
+    ///
+    /// Matches with characters that the encoding can't represent are skipped.
+    /// Encodings that [`encoding_rs`] can only decode, like UTF-16, produce UTF-8 instead.
+    /// See [`Encoding::output_encoding`].
+    ///
+    /// ```
+    /// use encoding_rs::SHIFT_JIS;
+    /// use regex_utils::{DenseDfaIter, Utf8Iter};
+    ///
+    /// let iter = Utf8Iter::try_from(DenseDfaIter::new(r"[aあ]").unwrap()).unwrap();
+    /// let x: Vec<Vec<u8>> = iter.encode(SHIFT_JIS).collect();
+    /// assert_eq!(x, [b"a".to_vec(), b"\x82\xa0".to_vec()]);
+    /// ```
+    pub fn encode(self, encoding: &'static Encoding) -> EncodedIter<I> {
+        EncodedIter {
+            iter: self.0,
+            encoding,
+            str: vec![],
+        }
+    }
+}
+
+/// `EncodedIter` converts the UTF-8 matches of a regex iterator into another text encoding.
+/// See [`Utf8Iter::encode`]
+pub struct EncodedIter<I> {
+    // the iterator of utf8 matches
+    iter: I,
+    // the encoding to convert the matches to
+    encoding: &'static Encoding,
+    // the current encoded match
+    str: Vec<u8>,
+}
+
+impl<I> EncodedIter<I> {
+    /// The encoding that the matches are converted to
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding.output_encoding()
+    }
+
+    /// Recover the regex iterator that this converts the matches of
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I: BorrowNext> EncodedIter<I> {
+    /// Get the next encoded match ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            let next = self.iter.borrow_next()?;
+            let next = std::str::from_utf8(next).expect("Regex should only match utf8");
+
+            // encoders can be stateful, so each match gets a new one
+            let mut encoder = self.encoding.new_encoder();
+            let len = encoder
+                .max_buffer_length_from_utf8_without_replacement(next.len())
+                .expect("match length overflowed");
+            self.str.clear();
+            self.str.reserve(len);
+            let (result, _) =
+                encoder.encode_from_utf8_to_vec_without_replacement(next, &mut self.str, true);
+            match result {
+                EncoderResult::InputEmpty => return Some(&self.str),
+                // there was enough space for the worst case
+                EncoderResult::OutputFull => unreachable!(),
+                EncoderResult::Unmappable(_) => continue,
+            }
+        }
+    }
+}
+
+impl<I: BorrowNext> BorrowNext for EncodedIter<I> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        EncodedIter::borrow_next(self)
+    }
+}
+
+impl<I: BorrowNext> Iterator for EncodedIter<I> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{ISO_2022_JP, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use crate::{DenseDfaIter, NfaIter, Utf8Iter};
+
+    #[test]
+    fn unmappable() {
+        let iter = Utf8Iter::try_from(DenseDfaIter::new(r"[é€Ωa]").unwrap()).unwrap();
+        let x: Vec<Vec<u8>> = iter.encode(WINDOWS_1252).collect();
+        // Ω isn't in windows-1252
+        assert_eq!(x, [b"a".to_vec(), b"\xe9".to_vec(), b"\x80".to_vec()]);
+    }
+
+    #[test]
+    fn stateful() {
+        let iter = Utf8Iter::try_from(NfaIter::new(r"aあ|あ").unwrap()).unwrap();
+        let x: Vec<Vec<u8>> = iter.encode(ISO_2022_JP).collect();
+        // each match switches back to ascii at the end
+        assert_eq!(
+            x,
+            [b"\x1b$B$\"\x1b(B".to_vec(), b"a\x1b$B$\"\x1b(B".to_vec()]
+        );
+    }
+
+    #[test]
+    fn output_encoding() {
+        let iter = Utf8Iter::try_from(DenseDfaIter::new(r"é").unwrap()).unwrap();
+        let mut iter = iter.encode(UTF_16LE);
+        assert_eq!(iter.encoding(), UTF_8);
+        assert_eq!(iter.borrow_next(), Some("é".as_bytes()));
+    }
+}
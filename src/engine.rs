@@ -0,0 +1,464 @@
+#![allow(clippy::result_large_err)]
+
+use core::fmt;
+use std::{
+    collections::{HashSet, VecDeque},
+    error,
+};
+
+use regex_automata::{
+    dfa::dense,
+    hybrid,
+    nfa::thompson::{self, State, NFA},
+    util::primitives::StateID,
+    Anchored, Input,
+};
+
+use crate::{BorrowNext, DenseDfaIter, HybridIter, NfaIter, SparseDfaIter};
+
+/// The regex engines that a [`RegexIter`] can use
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Engine {
+    /// Use an [`NfaIter`]
+    Nfa,
+    /// Use a [`DenseDfaIter`]
+    DenseDfa,
+    /// Use a [`SparseDfaIter`]
+    SparseDfa,
+    /// Use a [`HybridIter`]
+    Hybrid,
+}
+
+/// Patterns with at most this many NFA states, and only ASCII bytes, are small enough for a dense DFA
+const DENSE_NFA_STATES: usize = 100;
+/// Patterns with at most this many NFA states are small enough for a sparse DFA
+const SPARSE_NFA_STATES: usize = 1_000;
+/// Patterns with at most this many NFA states can still be determinized lazily.
+/// Each lazy DFA state can be as large as the NFA, so beyond this the NFA is used directly
+const HYBRID_NFA_STATES: usize = 100_000;
+
+impl Engine {
+    /// Choose an engine for the pattern, based on the size of its NFA and whether it matches non-ASCII bytes.
+    ///
+    /// Small ASCII patterns use [`Engine::DenseDfa`], which is the fastest. DFAs can be exponentially larger
+    /// than the NFA, and Unicode classes make them much larger still, so bigger patterns use
+    /// [`Engine::SparseDfa`], then [`Engine::Hybrid`], and only the very largest use [`Engine::Nfa`].
+    ///
+    /// ```
+    /// use regex_utils::Engine;
+    ///
+    /// let choice = Engine::choose(r"[a-z]+@[a-z]+\.com").unwrap();
+    /// assert_eq!(choice.engine, Engine::DenseDfa);
+    ///
+    /// let choice = Engine::choose(r"\w{20}").unwrap();
+    /// assert!(choice.non_ascii);
+    /// assert_eq!(choice.engine, Engine::Hybrid);
+    /// ```
+    pub fn choose(pattern: &str) -> Result<EngineChoice, EngineBuildError> {
+        let nfa = NFA::new(pattern).map_err(EngineBuildError::Nfa)?;
+        Ok(EngineChoice::new(&nfa))
+    }
+}
+
+/// How [`Engine::choose`] chose an engine for a pattern
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EngineChoice {
+    /// The chosen engine
+    pub engine: Engine,
+    /// The number of states in the pattern's anchored NFA
+    pub nfa_states: usize,
+    /// Whether the pattern matches any non-ASCII bytes, such as in Unicode classes
+    pub non_ascii: bool,
+}
+
+impl EngineChoice {
+    fn new(nfa: &NFA) -> Self {
+        // only the anchored states, since the unanchored prefix matches every byte
+        let mut seen = vec![false; nfa.states().len()];
+        let mut stack = vec![nfa.start_anchored()];
+        let (mut nfa_states, mut non_ascii) = (0, false);
+        while let Some(id) = stack.pop() {
+            if std::mem::replace(&mut seen[id.as_usize()], true) {
+                continue;
+            }
+            nfa_states += 1;
+            match nfa.state(id) {
+                State::ByteRange { trans } => {
+                    non_ascii |= trans.end >= 0x80;
+                    stack.push(trans.next);
+                }
+                State::Sparse(sparse) => {
+                    for trans in sparse.transitions.iter() {
+                        non_ascii |= trans.end >= 0x80;
+                        stack.push(trans.next);
+                    }
+                }
+                State::Dense(dense) => {
+                    for (b, &next) in dense.transitions.iter().enumerate() {
+                        if next != StateID::ZERO {
+                            non_ascii |= b >= 0x80;
+                            stack.push(next);
+                        }
+                    }
+                }
+                State::Look { next, .. } | State::Capture { next, .. } => stack.push(*next),
+                State::Union { alternates } => stack.extend_from_slice(alternates),
+                State::BinaryUnion { alt1, alt2 } => stack.extend([*alt1, *alt2]),
+                State::Fail | State::Match { .. } => {}
+            }
+        }
+
+        let engine = match nfa_states {
+            n if n <= DENSE_NFA_STATES && !non_ascii => Engine::DenseDfa,
+            n if n <= SPARSE_NFA_STATES => Engine::SparseDfa,
+            n if n <= HYBRID_NFA_STATES => Engine::Hybrid,
+            _ => Engine::Nfa,
+        };
+
+        Self {
+            engine,
+            nfa_states,
+            non_ascii,
+        }
+    }
+}
+
+/// Find how many bytes the dense DFA for the pattern would use, by determinizing it lazily,
+/// and give up once it would use more than `limit` bytes. Returns `None` if the DFA is too large.
+///
+/// This is a trial build, so it is not free: it builds every DFA state, just without the dense
+/// transition table or minimization. What it bounds is the cost, which grows with the size
+/// of the DFA only up to the limit, so a DFA that would use gigabytes is refused after only
+/// building `limit` bytes worth of states.
+///
+/// ```
+/// use regex_utils::{trial_dfa_size, DenseDfaIter, NfaIter};
+///
+/// // the DFA needs to remember the last 20 bytes
+/// let pattern = r"(?-u)[ab]*a[ab]{20}";
+/// if trial_dfa_size(pattern, 100_000).unwrap().is_none() {
+///     // fall back to the NFA engine
+///     let iter = NfaIter::new(pattern).unwrap();
+/// } else {
+///     # unreachable!();
+///     let iter = DenseDfaIter::new(pattern).unwrap();
+/// }
+/// ```
+pub fn trial_dfa_size(pattern: &str, limit: usize) -> Result<Option<usize>, EngineBuildError> {
+    let nfa = NFA::new(pattern).map_err(EngineBuildError::Nfa)?;
+    let classes = *nfa.byte_classes();
+    // each transition is a 4 byte state ID, and each state has a power of 2 number of transitions
+    let state_size = (1 << classes.stride2()) * std::mem::size_of::<u32>();
+    // plus the dead and quit states
+    let max_states = (limit / state_size).saturating_sub(2);
+    // enough room that the cache is never cleared before counting every state
+    let capacity = max_states
+        .saturating_add(1)
+        .saturating_mul(state_size + nfa.states().len() * std::mem::size_of::<u32>());
+    let dfa = hybrid::dfa::Builder::new()
+        .configure(
+            hybrid::dfa::Config::new()
+                .cache_capacity(capacity)
+                // a small limit is reached as soon as the cache is cleared
+                .skip_cache_capacity_check(true),
+        )
+        .build_from_nfa(nfa)
+        .map_err(EngineBuildError::Hybrid)?;
+    let mut cache = dfa.create_cache();
+
+    // the dense DFA has a start state for each kind of search, and for what comes before the search
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    for anchored in [Anchored::No, Anchored::Yes] {
+        for before in ["", "\n", "a", "-"] {
+            let input = Input::new(before).range(before.len()..).anchored(anchored);
+            let start = dfa
+                .start_state_forward(&mut cache, &input)
+                .expect("the lazy DFA should support every start state");
+            if seen.insert(start) {
+                queue.push_back(start);
+            }
+        }
+    }
+
+    while let Some(current) = queue.pop_front() {
+        if seen.len() > max_states || cache.clear_count() > 0 {
+            return Ok(None);
+        }
+        for b in classes.representatives(..).filter_map(|unit| unit.as_u8()) {
+            let next = dfa
+                .next_state(&mut cache, current, b)
+                .expect("the lazy DFA should not give up on clearing its cache");
+            if !next.is_dead() && !next.is_quit() && seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    if seen.len() > max_states {
+        return Ok(None);
+    }
+
+    Ok(Some((seen.len() + 2) * state_size))
+}
+
+/// The regex could not be compiled by the chosen [`Engine`]
+#[derive(Debug)]
+pub enum EngineBuildError {
+    /// Error from [`Engine::Nfa`]
+    Nfa(thompson::BuildError),
+    /// Error from [`Engine::DenseDfa`] or [`Engine::SparseDfa`]
+    Dfa(dense::BuildError),
+    /// Error from [`Engine::Hybrid`]
+    Hybrid(hybrid::BuildError),
+}
+
+impl fmt::Display for EngineBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineBuildError::Nfa(err) => err.fmt(f),
+            EngineBuildError::Dfa(err) => err.fmt(f),
+            EngineBuildError::Hybrid(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for EngineBuildError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            EngineBuildError::Nfa(err) => Some(err),
+            EngineBuildError::Dfa(err) => Some(err),
+            EngineBuildError::Hybrid(err) => Some(err),
+        }
+    }
+}
+
+/// `RegexIter` will produce every possible string value that will match with the given regex,
+/// using an [`Engine`] chosen at runtime.
+///
+/// ```
+/// use regex_utils::{Engine, RegexIter};
+///
+/// for engine in [Engine::Nfa, Engine::DenseDfa, Engine::SparseDfa, Engine::Hybrid] {
+///     let iter = RegexIter::new(r"[ab]c", engine).unwrap();
+///     assert_eq!(iter.engine(), engine);
+///     assert_eq!(iter.collect::<Vec<_>>(), [b"ac".to_vec(), b"bc".to_vec()]);
+/// }
+/// ```
+pub struct RegexIter(Inner);
+
+enum Inner {
+    Nfa(NfaIter),
+    DenseDfa(DenseDfaIter<Vec<u32>>),
+    SparseDfa(SparseDfaIter<Vec<u8>>),
+    Hybrid(Box<HybridIter>),
+}
+
+impl RegexIter {
+    /// Parse the given regular expression using a default configuration of the given engine
+    pub fn new(pattern: &str, engine: Engine) -> Result<Self, EngineBuildError> {
+        let inner = match engine {
+            Engine::Nfa => Inner::Nfa(NfaIter::new(pattern).map_err(EngineBuildError::Nfa)?),
+            Engine::DenseDfa => {
+                Inner::DenseDfa(DenseDfaIter::new(pattern).map_err(EngineBuildError::Dfa)?)
+            }
+            Engine::SparseDfa => {
+                Inner::SparseDfa(SparseDfaIter::new(pattern).map_err(EngineBuildError::Dfa)?)
+            }
+            Engine::Hybrid => Inner::Hybrid(Box::new(
+                HybridIter::new(pattern).map_err(EngineBuildError::Hybrid)?,
+            )),
+        };
+        Ok(Self(inner))
+    }
+
+    /// Parse the given regular expression using the engine that [`Engine::choose`] picks for it
+    ///
+    /// ```
+    /// use regex_utils::{Engine, RegexIter};
+    ///
+    /// let iter = RegexIter::auto(r"[0-9]{2}").unwrap();
+    /// assert_eq!(iter.engine(), Engine::DenseDfa);
+    /// assert_eq!(iter.count(), 100);
+    /// ```
+    pub fn auto(pattern: &str) -> Result<Self, EngineBuildError> {
+        let nfa = NFA::new(pattern).map_err(EngineBuildError::Nfa)?;
+        let engine = EngineChoice::new(&nfa).engine;
+        Self::from_nfa(nfa, engine)
+    }
+
+    /// Build the given engine from an already compiled NFA
+    fn from_nfa(nfa: NFA, engine: Engine) -> Result<Self, EngineBuildError> {
+        let dense = || {
+            dense::Builder::new()
+                .configure(dense::Config::new().accelerate(false))
+                .build_from_nfa(&nfa)
+        };
+        let inner = match engine {
+            Engine::Nfa => Inner::Nfa(NfaIter::from(nfa)),
+            Engine::DenseDfa => {
+                Inner::DenseDfa(DenseDfaIter::from(dense().map_err(EngineBuildError::Dfa)?))
+            }
+            Engine::SparseDfa => Inner::SparseDfa(SparseDfaIter::from(
+                dense()
+                    .and_then(|dense| dense.to_sparse())
+                    .map_err(EngineBuildError::Dfa)?,
+            )),
+            Engine::Hybrid => Inner::Hybrid(Box::new(HybridIter::from(
+                hybrid::dfa::Builder::new()
+                    .build_from_nfa(nfa)
+                    .map_err(EngineBuildError::Hybrid)?,
+            ))),
+        };
+        Ok(Self(inner))
+    }
+
+    /// The engine this iterator uses
+    pub fn engine(&self) -> Engine {
+        match self.0 {
+            Inner::Nfa(_) => Engine::Nfa,
+            Inner::DenseDfa(_) => Engine::DenseDfa,
+            Inner::SparseDfa(_) => Engine::SparseDfa,
+            Inner::Hybrid(_) => Engine::Hybrid,
+        }
+    }
+
+    /// The NFA that this iterator searches, if the engine keeps one.
+    /// The DFA engines don't keep the NFA they were built from.
+    pub fn as_nfa(&self) -> Option<&NFA> {
+        match &self.0 {
+            Inner::Nfa(iter) => Some(iter.as_nfa()),
+            Inner::DenseDfa(_) | Inner::SparseDfa(_) => None,
+            Inner::Hybrid(iter) => Some(iter.as_nfa()),
+        }
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        match &mut self.0 {
+            Inner::Nfa(iter) => iter.borrow_next(),
+            Inner::DenseDfa(iter) => iter.borrow_next(),
+            Inner::SparseDfa(iter) => iter.borrow_next(),
+            Inner::Hybrid(iter) => iter.borrow_next(),
+        }
+    }
+}
+
+impl From<NfaIter> for RegexIter {
+    fn from(iter: NfaIter) -> Self {
+        Self(Inner::Nfa(iter))
+    }
+}
+
+impl From<DenseDfaIter<Vec<u32>>> for RegexIter {
+    fn from(iter: DenseDfaIter<Vec<u32>>) -> Self {
+        Self(Inner::DenseDfa(iter))
+    }
+}
+
+impl From<SparseDfaIter<Vec<u8>>> for RegexIter {
+    fn from(iter: SparseDfaIter<Vec<u8>>) -> Self {
+        Self(Inner::SparseDfa(iter))
+    }
+}
+
+impl From<HybridIter> for RegexIter {
+    fn from(iter: HybridIter) -> Self {
+        Self(Inner::Hybrid(Box::new(iter)))
+    }
+}
+
+impl BorrowNext for RegexIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        RegexIter::borrow_next(self)
+    }
+}
+
+impl Iterator for RegexIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose() {
+        let choice = Engine::choose(r"(?-u:\w)+").unwrap();
+        assert_eq!(choice.engine, Engine::DenseDfa);
+        assert!(!choice.non_ascii);
+
+        // small, but unicode
+        let choice = Engine::choose(r"[а-яё]{2}").unwrap();
+        assert_eq!(choice.engine, Engine::SparseDfa);
+        assert!(choice.non_ascii);
+
+        let choice = Engine::choose(r"(?-u:\w){200}").unwrap();
+        assert_eq!(choice.engine, Engine::SparseDfa);
+
+        let choice = Engine::choose(r"\w{400}").unwrap();
+        assert!(choice.nfa_states > HYBRID_NFA_STATES);
+        assert_eq!(choice.engine, Engine::Nfa);
+    }
+
+    #[test]
+    fn trial() {
+        for pattern in [
+            r"[a-z]+@[a-z]+\.com",
+            r"(?-u:\w)+",
+            r"\d{4}-\d{2}",
+            r"[а-яё]{2}",
+        ] {
+            let size = trial_dfa_size(pattern, 1 << 20).unwrap().unwrap();
+            let actual = dense::DFA::new(pattern).unwrap().memory_usage();
+            assert!(
+                actual / 2 <= size && size <= actual * 2,
+                "{pattern}: {size} vs {actual}"
+            );
+            assert_eq!(trial_dfa_size(pattern, size / 2).unwrap(), None);
+        }
+
+        assert_eq!(
+            trial_dfa_size(r"(?-u)[ab]*a[ab]{20}", 100_000).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn from_nfa() {
+        for engine in [
+            Engine::Nfa,
+            Engine::DenseDfa,
+            Engine::SparseDfa,
+            Engine::Hybrid,
+        ] {
+            let nfa = NFA::new(r"[a-c]{1,2}x?").unwrap();
+            let x: Vec<Vec<u8>> = RegexIter::from_nfa(nfa, engine).unwrap().collect();
+            let y: Vec<Vec<u8>> = RegexIter::new(r"[a-c]{1,2}x?", engine).unwrap().collect();
+            assert_eq!(x, y, "{engine:?}");
+        }
+    }
+
+    #[test]
+    fn as_nfa() {
+        let iter = RegexIter::new(r"a|b", Engine::Hybrid).unwrap();
+        assert_eq!(iter.as_nfa().unwrap().pattern_len(), 1);
+        let iter = RegexIter::new(r"a|b", Engine::Nfa).unwrap();
+        assert!(iter.as_nfa().is_some());
+        let iter = RegexIter::new(r"a|b", Engine::DenseDfa).unwrap();
+        assert!(iter.as_nfa().is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let err = RegexIter::new(r"(", Engine::Nfa).err().unwrap();
+        assert!(matches!(err, EngineBuildError::Nfa(_)));
+        let err = RegexIter::new(r"(", Engine::SparseDfa).err().unwrap();
+        assert!(matches!(err, EngineBuildError::Dfa(_)));
+        let err = RegexIter::new(r"(", Engine::Hybrid).err().unwrap();
+        assert!(matches!(err, EngineBuildError::Hybrid(_)));
+    }
+}
@@ -0,0 +1,292 @@
+use std::io::{self, Write};
+
+use crate::{BorrowNext, PatternMatch};
+
+/// The record formats of an [`Exporter`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    /// Comma separated values, with a header row.
+    ///
+    /// Fields are quoted when they need to be. The matches are written as they are,
+    /// even when they aren't UTF-8.
+    Csv,
+    /// One JSON object per line. Matches that aren't UTF-8 are written as `{"base64":"..."}`
+    Ndjson,
+}
+
+/// Writes one record per match, with the pattern it matches, its length in bytes, and its rank,
+/// which is how many matches of the same pattern came before it.
+///
+/// ```
+/// use regex_utils::{ExportFormat, Exporter, MultiIter};
+///
+/// let mut iter = MultiIter::new_many(&[r"[ab]", r"x,y"]).unwrap();
+/// let mut csv = Exporter::new(vec![], ExportFormat::Csv).unwrap();
+/// assert_eq!(iter.export_to(&mut csv, 10).unwrap(), 3);
+/// assert_eq!(
+///     String::from_utf8(csv.into_inner()).unwrap(),
+///     "pattern,pattern_text,length,rank,match\n\
+///      0,[ab],1,0,a\n\
+///      0,[ab],1,1,b\n\
+///      1,\"x,y\",3,0,\"x,y\"\n",
+/// );
+///
+/// let mut iter = MultiIter::new_many(&[r"[ab]"]).unwrap();
+/// let mut ndjson = Exporter::new(vec![], ExportFormat::Ndjson).unwrap();
+/// assert_eq!(iter.export_to(&mut ndjson, 1).unwrap(), 1);
+/// assert_eq!(
+///     ndjson.into_inner(),
+///     b"{\"pattern\":0,\"pattern_text\":\"[ab]\",\"length\":1,\"rank\":0,\"match\":\"a\"}\n",
+/// );
+/// ```
+pub struct Exporter<W> {
+    w: W,
+    format: ExportFormat,
+    // [pattern]: the number of matches of the pattern written so far
+    ranks: Vec<u64>,
+}
+
+impl<W: Write> Exporter<W> {
+    /// Create an `Exporter` that writes to `w`. This writes the CSV header straight away.
+    ///
+    /// Each record is written separately, so wrap `w` in a [`BufWriter`](std::io::BufWriter) if writes are costly.
+    pub fn new(mut w: W, format: ExportFormat) -> io::Result<Self> {
+        if format == ExportFormat::Csv {
+            w.write_all(b"pattern,pattern_text,length,rank,match\n")?;
+        }
+        Ok(Self {
+            w,
+            format,
+            ranks: vec![],
+        })
+    }
+
+    /// Write the record of a match
+    pub fn write_match(&mut self, m: PatternMatch<'_>) -> io::Result<()> {
+        let pattern = m.pattern.as_usize();
+        if self.ranks.len() <= pattern {
+            self.ranks.resize(pattern + 1, 0);
+        }
+        let rank = self.ranks[pattern];
+        self.ranks[pattern] += 1;
+
+        let w = &mut self.w;
+        match self.format {
+            ExportFormat::Csv => {
+                write!(w, "{pattern},")?;
+                write_csv_field(w, m.pattern_text.as_bytes())?;
+                write!(w, ",{},{rank},", m.bytes.len())?;
+                write_csv_field(w, m.bytes)?;
+                w.write_all(b"\n")
+            }
+            ExportFormat::Ndjson => {
+                write!(w, r#"{{"pattern":{pattern},"pattern_text":"#)?;
+                write_json_str(w, m.pattern_text)?;
+                write!(w, r#","length":{},"rank":{rank},"match":"#, m.bytes.len())?;
+                write_json_value(w, m.bytes)?;
+                w.write_all(b"}\n")
+            }
+        }
+    }
+
+    /// Flush the writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+
+    /// Recover the writer
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// Write up to `limit` of the next matches of `iter` to `w` as a JSON array. See [`BorrowNext::to_json_writer`]
+pub(crate) fn write_json<I: BorrowNext + ?Sized, W: Write>(
+    iter: &mut I,
+    mut w: W,
+    limit: usize,
+) -> io::Result<usize> {
+    w.write_all(b"[")?;
+    let mut n = 0;
+    while n < limit {
+        let Some(next) = iter.borrow_next() else {
+            break;
+        };
+        if n > 0 {
+            w.write_all(b",")?;
+        }
+        write_json_value(&mut w, next)?;
+        n += 1;
+    }
+    w.write_all(b"]")?;
+    Ok(n)
+}
+
+/// Write a match as a JSON string, or as `{"base64":"..."}` if it isn't UTF-8
+pub(crate) fn write_json_value<W: Write>(w: &mut W, s: &[u8]) -> io::Result<()> {
+    match std::str::from_utf8(s) {
+        Ok(s) => write_json_str(w, s),
+        Err(_) => {
+            w.write_all(br#"{"base64":""#)?;
+            write_base64(w, s)?;
+            w.write_all(br#""}"#)
+        }
+    }
+}
+
+/// Write `s` as a quoted JSON string
+fn write_json_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    w.write_all(b"\"")?;
+    // the start of the text that doesn't need escaping
+    let mut start = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let escape: &[u8] = match b {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0x08 => b"\\b",
+            0x0c => b"\\f",
+            0..=0x1f => b"",
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[start..i])?;
+        if escape.is_empty() {
+            write!(w, "\\u{b:04x}")?;
+        } else {
+            w.write_all(escape)?;
+        }
+        start = i + 1;
+    }
+    w.write_all(&s.as_bytes()[start..])?;
+    w.write_all(b"\"")
+}
+
+/// Write a CSV field, quoted if it contains a separator, a quote or a line break
+fn write_csv_field<W: Write>(w: &mut W, s: &[u8]) -> io::Result<()> {
+    if !s.iter().any(|b| matches!(b, b',' | b'"' | b'\n' | b'\r')) {
+        return w.write_all(s);
+    }
+    w.write_all(b"\"")?;
+    for part in s.split_inclusive(|&b| b == b'"') {
+        w.write_all(part)?;
+        // quotes are escaped by doubling them
+        if part.ends_with(b"\"") {
+            w.write_all(b"\"")?;
+        }
+    }
+    w.write_all(b"\"")
+}
+
+/// Write `s` in the standard base64 alphabet, with padding
+fn write_base64<W: Write>(w: &mut W, s: &[u8]) -> io::Result<()> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in s.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        let mut out = [b'='; 4];
+        for (i, c) in out.iter_mut().enumerate().take(chunk.len() + 1) {
+            *c = ALPHABET[(n >> (18 - 6 * i)) as usize & 63];
+        }
+        w.write_all(&out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::dfa::dense::DFA;
+
+    use crate::{
+        BorrowNext, DenseDfaIter, DfaIter, ExportFormat, Exporter, MultiIter, PipelineIter,
+        SyntaxConfig,
+    };
+
+    fn json(s: &[u8]) -> String {
+        let mut out = vec![];
+        super::write_json_value(&mut out, s).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(json(b""), r#""""#);
+        assert_eq!(json(b"a\"b\\c"), r#""a\"b\\c""#);
+        assert_eq!(json(b"\n\r\t\x08\x0c"), r#""\n\r\t\b\f""#);
+        assert_eq!(json(b"\x00\x1f\x7f"), "\"\\u0000\\u001f\x7f\"");
+        assert_eq!(json("é☃".as_bytes()), "\"é☃\"");
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(json(b"\xff"), r#"{"base64":"/w=="}"#);
+        assert_eq!(json(b"\xffa"), r#"{"base64":"/2E="}"#);
+        assert_eq!(json(b"\xffab"), r#"{"base64":"/2Fi"}"#);
+        assert_eq!(json(b"\xffabc"), r#"{"base64":"/2FiYw=="}"#);
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = vec![];
+        for s in [&b"abc"[..], b"a,b", b"say \"hi\"", b"a\nb", b""] {
+            super::write_csv_field(&mut out, s).unwrap();
+            out.push(b';');
+        }
+        assert_eq!(out, b"abc;\"a,b\";\"say \"\"hi\"\"\";\"a\nb\";;");
+    }
+
+    #[test]
+    fn export() {
+        let patterns = [r"[ab]", r"x|(y)"];
+        let mut iter = MultiIter::new_many(&patterns).unwrap();
+        let mut ndjson = Exporter::new(vec![], ExportFormat::Ndjson).unwrap();
+        assert_eq!(iter.export_to(&mut ndjson, 10).unwrap(), 4);
+        let out = String::from_utf8(ndjson.into_inner()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"pattern":0,"pattern_text":"[ab]","length":1,"rank":0,"match":"a"}"#,
+                r#"{"pattern":0,"pattern_text":"[ab]","length":1,"rank":1,"match":"b"}"#,
+                r#"{"pattern":1,"pattern_text":"x|(y)","length":1,"rank":0,"match":"x"}"#,
+                r#"{"pattern":1,"pattern_text":"x|(y)","length":1,"rank":1,"match":"y"}"#,
+            ]
+        );
+
+        // the limit is across all patterns, and ranks carry on between calls
+        let mut iter = PipelineIter::with_threads(&[r"a+"], 1);
+        let mut csv = Exporter::new(vec![], ExportFormat::Csv).unwrap();
+        assert_eq!(iter.export_to(&mut csv, 2).unwrap(), 2);
+        assert_eq!(iter.export_to(&mut csv, 1).unwrap(), 1);
+        csv.flush().unwrap();
+        assert_eq!(
+            csv.into_inner(),
+            b"pattern,pattern_text,length,rank,match\n0,a+,1,0,a\n0,a+,2,1,aa\n0,a+,3,2,aaa\n"
+        );
+    }
+
+    #[test]
+    fn writer() {
+        let mut out = vec![];
+        let syntax = SyntaxConfig::new().unicode(false).utf8(false);
+        let dfa = DFA::builder().syntax(syntax).build(r#"a|"|\xff"#).unwrap();
+        let mut iter = DfaIter::from(&dfa);
+        assert_eq!(iter.to_json_writer(&mut out, 10).unwrap(), 3);
+        assert_eq!(out, br#"["\"","a",{"base64":"/w=="}]"#);
+
+        // the limit stops infinite languages, and the iterator can carry on
+        let mut iter = DenseDfaIter::new(r"a+").unwrap();
+        let mut out = vec![];
+        assert_eq!(iter.to_json_writer(&mut out, 2).unwrap(), 2);
+        assert_eq!(out, br#"["a","aa"]"#);
+        let mut out = vec![];
+        assert_eq!(iter.to_json_writer(&mut out, 0).unwrap(), 0);
+        assert_eq!(out, b"[]");
+        assert_eq!(iter.borrow_next(), Some(&b"aaa"[..]));
+    }
+}
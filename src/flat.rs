@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use crate::BorrowNext;
+
+/// Matching strings stored one after another in a single buffer, with a table of where each starts.
+///
+/// This makes one allocation for all of the strings, rather than one for each,
+/// which adds up when generating millions of short matches. See [`BorrowNext::collect_flat`]
+///
+/// ```
+/// use regex_utils::{BorrowNext, DenseDfaIter, FlatMatches};
+///
+/// let matches = DenseDfaIter::new(r"[ab]{1,2}").unwrap().collect_flat();
+/// assert_eq!(matches.len(), 6);
+/// assert_eq!(matches.get(3), Some(&b"ab"[..]));
+/// assert_eq!(matches.bytes(), b"abaaabbabb");
+///
+/// // more matches can be appended in chunks
+/// let mut iter = DenseDfaIter::new(r"[0-9]+").unwrap();
+/// let mut matches = FlatMatches::new();
+/// matches.extend_from(&mut iter, 10);
+/// matches.extend_from(&mut iter, 100);
+/// assert_eq!(matches.len(), 110);
+/// assert_eq!(matches.iter().last(), Some(&b"99"[..]));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlatMatches {
+    // every match, concatenated
+    bytes: Vec<u8>,
+    // [i]: where match `i` starts, with one extra entry for the end of the last match
+    offsets: Vec<usize>,
+}
+
+impl Default for FlatMatches {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlatMatches {
+    /// Create an empty `FlatMatches`
+    pub fn new() -> Self {
+        Self {
+            bytes: vec![],
+            offsets: vec![0],
+        }
+    }
+
+    /// The number of matches
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Whether there are no matches
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The match at index `i`
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        self.range(i).map(|r| &self.bytes[r])
+    }
+
+    /// Where the match at index `i` is in [`FlatMatches::bytes`]
+    pub fn range(&self, i: usize) -> Option<Range<usize>> {
+        Some(*self.offsets.get(i)?..*self.offsets.get(i + 1)?)
+    }
+
+    /// Every match, concatenated
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Where each match starts in [`FlatMatches::bytes`], followed by where the last match ends.
+    ///
+    /// This is the layout of Arrow string arrays.
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Iterate over the matches
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &[u8]> + DoubleEndedIterator + '_ {
+        self.offsets.windows(2).map(|w| &self.bytes[w[0]..w[1]])
+    }
+
+    /// Add a match to the end
+    pub fn push(&mut self, s: &[u8]) {
+        self.bytes.extend_from_slice(s);
+        self.offsets.push(self.bytes.len());
+    }
+
+    /// Add up to `n` of the next matches of `iter`, and return how many were added
+    pub fn extend_from<I: BorrowNext + ?Sized>(&mut self, iter: &mut I, n: usize) -> usize {
+        let len = self.len();
+        while self.len() - len < n {
+            let Some(next) = iter.borrow_next() else {
+                break;
+            };
+            self.push(next);
+        }
+        self.len() - len
+    }
+
+    /// Remove every match, keeping the allocations
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.offsets.truncate(1);
+    }
+}
+
+impl<'a> Extend<&'a [u8]> for FlatMatches {
+    fn extend<T: IntoIterator<Item = &'a [u8]>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|s| self.push(s));
+    }
+}
+
+impl<'a> FromIterator<&'a [u8]> for FlatMatches {
+    fn from_iter<T: IntoIterator<Item = &'a [u8]>>(iter: T) -> Self {
+        let mut matches = Self::new();
+        matches.extend(iter);
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BorrowNext, NfaIter};
+
+    use super::FlatMatches;
+
+    #[test]
+    fn flat() {
+        let x: Vec<Vec<u8>> = NfaIter::new(r"|a|[bc]{3}").unwrap().collect();
+        let matches = NfaIter::new(r"|a|[bc]{3}").unwrap().collect_flat();
+        assert_eq!(matches.len(), x.len());
+        assert!(matches.iter().eq(x.iter().map(Vec::as_slice)));
+        assert_eq!(matches.get(0), Some(&b""[..]));
+        assert_eq!(matches.range(1), Some(0..1));
+        assert_eq!(matches.get(x.len()), None);
+        assert_eq!(matches.offsets().len(), x.len() + 1);
+
+        let y: FlatMatches = x.iter().map(Vec::as_slice).collect();
+        assert_eq!(matches, y);
+
+        let mut matches = matches;
+        matches.clear();
+        assert!(matches.is_empty());
+        assert_eq!(matches, FlatMatches::default());
+        assert_eq!(matches.iter().count(), 0);
+    }
+
+    #[test]
+    fn extend_from() {
+        let mut iter = NfaIter::new(r"[a-c]").unwrap();
+        let mut matches = FlatMatches::new();
+        assert_eq!(matches.extend_from(&mut iter, 2), 2);
+        assert_eq!(matches.extend_from(&mut iter, 2), 1);
+        assert_eq!(matches.extend_from(&mut iter, 2), 0);
+        assert_eq!(matches.bytes(), b"abc");
+    }
+}
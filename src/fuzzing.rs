@@ -0,0 +1,329 @@
+//! Glue for driving fuzz targets with strings that match a regex.
+//!
+//! ```
+//! use regex_utils::fuzzing::FuzzTarget;
+//!
+//! let target = FuzzTarget::new(r"[a-z]+@[a-z]+\.com").unwrap();
+//!
+//! // any input from the fuzzer maps onto a matching string
+//! let email = target.generate(b"some fuzzer input").unwrap();
+//! assert!(email.ends_with(b".com"));
+//! ```
+
+#![allow(clippy::result_large_err)]
+
+use std::{collections::HashSet, fs, io, path::Path, sync::Arc};
+
+use regex_automata::{
+    dfa::{dense, Automaton},
+    util::primitives::StateID,
+};
+
+use crate::{dfa::anchored_start, graph::Graph, DfaIter};
+
+/// `FuzzTarget` deterministically maps arbitrary fuzzer input bytes onto strings that match
+/// the given regex.
+///
+/// Each input byte chooses the next transition to take through the DFA, or whether to stop at an
+/// accepting state. Once the input runs out, the shortest remaining path to an accepting state is taken.
+/// This means that similar inputs produce similar outputs, which lets coverage guided fuzzers
+/// like libFuzzer or AFL explore the language effectively.
+pub struct FuzzTarget<A> {
+    // the graph to search
+    regex: A,
+    // the start node of the graph
+    start: StateID,
+    // the reachable states of the graph
+    graph: Arc<Graph>,
+    // the distance from each state to an accepting state
+    dist: Arc<[usize]>,
+}
+
+impl<A: Automaton> From<A> for FuzzTarget<A> {
+    fn from(dfa: A) -> Self {
+        let start = anchored_start(&dfa);
+        let graph = Graph::new(&dfa, start);
+        let dist = graph.distances().into();
+        Self {
+            regex: dfa,
+            start,
+            graph: Arc::new(graph),
+            dist,
+        }
+    }
+}
+
+impl FuzzTarget<dense::DFA<Vec<u32>>> {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `FuzzTarget`.
+    ///
+    /// If you want a non-default configuration, then use the
+    /// [`dense::Builder`](dense::Builder) to set your own configuration.
+    pub fn new(pattern: &str) -> Result<Self, dense::BuildError> {
+        dense::DFA::builder()
+            .configure(dense::Config::new().accelerate(false))
+            .build(pattern)
+            .map(Self::from)
+    }
+}
+
+impl<A: Automaton> FuzzTarget<A> {
+    /// Recover the DFA that this target walks, to reuse it elsewhere
+    pub fn into_inner(self) -> A {
+        self.regex
+    }
+
+    /// Map the fuzzer input onto a matching string.
+    ///
+    /// Returns `None` if the regex matches no strings at all
+    pub fn generate(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let mut out = vec![];
+        self.generate_into(data, &mut out).then_some(out)
+    }
+
+    /// Map the fuzzer input onto a matching string, written into `out`.
+    ///
+    /// Returns `false` if the regex matches no strings at all
+    pub fn generate_into(&self, data: &[u8], out: &mut Vec<u8>) -> bool {
+        out.clear();
+        let mut current = 0;
+        if self.dist[current] == usize::MAX {
+            return false;
+        }
+
+        for &choice in data {
+            // option 0 is to stop, if we can
+            let stop = self.graph.accept[current] as usize;
+            let options = stop
+                + self
+                    .live_bytes(current)
+                    .map(|(b, _)| b.len())
+                    .sum::<usize>();
+            let mut choice = choice as usize % options;
+            if choice < stop {
+                return true;
+            }
+            choice -= stop;
+
+            for (bytes, next) in self.live_bytes(current) {
+                if choice < bytes.len() {
+                    out.push(bytes.start() + choice as u8);
+                    current = next;
+                    break;
+                }
+                choice -= bytes.len();
+            }
+        }
+
+        // out of input, take the shortest way out
+        while self.dist[current] > 0 {
+            let (bytes, next) = self
+                .live_bytes(current)
+                .find(|&(_, next)| self.dist[next] + 1 == self.dist[current])
+                .expect("a live state should have a path to an accepting state");
+            out.push(*bytes.start());
+            current = next;
+        }
+        true
+    }
+
+    fn live_bytes(
+        &self,
+        state: usize,
+    ) -> impl Iterator<Item = (std::ops::RangeInclusive<u8>, usize)> + '_ {
+        self.graph.edges[state]
+            .iter()
+            .filter(|&&(_, _, t)| self.dist[t] != usize::MAX)
+            .map(|&(start, end, t)| (start..=end, t))
+    }
+
+    /// The first `n` matching strings of the regex, to seed a fuzzing corpus with.
+    pub fn corpus(&self, n: usize) -> Vec<Vec<u8>> {
+        self.iter().take(n).collect()
+    }
+
+    /// Write the first `n` matching strings of the regex as individual files into `dir`,
+    /// ready to be used as a libFuzzer or AFL seed corpus.
+    pub fn write_corpus(&self, dir: impl AsRef<Path>, n: usize) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut iter = self.iter();
+        for i in 0..n {
+            let Some(next) = iter.borrow_next() else {
+                break;
+            };
+            fs::write(dir.join(format!("seed-{i}")), next)?;
+        }
+        Ok(())
+    }
+
+    /// Iterate over the matching strings, reusing the graph that was built for this target
+    fn iter(&self) -> DfaIter<&A> {
+        DfaIter::from_parts(
+            &self.regex,
+            self.start,
+            Arc::clone(&self.graph),
+            Arc::clone(&self.dist),
+        )
+    }
+
+    /// Select a small subset of `corpus` that covers the same DFA transitions as all of it,
+    /// to keep a seed corpus small without losing coverage.
+    ///
+    /// This is a greedy set cover: it repeatedly keeps the input that covers the most transitions
+    /// that are not covered yet, preferring shorter inputs on ties. The kept inputs are returned
+    /// in their original order.
+    ///
+    /// Inputs that don't match still cover the transitions they take before they can no longer match.
+    ///
+    /// ```
+    /// use regex_utils::fuzzing::FuzzTarget;
+    ///
+    /// let target = FuzzTarget::new(r"[a-z]+(?:-[0-9]+)?").unwrap();
+    /// let corpus = ["abc", "a-1", "x", "b-22", "hello"];
+    /// assert_eq!(target.minimize_corpus(corpus), ["abc", "b-22"]);
+    /// ```
+    pub fn minimize_corpus<S: AsRef<[u8]>>(&self, corpus: impl IntoIterator<Item = S>) -> Vec<S> {
+        let mut inputs: Vec<(S, HashSet<(usize, usize)>)> = corpus
+            .into_iter()
+            .map(|s| {
+                let coverage = self.coverage(s.as_ref());
+                (s, coverage)
+            })
+            .collect();
+
+        let mut covered = HashSet::new();
+        let mut keep = vec![false; inputs.len()];
+        loop {
+            let best = inputs
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| !keep[i])
+                .map(|(i, (s, coverage))| {
+                    let new = coverage.difference(&covered).count();
+                    (i, new, s.as_ref().len())
+                })
+                .filter(|&(_, new, _)| new > 0)
+                .min_by_key(|&(i, new, len)| (std::cmp::Reverse(new), len, i));
+            let Some((i, _, _)) = best else { break };
+            keep[i] = true;
+            covered.extend(inputs[i].1.iter().copied());
+        }
+
+        let mut keep = keep.into_iter();
+        inputs.retain(|_| keep.next().unwrap_or(false));
+        inputs.into_iter().map(|(s, _)| s).collect()
+    }
+
+    /// The transitions, as (state, edge), that `s` takes through the graph
+    fn coverage(&self, s: &[u8]) -> HashSet<(usize, usize)> {
+        let mut coverage = HashSet::new();
+        let mut state = 0;
+        for &b in s {
+            let Some(edge) = self.graph.edges[state]
+                .iter()
+                .position(|&(start, end, _)| start <= b && b <= end)
+            else {
+                break;
+            };
+            let next = self.graph.edges[state][edge].2;
+            if self.dist[next] == usize::MAX {
+                break;
+            }
+            coverage.insert((state, edge));
+            state = next;
+        }
+        coverage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(target: &FuzzTarget<dense::DFA<Vec<u32>>>, s: &[u8]) -> bool {
+        let dfa = &target.regex;
+        let mut state = target.graph.states[0];
+        for &b in s {
+            state = dfa.next_state(state, b);
+        }
+        dfa.is_match_state(dfa.next_eoi_state(state))
+    }
+
+    #[test]
+    fn deterministic() {
+        let target = FuzzTarget::new(r"[a-z]{2,}-[0-9]+").unwrap();
+
+        for data in [
+            &b""[..],
+            b"\x00",
+            b"hello world",
+            b"\xff\xff\xff\xff\x01\x02",
+        ] {
+            let x = target.generate(data).unwrap();
+            assert_eq!(Some(&x), target.generate(data).as_ref());
+            assert!(is_match(&target, &x), "{x:?}");
+        }
+
+        assert_eq!(target.generate(b"").unwrap(), b"aa-0");
+    }
+
+    #[test]
+    fn stop() {
+        let target = FuzzTarget::new(r"a*").unwrap();
+
+        // stopping is the first option at an accepting state
+        assert_eq!(target.generate(b"\x01\x01\x00\x01").unwrap(), b"aa");
+    }
+
+    #[test]
+    fn empty() {
+        let target = FuzzTarget::new(r"a(?-u:\b)b").unwrap();
+        assert_eq!(target.generate(b"foo"), None);
+    }
+
+    #[test]
+    fn corpus() {
+        let target = FuzzTarget::new(r"[ab]{1,2}").unwrap();
+        assert_eq!(
+            target.corpus(4),
+            [b"a".to_vec(), b"b".to_vec(), b"aa".to_vec(), b"ab".to_vec()]
+        );
+        // the corpus is walked over the graph that the target already built
+        assert!(Arc::ptr_eq(&target.iter().graph, &target.graph));
+
+        let dir = std::env::temp_dir().join(format!("regex-utils-corpus-{}", std::process::id()));
+        target.write_corpus(&dir, 10).unwrap();
+        let mut seeds: Vec<Vec<u8>> = (0..6)
+            .map(|i| std::fs::read(dir.join(format!("seed-{i}"))).unwrap())
+            .collect();
+        assert!(!dir.join("seed-6").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+        seeds.truncate(4);
+        assert_eq!(seeds, target.corpus(4));
+    }
+
+    #[test]
+    fn minimize_corpus() {
+        let target = FuzzTarget::new(r"(?:foo|bar)[0-9]*").unwrap();
+        let corpus = [
+            "foo", "bar", "foo1", "bar12", "foo123", "fooo", "baz", "bar", "foo9",
+        ];
+        let kept = target.minimize_corpus(corpus);
+        assert_eq!(kept, ["foo", "bar12"]);
+
+        let all: HashSet<_> = corpus
+            .iter()
+            .flat_map(|s| target.coverage(s.as_bytes()))
+            .collect();
+        let some: HashSet<_> = kept
+            .iter()
+            .flat_map(|s| target.coverage(s.as_bytes()))
+            .collect();
+        assert_eq!(all, some);
+
+        assert!(target.minimize_corpus(Vec::<Vec<u8>>::new()).is_empty());
+        // the empty input covers nothing
+        assert!(target.minimize_corpus([""]).is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::{HashMap, VecDeque};
+
+use regex_automata::{dfa::Automaton, util::primitives::StateID};
+
+use crate::ByteClass;
+
+/// The reachable, non-dead, non-quit states of a DFA, with their transitions
+pub(crate) struct Graph {
+    pub(crate) states: Vec<StateID>,
+    // (start byte, end byte, target index) runs of bytes that share a target
+    pub(crate) edges: Vec<Vec<(u8, u8, usize)>>,
+    // whether the input can end in this state
+    pub(crate) accept: Vec<bool>,
+}
+
+impl Graph {
+    /// Explore every state reachable from `start`
+    pub(crate) fn new<A: Automaton>(dfa: &A, start: StateID) -> Self {
+        let mut graph = Self {
+            states: vec![start],
+            edges: vec![],
+            accept: vec![],
+        };
+        let mut index = HashMap::from([(start, 0)]);
+
+        let mut i = 0;
+        while i < graph.states.len() {
+            let current = graph.states[i];
+            let mut edges: Vec<(u8, u8, usize)> = vec![];
+            for b in 0..=255 {
+                let next = dfa.next_state(current, b);
+                // the DFA can't tell whether anything through a quit byte matches
+                if dfa.is_dead_state(next) || dfa.is_quit_state(next) {
+                    continue;
+                }
+                let target = *index.entry(next).or_insert_with(|| {
+                    graph.states.push(next);
+                    graph.states.len() - 1
+                });
+                match edges.last_mut() {
+                    Some((_, end, t)) if *t == target && *end + 1 == b => *end = b,
+                    _ => edges.push((b, b, target)),
+                }
+            }
+            graph.edges.push(edges);
+            graph
+                .accept
+                .push(dfa.is_match_state(dfa.next_eoi_state(current)));
+            i += 1;
+        }
+
+        graph
+    }
+
+    /// The product of this graph with a counter of the path length modulo `modulus`,
+    /// which only accepts when the length is congruent to `residue`
+    pub(crate) fn length_mod(&self, modulus: usize, residue: usize) -> Self {
+        let mut pairs = vec![(0, 0)];
+        let mut index = HashMap::from([((0, 0), 0)]);
+        let mut graph = Self {
+            states: vec![],
+            edges: vec![],
+            accept: vec![],
+        };
+
+        let mut i = 0;
+        while i < pairs.len() {
+            let (current, len) = pairs[i];
+            let next_len = (len + 1) % modulus;
+            let edges = self.edges[current]
+                .iter()
+                .map(|&(start, end, t)| {
+                    let target = *index.entry((t, next_len)).or_insert_with(|| {
+                        pairs.push((t, next_len));
+                        pairs.len() - 1
+                    });
+                    (start, end, target)
+                })
+                .collect();
+            graph.states.push(self.states[current]);
+            graph.edges.push(edges);
+            graph.accept.push(self.accept[current] && len == residue);
+            i += 1;
+        }
+
+        graph
+    }
+
+    /// A copy of this graph whose start only takes the bytes in `first`, and only accepts
+    /// if `first` is empty, like the parts of [`DfaIter::partition_by_first`](crate::DfaIter::partition_by_first).
+    /// Paths that come back to the start continue from an unrestricted copy of it, added at the end.
+    pub(crate) fn restrict_first(&self, first: &ByteClass) -> Self {
+        let n = self.states.len();
+        let redirect =
+            |&(start, end, t): &(u8, u8, usize)| (start, end, if t == 0 { n } else { t });
+
+        let mut edges: Vec<Vec<(u8, u8, usize)>> = self
+            .edges
+            .iter()
+            .map(|edges| edges.iter().map(redirect).collect())
+            .collect();
+        edges.push(edges[0].clone());
+        edges[0] = edges[0]
+            .iter()
+            .flat_map(|&(start, end, t)| {
+                first
+                    .ranges()
+                    .filter(move |&(s, e)| s <= end && start <= e)
+                    .map(move |(s, e)| (u8::max(s, start), u8::min(e, end), t))
+            })
+            .collect();
+
+        let mut states = self.states.clone();
+        states.push(self.states[0]);
+        let mut accept = self.accept.clone();
+        accept.push(self.accept[0]);
+        accept[0] &= first.is_empty();
+
+        Self {
+            states,
+            edges,
+            accept,
+        }
+    }
+
+    /// The fewest bytes needed to get from each state to an accepting state.
+    /// `usize::MAX` if no accepting state can be reached.
+    pub(crate) fn distances(&self) -> Vec<usize> {
+        let n = self.states.len();
+        let mut reverse = vec![vec![]; n];
+        for (i, edges) in self.edges.iter().enumerate() {
+            for &(_, _, t) in edges {
+                reverse[t].push(i);
+            }
+        }
+
+        let mut dist = vec![usize::MAX; n];
+        let mut queue = VecDeque::new();
+        for i in (0..n).filter(|&i| self.accept[i]) {
+            dist[i] = 0;
+            queue.push_back(i);
+        }
+        while let Some(i) = queue.pop_front() {
+            for &j in &reverse[i] {
+                if dist[j] == usize::MAX {
+                    dist[j] = dist[i] + 1;
+                    queue.push_back(j);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Assign every state the id of its strongly connected component
+    pub(crate) fn components(&self) -> Vec<usize> {
+        // iterative tarjan's algorithm
+        let n = self.states.len();
+        let mut index = vec![usize::MAX; n];
+        let mut low = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut component = vec![usize::MAX; n];
+        let mut stack = vec![];
+        let mut next_index = 0;
+        let mut next_component = 0;
+
+        for root in 0..n {
+            if index[root] != usize::MAX {
+                continue;
+            }
+            // (state, next edge to visit)
+            let mut work = vec![(root, 0)];
+            while let Some(&mut (v, ref mut e)) = work.last_mut() {
+                if *e == 0 && index[v] == usize::MAX {
+                    index[v] = next_index;
+                    low[v] = next_index;
+                    next_index += 1;
+                    stack.push(v);
+                    on_stack[v] = true;
+                }
+
+                if let Some(&(_, _, w)) = self.edges[v].get(*e) {
+                    *e += 1;
+                    if index[w] == usize::MAX {
+                        work.push((w, 0));
+                    } else if on_stack[w] {
+                        low[v] = usize::min(low[v], index[w]);
+                    }
+                    continue;
+                }
+
+                work.pop();
+                if let Some(&(parent, _)) = work.last() {
+                    low[parent] = usize::min(low[parent], low[v]);
+                }
+                if low[v] == index[v] {
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component[w] = next_component;
+                        if w == v {
+                            break;
+                        }
+                    }
+                    next_component += 1;
+                }
+            }
+        }
+
+        component
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::dfa::dense::DFA;
+
+    use crate::DfaIter;
+
+    use super::*;
+
+    #[test]
+    fn components() {
+        let dfa = DFA::new(r"(?:ab)+c").unwrap();
+        let iter = DfaIter::from(&dfa);
+        let graph = Graph::new(&iter.regex, iter.start);
+        let components = graph.components();
+
+        // only the `(ab)+` loop shares a component
+        let mut sizes = HashMap::<usize, usize>::new();
+        for c in components {
+            *sizes.entry(c).or_default() += 1;
+        }
+        let mut sizes: Vec<usize> = sizes.into_values().filter(|&s| s > 1).collect();
+        sizes.sort();
+        assert_eq!(sizes, [2]);
+    }
+
+    #[test]
+    fn length_mod() {
+        let dfa = DFA::new(r"a*").unwrap();
+        let iter = DfaIter::from(&dfa);
+        let graph = Graph::new(&iter.regex, iter.start).length_mod(3, 1);
+
+        // the shortest match is `a`. the start state, then the `a*` state at each length modulo 3
+        let dist = graph.distances();
+        assert_eq!(dist[0], 1);
+        let live = dist.iter().filter(|&&d| d != usize::MAX);
+        assert_eq!(live.count(), 4);
+    }
+
+    #[test]
+    fn quit() {
+        let dfa = DFA::builder()
+            .configure(DFA::config().quit(b'x', true))
+            .build(r"[a-z]")
+            .unwrap();
+        let iter = DfaIter::from(&dfa);
+        let graph = Graph::new(&iter.regex, iter.start);
+        assert!(graph.states.iter().all(|&id| !dfa.is_quit_state(id)));
+        assert_eq!(graph.edges[0], [(b'a', b'w', 1), (b'y', b'z', 1)]);
+    }
+
+    #[test]
+    fn restrict_first() {
+        // (?:[a-c]x)*
+        let graph = Graph {
+            states: vec![StateID::ZERO; 2],
+            edges: vec![vec![(b'a', b'c', 1)], vec![(b'x', b'x', 0)]],
+            accept: vec![true, false],
+        };
+        let restricted = graph.restrict_first(&ByteClass::from_iter([b'b', b'c', b'z']));
+
+        // the start only takes `b` or `c`, and no longer accepts
+        assert!(!restricted.accept[0]);
+        assert_eq!(restricted.edges[0], [(b'b', b'c', 1)]);
+        // coming back around the loop gets to the unrestricted start
+        assert_eq!(restricted.edges[1], [(b'x', b'x', 2)]);
+        assert_eq!(restricted.edges[2], [(b'a', b'c', 1)]);
+        assert!(restricted.accept[2]);
+        assert_eq!(restricted.distances(), [2, 1, 0]);
+    }
+
+    #[test]
+    fn distances() {
+        let dfa = DFA::new(r"abc|d").unwrap();
+        let iter = DfaIter::from(&dfa);
+        let graph = Graph::new(&iter.regex, iter.start);
+
+        let dist = graph.distances();
+        assert_eq!(dist[0], 1);
+        let live = dist.iter().filter(|&&d| d != usize::MAX);
+        assert_eq!(live.max(), Some(&2));
+        // after `d` has matched, any other byte leads to a state that can never match
+        assert!(dist.contains(&usize::MAX));
+    }
+}
@@ -0,0 +1,682 @@
+#![allow(clippy::result_large_err)]
+
+use std::{iter, rc::Rc};
+
+use regex_automata::util::look::{Look, LookMatcher};
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::{
+    term::{TermId, Terms},
+    BorrowNext,
+};
+
+/// `HirIter` will produce every possible string value that will match with the given regex,
+/// by following the structure of the parsed regex rather than an automaton.
+///
+/// Strings are produced shortest first. Strings of the same length are ordered by construction:
+///
+/// * a concatenation `xy` produces the strings of `x` in order, from shortest to longest,
+///   each followed by the strings of `y` that make up the rest of the length, in order.
+/// * an alternation `x|y` produces all the strings of `x`, then all the strings of `y`.
+/// * a repetition `x{n,m}` produces the strings with `n` repetitions, then `n + 1`, and so on,
+///   where each repetition is ordered like a concatenation. Repetitions above `n` are never empty.
+///
+/// ```
+/// use regex_utils::HirIter;
+///
+/// let iter = HirIter::new(r"(?:a+|b)*").unwrap();
+/// let x: Vec<Vec<u8>> = iter.take(8).collect();
+/// assert_eq!(x, [
+///     b"".to_vec(),
+///     // one repetition
+///     b"a".to_vec(),
+///     b"b".to_vec(),
+///     b"aa".to_vec(),
+///     // two repetitions
+///     b"aa".to_vec(),
+///     b"ab".to_vec(),
+///     b"ba".to_vec(),
+///     b"bb".to_vec(),
+/// ]);
+/// ```
+///
+/// # Note
+///
+/// Regexes can be infinite (eg `a*`). Either use this iterator lazily, or limit the number
+/// of iterations.
+///
+/// Output strings are not guaranteed to be unique, since different parts of the regex
+/// can produce the same string.
+///
+/// Look-around assertions are checked once a string is produced, so a regex with look-arounds that
+/// rarely hold can spend a long time between matches. Once a length has no matches, the derivatives
+/// of the regex are used to find its longest match, so look-arounds that never hold (like `a*$b`)
+/// don't search forever. This is not possible with Unicode word boundaries, which the derivatives
+/// don't support.
+pub struct HirIter {
+    // the flattened regex
+    nodes: Rc<Nodes>,
+    // the length of strings currently being produced
+    len: usize,
+    // the strings of the current length
+    strings: Strings,
+    // checks the look-around assertions
+    matcher: LookMatcher,
+    // the current match
+    str: Vec<u8>,
+    // the max length when producing strings in preference order instead of by length
+    preferred: Option<usize>,
+    // whether the current length found a match
+    found: bool,
+    // the derivatives of a regex with look-arounds, until they are used to find the longest match
+    terms: Option<(Terms, TermId)>,
+    // the longest match that the derivatives found, if there is one
+    longest: Option<usize>,
+}
+
+type Strings = Box<dyn Iterator<Item = Fragment>>;
+
+/// Part of a match, with the look-arounds that need to hold within it
+#[derive(Default)]
+struct Fragment {
+    bytes: Vec<u8>,
+    looks: Vec<(Look, usize)>,
+}
+
+impl Fragment {
+    fn concat(&self, other: &Fragment) -> Fragment {
+        let offset = self.bytes.len();
+        Fragment {
+            bytes: [&self.bytes[..], &other.bytes[..]].concat(),
+            looks: self
+                .looks
+                .iter()
+                .copied()
+                .chain(other.looks.iter().map(|&(look, at)| (look, at + offset)))
+                .collect(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Fragment {
+    fn from(bytes: Vec<u8>) -> Self {
+        Fragment {
+            bytes,
+            looks: vec![],
+        }
+    }
+}
+
+enum Node {
+    Look(Look),
+    Literal(Box<[u8]>),
+    Bytes(Vec<(u8, u8)>),
+    Unicode(Vec<(char, char)>),
+    Repeat {
+        sub: usize,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+    Concat(Rc<[usize]>),
+    Alt(Vec<usize>),
+}
+
+struct Nodes {
+    // children always come before their parents, so the root is last
+    nodes: Vec<Node>,
+    // the (min, max) length of the strings of each node.
+    // min is None if the node matches nothing, and max is None if there is no longest string
+    lens: Vec<(Option<usize>, Option<usize>)>,
+}
+
+impl Nodes {
+    fn push(&mut self, hir: &Hir) -> usize {
+        let node = match hir.kind() {
+            HirKind::Empty => Node::Concat(Rc::new([])),
+            HirKind::Literal(lit) => Node::Literal(lit.0.clone()),
+            HirKind::Class(Class::Bytes(class)) => Node::Bytes(
+                class
+                    .ranges()
+                    .iter()
+                    .map(|r| (r.start(), r.end()))
+                    .collect(),
+            ),
+            HirKind::Class(Class::Unicode(class)) => Node::Unicode(
+                class
+                    .ranges()
+                    .iter()
+                    .map(|r| (r.start(), r.end()))
+                    .collect(),
+            ),
+            HirKind::Look(look) => Node::Look(Look::from_repr(look.as_repr()).unwrap()),
+            HirKind::Repetition(rep) => Node::Repeat {
+                sub: self.push(&rep.sub),
+                min: rep.min,
+                max: rep.max,
+                greedy: rep.greedy,
+            },
+            HirKind::Capture(cap) => return self.push(&cap.sub),
+            HirKind::Concat(hirs) => Node::Concat(hirs.iter().map(|hir| self.push(hir)).collect()),
+            HirKind::Alternation(hirs) => {
+                Node::Alt(hirs.iter().map(|hir| self.push(hir)).collect())
+            }
+        };
+        let lens = self.lens(&node);
+        self.nodes.push(node);
+        self.lens.push(lens);
+        self.nodes.len() - 1
+    }
+
+    fn lens(&self, node: &Node) -> (Option<usize>, Option<usize>) {
+        match node {
+            Node::Look(_) => (Some(0), Some(0)),
+            Node::Literal(lit) => (Some(lit.len()), Some(lit.len())),
+            Node::Bytes(ranges) if ranges.is_empty() => (None, None),
+            Node::Bytes(_) => (Some(1), Some(1)),
+            Node::Unicode(ranges) => match (ranges.first(), ranges.last()) {
+                (Some(&(start, _)), Some(&(_, end))) => {
+                    (Some(start.len_utf8()), Some(end.len_utf8()))
+                }
+                _ => (None, None),
+            },
+            &Node::Repeat { sub, min, max, .. } => match self.lens[sub] {
+                (None, _) if min == 0 => (Some(0), Some(0)),
+                (None, _) => (None, None),
+                (Some(sub_min), sub_max) => {
+                    let max = match (max, sub_max) {
+                        (Some(0), _) | (_, Some(0)) => Some(0),
+                        (max, sub_max) => max.zip(sub_max).map(|(m, s)| m as usize * s),
+                    };
+                    (Some(min as usize * sub_min), max)
+                }
+            },
+            Node::Concat(parts) => parts.iter().map(|&id| self.lens[id]).fold(
+                (Some(0), Some(0)),
+                |(min, max), (a, b)| match (min.zip(a), max.zip(b)) {
+                    (None, _) => (None, None),
+                    (min, max) => (min.map(|(x, y)| x + y), max.map(|(x, y)| x + y)),
+                },
+            ),
+            // arms that match nothing don't count towards the lengths
+            Node::Alt(alts) => alts
+                .iter()
+                .map(|&id| self.lens[id])
+                .filter_map(|(min, max)| Some((min?, max)))
+                .fold((None, Some(0)), |(min, max), (a, b)| {
+                    (
+                        Some(min.map_or(a, |min: usize| min.min(a))),
+                        max.zip(b).map(|(x, y)| x.max(y)),
+                    )
+                }),
+        }
+    }
+
+    fn root(&self) -> usize {
+        self.nodes.len() - 1
+    }
+}
+
+/// The chars that have each length of UTF-8 encoding
+const UTF8_LENS: [(char, char); 4] = [
+    ('\0', '\x7f'),
+    ('\u{80}', '\u{7ff}'),
+    ('\u{800}', '\u{ffff}'),
+    ('\u{10000}', '\u{10ffff}'),
+];
+
+/// The strings of exactly `n` bytes from the node
+fn strings(nodes: &Rc<Nodes>, id: usize, n: usize) -> Strings {
+    let (min, max) = nodes.lens[id];
+    if min.is_none_or(|min| n < min) || max.is_some_and(|max| n > max) {
+        return Box::new(iter::empty());
+    }
+    // the length bounds already checked that leaf nodes have strings of this length
+    match &nodes.nodes[id] {
+        &Node::Look(look) => Box::new(iter::once(Fragment {
+            bytes: vec![],
+            looks: vec![(look, 0)],
+        })),
+        Node::Literal(lit) => Box::new(iter::once(Fragment::from(lit.to_vec()))),
+        Node::Bytes(ranges) => Box::new(
+            ranges
+                .clone()
+                .into_iter()
+                .flat_map(|(start, end)| start..=end)
+                .map(|b| Fragment::from(vec![b])),
+        ),
+        Node::Unicode(ranges) => {
+            let (lo, hi) = UTF8_LENS[n - 1];
+            Box::new(
+                ranges
+                    .clone()
+                    .into_iter()
+                    .flat_map(move |(start, end)| start.max(lo)..=end.min(hi))
+                    .map(|c| Fragment::from(c.to_string().into_bytes())),
+            )
+        }
+        &Node::Repeat { sub, min, max, .. } => {
+            let min = min as usize;
+            // repetitions above the minimum are never empty, so there can't be more than `n` of them
+            let most = max.map_or(usize::MAX, |max| max as usize).min(min + n);
+            let nodes = nodes.clone();
+            Box::new((min..=most).flat_map(move |k| power(&nodes, sub, k, min, n)))
+        }
+        Node::Concat(parts) => product(nodes, parts.clone(), 0, n),
+        Node::Alt(alts) => {
+            let nodes = nodes.clone();
+            let alts = alts.clone();
+            Box::new(
+                alts.into_iter()
+                    .flat_map(move |alt| strings(&nodes, alt, n)),
+            )
+        }
+    }
+}
+
+/// The strings of exactly `n` bytes from the concatenation of `parts[start..]`
+fn product(nodes: &Rc<Nodes>, parts: Rc<[usize]>, start: usize, n: usize) -> Strings {
+    match parts.len() - start {
+        0 => return Box::new((n == 0).then(Fragment::default).into_iter()),
+        1 => return strings(nodes, parts[start], n),
+        _ => {}
+    }
+
+    // the lengths that the rest of the parts could make up
+    let (rest_min, rest_max) = parts[start + 1..].iter().map(|&id| nodes.lens[id]).fold(
+        (Some(0), Some(0)),
+        |(min, max), (a, b)| {
+            (
+                min.zip(a).map(|(x, y)| x + y),
+                max.zip(b).map(|(x, y)| x + y),
+            )
+        },
+    );
+    let Some(rest_min) = rest_min else {
+        return Box::new(iter::empty());
+    };
+    let lo = rest_max.map_or(0, |max| n.saturating_sub(max));
+    let hi = n.saturating_sub(rest_min);
+
+    let first = parts[start];
+    let nodes = nodes.clone();
+    Box::new((lo..=hi).flat_map(move |i| {
+        let (nodes, parts) = (nodes.clone(), parts.clone());
+        strings(&nodes, first, i).flat_map(move |prefix| {
+            product(&nodes, parts.clone(), start + 1, n - i)
+                .map(move |suffix| prefix.concat(&suffix))
+        })
+    }))
+}
+
+/// The strings of exactly `n` bytes from `k` repetitions of the node.
+/// Only the first `may_be_empty` repetitions can be empty
+fn power(nodes: &Rc<Nodes>, sub: usize, k: usize, may_be_empty: usize, n: usize) -> Strings {
+    if k == 0 {
+        return Box::new((n == 0).then(Fragment::default).into_iter());
+    }
+    let (Some(min), max) = nodes.lens[sub] else {
+        return Box::new(iter::empty());
+    };
+    let least = (k - may_be_empty.min(k)) * min.max(1) + may_be_empty.min(k) * min;
+    if n < least || max.is_some_and(|max| n > k * max) {
+        return Box::new(iter::empty());
+    }
+
+    let lo = if may_be_empty > 0 { 0 } else { 1 };
+    let nodes = nodes.clone();
+    Box::new((lo..=n).flat_map(move |i| {
+        let nodes = nodes.clone();
+        strings(&nodes, sub, i).flat_map(move |prefix| {
+            power(&nodes, sub, k - 1, may_be_empty.saturating_sub(1), n - i)
+                .map(move |suffix| prefix.concat(&suffix))
+        })
+    }))
+}
+
+/// The strings of at most `n` bytes from the node, in the order that a backtracking engine prefers them
+fn preferred(nodes: &Rc<Nodes>, id: usize, n: usize) -> Strings {
+    if nodes.lens[id].0.is_none_or(|min| n < min) {
+        return Box::new(iter::empty());
+    }
+    match &nodes.nodes[id] {
+        &Node::Look(look) => Box::new(iter::once(Fragment {
+            bytes: vec![],
+            looks: vec![(look, 0)],
+        })),
+        Node::Literal(lit) => Box::new(iter::once(Fragment::from(lit.to_vec()))),
+        Node::Bytes(ranges) => Box::new(
+            ranges
+                .clone()
+                .into_iter()
+                .flat_map(|(start, end)| start..=end)
+                .map(|b| Fragment::from(vec![b])),
+        ),
+        Node::Unicode(ranges) => Box::new(
+            ranges
+                .clone()
+                .into_iter()
+                .flat_map(|(start, end)| start..=end)
+                .filter(move |c| c.len_utf8() <= n)
+                .map(|c| Fragment::from(c.to_string().into_bytes())),
+        ),
+        &Node::Repeat {
+            sub,
+            min,
+            max,
+            greedy,
+        } => preferred_power(
+            nodes,
+            sub,
+            0,
+            min as usize,
+            max.map(|m| m as usize),
+            greedy,
+            n,
+        ),
+        Node::Concat(parts) => preferred_product(nodes, parts.clone(), 0, n),
+        Node::Alt(alts) => {
+            let nodes = nodes.clone();
+            let alts = alts.clone();
+            Box::new(
+                alts.into_iter()
+                    .flat_map(move |alt| preferred(&nodes, alt, n)),
+            )
+        }
+    }
+}
+
+/// The strings of at most `n` bytes from the concatenation of `parts[start..]`, in preference order
+fn preferred_product(nodes: &Rc<Nodes>, parts: Rc<[usize]>, start: usize, n: usize) -> Strings {
+    if start == parts.len() {
+        return Box::new(iter::once(Fragment::default()));
+    }
+    let rest_min = parts[start + 1..]
+        .iter()
+        .try_fold(0, |sum, &id| Some(sum + nodes.lens[id].0?));
+    let Some(rest_min) = rest_min.filter(|&min| min <= n) else {
+        return Box::new(iter::empty());
+    };
+
+    let nodes = nodes.clone();
+    let first = preferred(&nodes, parts[start], n - rest_min);
+    Box::new(first.flat_map(move |prefix| {
+        preferred_product(&nodes, parts.clone(), start + 1, n - prefix.bytes.len())
+            .map(move |suffix| prefix.concat(&suffix))
+    }))
+}
+
+/// The strings of at most `n` bytes from repeating the node after `done` repetitions, in preference order.
+/// Greedy repetitions try another repetition before stopping, and lazy ones stop first.
+/// Repetitions above `min` are never empty.
+fn preferred_power(
+    nodes: &Rc<Nodes>,
+    sub: usize,
+    done: usize,
+    min: usize,
+    max: Option<usize>,
+    greedy: bool,
+    n: usize,
+) -> Strings {
+    let stop = (done >= min).then(Fragment::default).into_iter();
+    let (Some(sub_min), _) = nodes.lens[sub] else {
+        return Box::new(stop);
+    };
+    if max.is_some_and(|max| done >= max) || (min.saturating_sub(done)) * sub_min > n {
+        return Box::new(stop);
+    }
+
+    let nodes = nodes.clone();
+    let more = preferred(&nodes, sub, n)
+        .filter(move |prefix| done < min || !prefix.bytes.is_empty())
+        .flat_map(move |prefix| {
+            preferred_power(
+                &nodes,
+                sub,
+                done + 1,
+                min,
+                max,
+                greedy,
+                n - prefix.bytes.len(),
+            )
+            .map(move |suffix| prefix.concat(&suffix))
+        });
+    if greedy {
+        Box::new(more.chain(stop))
+    } else {
+        Box::new(stop.chain(more))
+    }
+}
+
+impl From<&Hir> for HirIter {
+    fn from(hir: &Hir) -> Self {
+        let mut nodes = Nodes {
+            nodes: vec![],
+            lens: vec![],
+        };
+        nodes.push(hir);
+        let nodes = Rc::new(nodes);
+        let terms = if hir.properties().look_set().is_empty() {
+            None
+        } else {
+            let mut terms = Terms::new();
+            terms.hir(hir).ok().map(|start| (terms, start))
+        };
+        Self {
+            strings: strings(&nodes, nodes.root(), 0),
+            nodes,
+            len: 0,
+            matcher: LookMatcher::new(),
+            str: vec![],
+            preferred: None,
+            found: false,
+            terms,
+            longest: None,
+        }
+    }
+}
+
+impl HirIter {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `HirIter`.
+    ///
+    /// If you want a non-default configuration, then use the
+    /// [`regex_syntax::ParserBuilder`] to parse the [`Hir`] yourself.
+    pub fn new(pattern: &str) -> Result<Self, regex_syntax::Error> {
+        regex_syntax::parse(pattern).map(|hir| Self::from(&hir))
+    }
+
+    /// Produce the strings of up to `max_len` bytes in the order that a backtracking regex engine
+    /// would prefer them, instead of by length. This restarts the iteration.
+    ///
+    /// Greedy repetitions try to repeat once more before stopping, and lazy ones (like `a+?`, or
+    /// everything in `(?U)`) try to stop first. Alternations try their arms in the order they are
+    /// written. So the first string is the "canonical" example of the regex.
+    ///
+    /// The parser merges alternations of single characters into classes, and classes are
+    /// always tried from the lowest byte, so `b|a` tries `a` first.
+    ///
+    /// ```
+    /// use regex_utils::HirIter;
+    ///
+    /// let iter = HirIter::new(r"(?:yes|no)(?:, thanks)?").unwrap().preference_order(11);
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x, [
+    ///     b"yes, thanks".to_vec(),
+    ///     b"yes".to_vec(),
+    ///     b"no, thanks".to_vec(),
+    ///     b"no".to_vec(),
+    /// ]);
+    ///
+    /// let iter = HirIter::new(r"x+?").unwrap().preference_order(3);
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x, [b"x".to_vec(), b"xx".to_vec(), b"xxx".to_vec()]);
+    /// ```
+    pub fn preference_order(mut self, max_len: usize) -> Self {
+        self.preferred = Some(max_len);
+        self.strings = preferred(&self.nodes, self.nodes.root(), max_len);
+        self
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            let Some(next) = self.strings.next() else {
+                if self.preferred.is_some() {
+                    return None;
+                }
+                // there are no longer strings
+                let (min, max) = self.nodes.lens[self.nodes.root()];
+                if min.is_none() || max.is_some_and(|max| self.len >= max) {
+                    return None;
+                }
+                if !self.found {
+                    if let Some((mut terms, start)) = self.terms.take() {
+                        // the look-arounds might never hold in longer strings
+                        self.longest = terms.reachable(start, None, false).longest();
+                    }
+                }
+                if self.longest.is_some_and(|longest| self.len >= longest) {
+                    return None;
+                }
+                self.found = false;
+                self.len += 1;
+                self.strings = strings(&self.nodes, self.nodes.root(), self.len);
+                continue;
+            };
+
+            let holds = next
+                .looks
+                .iter()
+                .all(|&(look, at)| self.matcher.matches(look, &next.bytes, at));
+            if holds {
+                self.found = true;
+                self.str = next.bytes;
+                return Some(&self.str);
+            }
+        }
+    }
+}
+
+impl BorrowNext for HirIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        HirIter::borrow_next(self)
+    }
+}
+
+impl Iterator for HirIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::BrzozowskiIter;
+
+    use super::*;
+
+    #[test]
+    fn order() {
+        let iter = HirIter::new(r"(?:x|yy)(?:[0-9]|ab)").unwrap();
+        let x: Vec<Vec<u8>> = iter.skip(10).collect();
+        assert_eq!(
+            x,
+            [
+                b"xab".to_vec(),
+                b"yy0".to_vec(),
+                b"yy1".to_vec(),
+                b"yy2".to_vec(),
+                b"yy3".to_vec(),
+                b"yy4".to_vec(),
+                b"yy5".to_vec(),
+                b"yy6".to_vec(),
+                b"yy7".to_vec(),
+                b"yy8".to_vec(),
+                b"yy9".to_vec(),
+                b"yyab".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_repetitions() {
+        // one repetition of the outer group before two
+        let iter = HirIter::new(r"(?:[ab]{1,2}){1,2}").unwrap();
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(x.len(), 2 + (4 + 4) + 2 * 4 * 2 + 16);
+        assert_eq!(x[2..7], [b"aa", b"ab", b"ba", b"bb", b"aa"]);
+    }
+
+    #[test]
+    fn matches_brzozowski() {
+        for pattern in [
+            r"[a-c]{1,3}x?|foo",
+            r"(?:a*)*b",
+            r"(?:a?){3}",
+            r"[а-яё0-9]{2}",
+            r"(?m)[ab\n]{0,2}^b$(?-u:\b)",
+            r"[a&&b]+|c",
+        ] {
+            let x: HashSet<Vec<u8>> = HirIter::new(pattern)
+                .unwrap()
+                .take_while(|s| s.len() <= 6)
+                .collect();
+            let y: HashSet<Vec<u8>> = BrzozowskiIter::new(pattern)
+                .unwrap()
+                .take_while(|s| s.len() <= 6)
+                .collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn looks_never_hold() {
+        assert_eq!(HirIter::new(r"a*$b").unwrap().next(), None);
+        let iter = HirIter::new(r"x|(?:a|b)*$c").unwrap();
+        assert_eq!(iter.collect::<Vec<_>>(), [b"x".to_vec()]);
+        // `c` can only match at the start
+        let iter = HirIter::new(r"x|(?:a|b)*^c").unwrap();
+        assert_eq!(iter.collect::<Vec<_>>(), [b"x".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    fn preference_order() {
+        let collect = |pattern: &str, max_len| -> Vec<String> {
+            HirIter::new(pattern)
+                .unwrap()
+                .preference_order(max_len)
+                .map(|s| String::from_utf8(s).unwrap())
+                .collect()
+        };
+        assert_eq!(collect(r"a*", 3), ["aaa", "aa", "a", ""]);
+        assert_eq!(collect(r"a*?", 3), ["", "a", "aa", "aaa"]);
+        assert_eq!(collect(r"(?U)a*", 3), ["", "a", "aa", "aaa"]);
+        // `swap_greed` makes `*?` greedy again
+        assert_eq!(collect(r"(?U)a*?", 2), ["aa", "a", ""]);
+        assert_eq!(collect(r"bc|ab", 2), ["bc", "ab"]);
+        assert_eq!(collect(r"(?:b|a)c|ab", 2), ["ac", "bc", "ab"]);
+        assert_eq!(collect(r"[ab]{1,2}", 2), ["aa", "ab", "a", "ba", "bb", "b"]);
+        assert_eq!(collect(r"(?:x{2})+y?", 4), ["xxxx", "xxy", "xx"]);
+        assert_eq!(collect(r"é?", 1), [""]);
+        assert_eq!(collect(r"a$b|c", 3), ["c"]);
+
+        // the same strings as the length order
+        for pattern in [r"(ab|b)*c?", r"(?:a?){2,3}b*?", r"[а-я]{1,2}"] {
+            let mut x: Vec<Vec<u8>> = HirIter::new(pattern).unwrap().preference_order(4).collect();
+            let mut y: Vec<Vec<u8>> = HirIter::new(pattern)
+                .unwrap()
+                .take_while(|s| s.len() <= 4)
+                .collect();
+            x.sort();
+            x.dedup();
+            y.sort();
+            y.dedup();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+}
@@ -0,0 +1,379 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::{HashSet, VecDeque};
+
+use regex_automata::{
+    hybrid::{
+        dfa::{Cache, DFA},
+        BuildError, LazyStateID,
+    },
+    nfa::thompson::{State, NFA},
+    util::primitives::StateID,
+    Anchored, Input,
+};
+
+use crate::BorrowNext;
+
+/// `HybridIter` will produce every possible string value that will match with the given regex,
+/// building the DFA lazily as it goes.
+///
+/// ```
+/// use regex_utils::HybridIter;
+///
+/// let iter = HybridIter::new(r"[ab]+c").unwrap();
+/// let x: Vec<Vec<u8>> = iter.take(4).collect();
+/// assert_eq!(x, [
+///     b"ac".to_vec(),
+///     b"bc".to_vec(),
+///     b"aac".to_vec(),
+///     b"abc".to_vec(),
+/// ]);
+/// ```
+///
+/// # Note
+///
+/// Regexes can be infinite (eg `a*`). Either use this iterator lazily, or limit the number
+/// of iterations.
+///
+/// # Implementation Details
+///
+/// This traverses a [lazy DFA](regex_automata::hybrid), which only determinizes the states that the
+/// search visits, and keeps them in a bounded cache. Like [`DfaIter`](crate::DfaIter), output strings
+/// are unique and produced in lexicographical byte ordering, shortest first.
+///
+/// Unlike [`DfaIter`](crate::DfaIter), the distance to a match is not known up front, so paths
+/// are explored up to the current depth even if they can't reach a match in time.
+///
+/// If the cache fills up, it is cleared, and any states that the search still needs are rebuilt
+/// by replaying their path from the start.
+///
+/// Once a search depth finds no new states, every reachable state is known. If there are `n` of
+/// them, then any longer match could be shortened to one within the last `n` depths, so when those
+/// have no matches, the iterator ends. This can only be decided while the cache is not being
+/// cleared, so with a very small cache, empty languages like `[ab]*(?-u:\b\B)x` may never end.
+///
+/// There is no up-front determinization, so this starts producing strings straight away even for
+/// very large patterns, where building a [`DenseDfaIter`](crate::DenseDfaIter) could take seconds.
+/// The search starts at the shortest match length, found from the NFA, so long patterns
+/// like `[a-z]{200}` don't search every shorter length first.
+pub struct HybridIter {
+    // the lazy graph to search
+    regex: DFA,
+    // the states built so far
+    cache: Cache,
+    // the max depth we currently want to search
+    depth: usize,
+    // the max depth observed in the graph
+    max_depth: usize,
+    // (state, edge, depth, cache clear count when the state was found)
+    stack: Vec<(LazyStateID, u8, usize, usize)>,
+    // the current path, with a placeholder first byte for the start
+    str: Vec<u8>,
+    // the states the search has reached since the cache was last cleared
+    seen: HashSet<LazyStateID>,
+    // how many states had been reached by the end of the previous depth,
+    // if the cache was not cleared since
+    seen_before: Option<usize>,
+    // the cache clear count at the end of the previous depth
+    clears: usize,
+    // the length of the longest match so far
+    last_match: Option<usize>,
+}
+
+impl From<DFA> for HybridIter {
+    fn from(dfa: DFA) -> Self {
+        let cache = dfa.create_cache();
+        let min_len = min_len(dfa.get_nfa());
+        let mut iter = Self {
+            regex: dfa,
+            cache,
+            depth: min_len,
+            max_depth: min_len,
+            stack: vec![],
+            str: vec![],
+            seen: HashSet::new(),
+            seen_before: None,
+            clears: 0,
+            last_match: None,
+        };
+        iter.clears = iter.cache.clear_count();
+        let start = iter.start();
+        iter.seen.insert(start);
+        iter.stack.push((start, 0, 0, iter.cache.clear_count()));
+        iter
+    }
+}
+
+impl HybridIter {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `HybridIter`.
+    ///
+    /// If you want a non-default configuration, then use the
+    /// [`hybrid::dfa::Builder`](regex_automata::hybrid::dfa::Builder) to set your own configuration.
+    ///
+    /// See [`DFA`] for details
+    pub fn new(pattern: &str) -> Result<Self, BuildError> {
+        DFA::new(pattern).map(Self::from)
+    }
+
+    /// Parse the given regular expressions using a default configuration and
+    /// return the corresponding multi-`HybridIter`.
+    ///
+    /// If you want a non-default configuration, then use the
+    /// [`hybrid::dfa::Builder`](regex_automata::hybrid::dfa::Builder) to set your own configuration.
+    ///
+    /// See [`DFA`] for details
+    pub fn new_many<P: AsRef<str>>(patterns: &[P]) -> Result<Self, BuildError> {
+        DFA::new_many(patterns).map(Self::from)
+    }
+
+    /// The lazy DFA that this iterator searches.
+    ///
+    /// Searches with it need their own [`Cache`], see [`DFA::create_cache`]
+    pub fn as_dfa(&self) -> &DFA {
+        &self.regex
+    }
+
+    /// The NFA that the lazy DFA is built from
+    pub fn as_nfa(&self) -> &NFA {
+        self.regex.get_nfa()
+    }
+
+    /// Recover the lazy DFA that this iterator searches, to reuse it elsewhere.
+    /// The states it has built so far are in the cache, which is dropped.
+    pub fn into_inner(self) -> DFA {
+        self.regex
+    }
+
+    /// The anchored start state
+    fn start(&mut self) -> LazyStateID {
+        self.regex
+            .start_state_forward(&mut self.cache, &Input::new("").anchored(Anchored::Yes))
+            .expect("the lazy DFA should support anchored searches")
+    }
+
+    fn next_state(&mut self, current: LazyStateID, b: u8) -> LazyStateID {
+        self.regex
+            .next_state(&mut self.cache, current, b)
+            .expect("the lazy DFA should not give up on clearing its cache")
+    }
+
+    /// Rebuild the state for the current path, after the cache was cleared
+    fn replay(&mut self) -> LazyStateID {
+        let mut current = self.start();
+        for i in 1..self.str.len() {
+            let b = self.str[i];
+            current = self.next_state(current, b);
+        }
+        current
+    }
+
+    /// Whether there are no matches longer than the current depth, checked once the depth is done
+    fn exhausted(&mut self) -> bool {
+        let clears = self.cache.clear_count();
+        if clears != self.clears {
+            // the states seen before the clear have different ids now
+            self.seen.clear();
+            self.seen_before = None;
+            self.clears = clears;
+            return false;
+        }
+
+        let n = self.seen.len();
+        // if this depth found new states, there could be more to find
+        let complete = self.seen_before.replace(n) == Some(n);
+        complete && n <= self.depth && self.last_match.is_none_or(|len| len + n <= self.depth)
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            let Some((mut current, b, depth, clears)) = self.stack.pop() else {
+                // we didn't get any deeper. no more search space
+                if self.max_depth < self.depth || self.exhausted() {
+                    break None;
+                }
+
+                self.depth += 1;
+                let start = self.start();
+                self.seen.insert(start);
+                self.stack.push((start, 0, 0, self.cache.clear_count()));
+                continue;
+            };
+
+            // update recorded max depth
+            self.max_depth = usize::max(self.max_depth, depth);
+            self.str.truncate(depth);
+            self.str.push(b);
+            if clears != self.cache.clear_count() {
+                current = self.replay();
+            }
+
+            // check we can explore deeper
+            if depth < self.depth {
+                for b in (0..=255).rev() {
+                    let before = self.cache.clear_count();
+                    let next_state = self.next_state(current, b);
+                    let after = self.cache.clear_count();
+                    if before != after {
+                        // `next_state` is still valid, but `current` is not
+                        current = self.replay();
+                    }
+                    if next_state.is_dead() || next_state.is_quit() {
+                        continue;
+                    }
+                    self.seen.insert(next_state);
+                    self.stack.push((next_state, b, depth + 1, after));
+                }
+            } else {
+                // test that this state is final
+                let eoi_state = self
+                    .regex
+                    .next_eoi_state(&mut self.cache, current)
+                    .expect("the lazy DFA should not give up on clearing its cache");
+                if eoi_state.is_match() {
+                    self.last_match = Some(depth);
+                    break Some(&self.str[1..]);
+                }
+            }
+        }
+    }
+}
+
+/// The length of the shortest anchored match in the NFA, ignoring look-around assertions.
+///
+/// Returns 0 if there are no matches.
+fn min_len(nfa: &NFA) -> usize {
+    let mut dist = vec![usize::MAX; nfa.states().len()];
+    // 0-1 BFS: epsilon transitions go to the front, byte transitions to the back
+    let mut queue = VecDeque::from([(nfa.start_anchored(), 0)]);
+    while let Some((id, d)) = queue.pop_front() {
+        if dist[id.as_usize()] <= d {
+            continue;
+        }
+        dist[id.as_usize()] = d;
+        match nfa.state(id) {
+            State::ByteRange { trans } => queue.push_back((trans.next, d + 1)),
+            State::Sparse(sparse) => {
+                queue.extend(sparse.transitions.iter().map(|trans| (trans.next, d + 1)));
+            }
+            State::Dense(dense) => queue.extend(
+                dense
+                    .transitions
+                    .iter()
+                    .filter(|&&next| next != StateID::ZERO)
+                    .map(|&next| (next, d + 1)),
+            ),
+            State::Look { next, .. } | State::Capture { next, .. } => {
+                queue.push_front((*next, d));
+            }
+            State::Union { alternates } => {
+                for &alt in alternates.iter().rev() {
+                    queue.push_front((alt, d));
+                }
+            }
+            State::BinaryUnion { alt1, alt2 } => {
+                queue.push_front((*alt2, d));
+                queue.push_front((*alt1, d));
+            }
+            State::Fail => {}
+            State::Match { .. } => return d,
+        }
+    }
+    0
+}
+
+impl BorrowNext for HybridIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        HybridIter::borrow_next(self)
+    }
+}
+
+impl Iterator for HybridIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::hybrid::dfa;
+
+    use crate::DenseDfaIter;
+
+    use super::*;
+
+    #[test]
+    fn specialized_starts() {
+        use regex_automata::{util::prefilter::Prefilter, MatchKind};
+
+        let pattern = r"foo[0-9]{1,2}|bar|(?m:^)a+";
+        let prefilter = Prefilter::new(MatchKind::LeftmostFirst, &["foo", "bar", "a"]);
+        let config = dfa::Config::new()
+            .specialize_start_states(true)
+            .prefilter(prefilter);
+        let dfa = DFA::builder().configure(config).build(pattern).unwrap();
+        let x: Vec<Vec<u8>> = HybridIter::from(dfa).take(60).collect();
+        let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().take(60).collect();
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn matches_dfa() {
+        for pattern in [r"[a-c]{1,3}x?|foo", r"a+(0|1)", r"[а-яё0-9]{2}"] {
+            let x: Vec<Vec<u8>> = HybridIter::new(pattern).unwrap().take(60).collect();
+            let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().take(60).collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn cache_clears() {
+        let pattern = r"[a-c]{2}[0-9]";
+        let dfa = dfa::Builder::new()
+            .configure(
+                dfa::Config::new()
+                    .cache_capacity(0)
+                    .skip_cache_capacity_check(true),
+            )
+            .build(pattern)
+            .unwrap();
+        let mut iter = HybridIter::from(dfa);
+        let x: Vec<Vec<u8>> = iter.by_ref().collect();
+        assert!(iter.cache.clear_count() > 0);
+
+        let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().collect();
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn dead_ends() {
+        // these loops never reach a match, but the states are not dead
+        for pattern in [
+            r"[ab]*(?-u:\b)(?-u:\B)x",
+            r"a|b[cd]*(?-u:\b)(?-u:\B)x",
+            r"(?:ab)+(?-u:\b)(?-u:\B)|[0-9]{2}",
+        ] {
+            let x: Vec<Vec<u8>> = HybridIter::new(pattern).unwrap().collect();
+            let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().collect();
+            assert_eq!(x, y, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn long_pattern() {
+        // searching every shorter length first would never finish
+        let mut iter = HybridIter::new(r"[a-z]{200}").unwrap();
+        assert_eq!(iter.next().unwrap(), [b'a'; 200]);
+        assert_eq!(
+            iter.next().unwrap(),
+            [[b'a'; 199].as_slice(), b"b"].concat()
+        );
+
+        let mut iter = HybridIter::new(r"a{3}(?:b|[a&&b])").unwrap();
+        assert_eq!(iter.next().unwrap(), b"aaab");
+        assert_eq!(iter.next(), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::BorrowNext;
+
+/// `LanguageIndex` is a snapshot of the first matches of a regex iterator, for random access.
+///
+/// ```
+/// use regex_utils::{DenseDfaIter, LanguageIndex};
+///
+/// let iter = DenseDfaIter::new(r"[a-z]{2}[0-9]").unwrap();
+/// let index = LanguageIndex::build(iter, 1000);
+/// assert_eq!(index.len(), 1000);
+/// assert_eq!(index.get(11), Some(&b"ab1"[..]));
+/// assert_eq!(index.position(b"ab1"), Some(11));
+///
+/// let page: Vec<&[u8]> = index.slice(20..23).collect();
+/// assert_eq!(page, [b"ac0", b"ac1", b"ac2"]);
+/// ```
+///
+/// # Implementation Details
+///
+/// All of the matches are stored in one buffer, with the offset of each of them,
+/// so getting a match by its index is O(1). A permutation of the indices sorted by string is kept
+/// for [`LanguageIndex::position`], which is a binary search.
+#[derive(Clone, Debug, Default)]
+pub struct LanguageIndex {
+    // every match, one after the other
+    bytes: Vec<u8>,
+    // the end of each match in `bytes`
+    ends: Vec<usize>,
+    // the indices of the matches, sorted by the matches
+    sorted: Vec<usize>,
+}
+
+impl LanguageIndex {
+    /// Take up to `n` matches from the iterator
+    pub fn build<I: BorrowNext>(mut iter: I, n: usize) -> Self {
+        let mut index = Self::default();
+        while index.ends.len() < n {
+            let Some(next) = iter.borrow_next() else {
+                break;
+            };
+            index.bytes.extend_from_slice(next);
+            index.ends.push(index.bytes.len());
+        }
+
+        let mut sorted: Vec<usize> = (0..index.ends.len()).collect();
+        sorted.sort_by(|&a, &b| index.match_at(a).cmp(index.match_at(b)));
+        index.sorted = sorted;
+        index
+    }
+
+    fn match_at(&self, i: usize) -> &[u8] {
+        let start = i.checked_sub(1).map_or(0, |i| self.ends[i]);
+        &self.bytes[start..self.ends[i]]
+    }
+
+    /// The number of matches in the index
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Whether the index has no matches
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// The `i`th match, in the order the iterator produced them
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        (i < self.len()).then(|| self.match_at(i))
+    }
+
+    /// The index of the given match, or `None` if it's not in the index.
+    ///
+    /// If the iterator produced the match more than once, any of its indices might be returned.
+    pub fn position(&self, str: &[u8]) -> Option<usize> {
+        let i = self
+            .sorted
+            .binary_search_by(|&i| self.match_at(i).cmp(str))
+            .ok()?;
+        Some(self.sorted[i])
+    }
+
+    /// The matches in the given range of indices
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> impl ExactSizeIterator<Item = &[u8]> {
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => i + 1,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            start <= end && end <= self.len(),
+            "range {start}..{end} out of bounds for index of length {}",
+            self.len()
+        );
+        (start..end).map(|i| self.match_at(i))
+    }
+
+    /// Every match in the index, in order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &[u8]> {
+        self.slice(..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DenseDfaIter, NfaIter};
+
+    use super::*;
+
+    #[test]
+    fn index() {
+        let x: Vec<Vec<u8>> = NfaIter::new(r"b|a|(a)?|cc").unwrap().collect();
+        let index = LanguageIndex::build(NfaIter::new(r"b|a|(a)?|cc").unwrap(), 100);
+        assert_eq!(index.len(), x.len());
+        for (i, s) in x.iter().enumerate() {
+            assert_eq!(index.get(i), Some(&s[..]));
+            // `a` is produced twice
+            assert_eq!(index.get(index.position(s).unwrap()), Some(&s[..]));
+        }
+        assert_eq!(index.get(x.len()), None);
+        assert_eq!(index.position(b"d"), None);
+        assert_eq!(index.iter().collect::<Vec<_>>(), x);
+        assert_eq!(index.slice(1..=2).len(), 2);
+    }
+
+    #[test]
+    fn limit() {
+        let index = LanguageIndex::build(DenseDfaIter::new(r"a*").unwrap(), 5);
+        assert_eq!(index.len(), 5);
+        assert_eq!(index.get(0), Some(&b""[..]));
+        assert_eq!(index.position(b""), Some(0));
+        assert_eq!(index.get(4), Some(&b"aaaa"[..]));
+
+        let index = LanguageIndex::build(DenseDfaIter::new(r"a*").unwrap(), 0);
+        assert!(index.is_empty());
+        assert_eq!(index.position(b""), None);
+    }
+
+    #[test]
+    #[should_panic = "out of bounds"]
+    fn slice_out_of_bounds() {
+        let index = LanguageIndex::build(DenseDfaIter::new(r"a*").unwrap(), 5);
+        let _ = index.slice(3..6);
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::BorrowNext;
+
+/// The ID of a string in an [`Interner`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// The index of the string, in the order the strings were first interned
+    pub fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Stores each distinct string once, so that every copy of it shares the same allocation.
+///
+/// ```
+/// use std::sync::Arc;
+/// use regex_utils::Interner;
+///
+/// let mut interner = Interner::new();
+/// let a = interner.intern(b"foo");
+/// let b = interner.intern(b"foo");
+/// assert_eq!(a, b);
+/// assert_eq!(interner.len(), 1);
+/// assert!(Arc::ptr_eq(interner.get(a).unwrap(), interner.get(b).unwrap()));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    // the symbol of each string
+    symbols: HashMap<Arc<[u8]>, Symbol>,
+    // [symbol]: the string
+    strs: Vec<Arc<[u8]>>,
+}
+
+impl Interner {
+    /// Create an empty `Interner`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The symbol of `s`, storing it if it hasn't been seen before
+    ///
+    /// # Panics
+    ///
+    /// If there are more than `u32::MAX` distinct strings
+    pub fn intern(&mut self, s: &[u8]) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(s) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.strs.len()).expect("too many interned strings"));
+        let s: Arc<[u8]> = s.into();
+        self.strs.push(Arc::clone(&s));
+        self.symbols.insert(s, symbol);
+        symbol
+    }
+
+    /// The symbol of `s`, if it has been interned
+    pub fn lookup(&self, s: &[u8]) -> Option<Symbol> {
+        self.symbols.get(s).copied()
+    }
+
+    /// The string of a symbol. Returns `None` if the symbol is from another interner
+    pub fn get(&self, symbol: Symbol) -> Option<&Arc<[u8]>> {
+        self.strs.get(symbol.as_usize())
+    }
+
+    /// The number of distinct strings
+    pub fn len(&self) -> usize {
+        self.strs.len()
+    }
+
+    /// Whether no strings have been interned
+    pub fn is_empty(&self) -> bool {
+        self.strs.is_empty()
+    }
+
+    /// Iterate over the distinct strings, in the order they were first interned
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Arc<[u8]>> + '_ {
+        self.strs.iter()
+    }
+}
+
+/// An iterator over the matches of a regex iterator, with each distinct match only stored once.
+/// See [`BorrowNext::interned`]
+pub struct Interned<I> {
+    iter: I,
+    interner: Interner,
+}
+
+impl<I> Interned<I> {
+    pub(crate) fn new(iter: I, interner: Interner) -> Self {
+        Self { iter, interner }
+    }
+
+    /// The distinct matches so far
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    /// Recover the regex iterator and the interned matches
+    pub fn into_parts(self) -> (I, Interner) {
+        (self.iter, self.interner)
+    }
+}
+
+impl<I: BorrowNext> Interned<I> {
+    /// Get the symbol of the next match. The string is in [`Interned::interner`]
+    pub fn next_symbol(&mut self) -> Option<Symbol> {
+        let next = self.iter.borrow_next()?;
+        Some(self.interner.intern(next))
+    }
+}
+
+impl<I: BorrowNext> Iterator for Interned<I> {
+    type Item = Arc<[u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let symbol = self.next_symbol()?;
+        self.interner.get(symbol).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{BorrowNext, NfaIter};
+
+    use super::Interner;
+
+    #[test]
+    fn interned() {
+        // the nfa produces `a` and `aa` several times
+        let pattern = r"a|a|(?:a|aa)a?";
+        let x: Vec<Vec<u8>> = NfaIter::new(pattern).unwrap().collect();
+        let mut iter = NfaIter::new(pattern).unwrap().interned();
+        let y: Vec<Arc<[u8]>> = iter.by_ref().collect();
+        assert!(x.iter().map(Vec::as_slice).eq(y.iter().map(|s| &**s)));
+        assert!(x.len() > iter.interner().len());
+        assert_eq!(iter.interner().len(), 3);
+
+        // copies share the allocation
+        let a: Vec<&Arc<[u8]>> = y.iter().filter(|s| ***s == *b"a").collect();
+        assert!(a.len() > 1);
+        assert!(a.iter().all(|s| Arc::ptr_eq(s, a[0])));
+    }
+
+    #[test]
+    fn shared() {
+        // symbols are shared between iterators
+        let mut a = NfaIter::new(r"[ab]").unwrap().interned();
+        let sa: Vec<_> = std::iter::from_fn(|| a.next_symbol()).collect();
+        let (_, interner) = a.into_parts();
+
+        let mut b = NfaIter::new(r"[bc]").unwrap().with_interner(interner);
+        let sb: Vec<_> = std::iter::from_fn(|| b.next_symbol()).collect();
+        assert_eq!(sa[1], sb[0]);
+        let interner = b.into_parts().1;
+        assert_eq!(interner.len(), 3);
+        assert_eq!(interner.lookup(b"c"), Some(sb[1]));
+        assert_eq!(interner.lookup(b"d"), None);
+        assert_eq!(&**interner.get(sb[1]).unwrap(), b"c");
+        let all: Vec<&[u8]> = interner.iter().map(|s| &**s).collect();
+        assert_eq!(all, [b"a", b"b", b"c"]);
+
+        assert!(Interner::new().is_empty());
+    }
+}
@@ -56,6 +56,49 @@
 //!
 //! These guarantee that output strings are unique, but the search space will likely use more memory.
 //!
+//! With the `encoding_rs` feature, [`Utf8Iter::encode`] converts the outputs to other text encodings,
+//! like Shift_JIS, for testing systems that don't use UTF-8.
+//!
+//! With the `bytes` feature, [`BorrowNext::into_bytes`] produces the outputs as [`bytes::Bytes`]
+//! that share one buffer, to send generated payloads without copying them.
+//!
+//! ## Lazy DFA
+//!
+//! Using [`HybridIter`] you can traverse the regex using a [lazy DFA](regex_automata::hybrid), which
+//! only builds the DFA states that the search visits, within a bounded cache.
+//! There is no up-front determinization, so this is the fastest way to get the first few
+//! matches of a very large pattern.
+//!
+//! ## Choosing an engine at runtime
+//!
+//! Using [`RegexIter`] you can pick any of the NFA, DFA or lazy DFA engines with an [`Engine`] value,
+//! for example from configuration, with a single iterator type. [`RegexIter::auto`] picks one for you.
+//!
+//! ## Multiple patterns
+//!
+//! Using [`MultiIter`] you can iterate over the matches of several patterns, each with its own engine,
+//! and add more patterns while iterating.
+//!
+//! ## Derivatives
+//!
+//! Using [`BrzozowskiIter`] you can traverse the regex without building any automaton, by taking
+//! derivatives of the parsed regex as they are needed. These have almost no build cost.
+//!
+//! These guarantee that output strings are unique, like the DFA iterators.
+//!
+//! Using [`DerivIter`] you can instead traverse partial derivatives, which are to [`BrzozowskiIter`]
+//! what NFAs are to DFAs: much less memory, but output strings are not guaranteed to be unique.
+//!
+//! ## HIR
+//!
+//! Using [`HirIter`] you can traverse the structure of the parsed regex directly, which gives a
+//! predictable ordering for nested repetitions and alternations. See [`HirIter`] for the order.
+//!
+//! ## Property testing
+//!
+//! With the `proptest` feature, [`RegexStrategy`] generates matches as [`proptest`] inputs,
+//! and shrinks failing inputs to smaller matches with [`DfaIter::shrink`].
+//!
 //! ## Utf8
 //!
 //! Using [`Utf8Iter`] you can get the outputs of the NFA or DFA iterators as [`String`]
@@ -66,16 +109,109 @@
 use core::fmt;
 use std::error;
 
-pub use dfa::{DenseDfaIter, DfaIter, SparseDfaIter};
-pub use nfa::NfaIter;
+pub use adaptors::{
+    chain_dedup, interleave, interleave_all, stable_hash, BorrowNext, BoundedIter, ChainDedup,
+    Exhausted, Hashes, Interleave, Validated, WhileLenLe,
+};
+pub use brzozowski::BrzozowskiIter;
+pub use builder::Builder;
+pub use checksum::Checksum;
+pub use class::ByteClass;
+pub use dawg::{Dawg, InvalidDawg};
+pub use deriv::DerivIter;
+pub use diagnostic::PatternError;
+pub use dfa::{DenseDfaIter, DfaBuildError, DfaIter, DfaIterBuilder, SparseDfaIter};
+pub use dict::DictIter;
+#[cfg(feature = "encoding_rs")]
+pub use encoded::EncodedIter;
+pub use engine::{trial_dfa_size, Engine, EngineBuildError, EngineChoice, RegexIter};
+pub use export::{ExportFormat, Exporter};
+pub use flat::FlatMatches;
+pub use hir::HirIter;
+pub use hybrid::HybridIter;
+pub use index::LanguageIndex;
+pub use intern::{Interned, Interner, Symbol};
+pub use log::{Recorder, RecorderError};
+pub use multi::{MultiIter, PatternMatch};
+pub use nth::NthCache;
+pub use padded::PaddedIter;
+pub use nfa::{NfaIter, Unsupported};
+#[cfg(feature = "bytes")]
+pub use payload::BytesIter;
+pub use pipeline::PipelineIter;
+pub use profile::{DepthProfile, ProfileReport};
+pub use query::Analysis;
+pub use ranked::{RankedIter, ShuffledIter, SpreadIter, ThenRandomIter};
+/// Syntax options for the `new_with_syntax` constructors, like case insensitivity
+pub use regex_automata::util::syntax::Config as SyntaxConfig;
+pub use sample::{Locale, Sampler, Samples, ENGLISH_LETTER_FREQUENCIES};
+pub use shape::{FilledIter, ShapeIter, SkeletonIter};
+pub use show::ShowMatch;
+pub use shrink::ShrinkIter;
+#[cfg(feature = "proptest")]
+pub use strategy::{RegexStrategy, RegexValueTree};
+pub use term::DerivBuildError;
+pub use walk::Visitor;
+pub use weighted::WeightedIter;
 use regex_automata::dfa::Automaton;
 
+mod adaptors;
+mod brzozowski;
+mod builder;
+mod checksum;
+mod class;
+mod count;
+mod dawg;
+mod deriv;
 mod dfa;
+mod diagnostic;
+mod dict;
+pub mod difftest;
+mod dot;
+#[cfg(feature = "encoding_rs")]
+mod encoded;
+mod engine;
+mod export;
+mod flat;
+pub mod fuzzing;
+mod graph;
+mod hir;
+mod hybrid;
+mod index;
+mod intern;
+mod log;
+mod multi;
 mod nfa;
+mod nth;
+mod padded;
+#[cfg(feature = "bytes")]
+mod payload;
+mod pipeline;
+pub mod presets;
+mod profile;
+mod query;
+mod ranked;
+mod sample;
+mod shape;
+mod show;
+mod shrink;
+mod spill;
+#[cfg(feature = "proptest")]
+mod strategy;
+mod term;
+mod walk;
+mod weighted;
 
 /// [`NfaIter`] or [`DfaIter`] iterator with UTF8 [`String`]s as output
 pub struct Utf8Iter<I>(I);
 
+impl<I> Utf8Iter<I> {
+    /// Recover the regex iterator that this converts the matches of
+    pub fn into_inner(self) -> I {
+        self.0
+    }
+}
+
 #[derive(Debug)]
 /// Regex provided to [`Utf8Iter`] was not valid for generating UTF8 strings
 pub struct RegexNotUtf8;
@@ -88,6 +224,18 @@ impl fmt::Display for RegexNotUtf8 {
 
 impl error::Error for RegexNotUtf8 {}
 
+#[derive(Debug)]
+/// Regex provided matches an infinite number of strings, so the operation could not complete
+pub struct InfiniteLanguage;
+
+impl fmt::Display for InfiniteLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("regex matches infinitely many strings")
+    }
+}
+
+impl error::Error for InfiniteLanguage {}
+
 impl TryFrom<NfaIter> for Utf8Iter<NfaIter> {
     type Error = RegexNotUtf8;
     fn try_from(value: NfaIter) -> Result<Self, Self::Error> {
@@ -200,17 +348,15 @@ mod tests {
             "a".to_owned(),
             "b".to_owned(),
             "aa".to_owned(),
+            "ab".to_owned(),
             "bb".to_owned(),
+            "ba".to_owned(),
             "aaa".to_owned(),
+            "aab".to_owned(),
+            "abb".to_owned(),
+            "aba".to_owned(),
             "bbb".to_owned(),
-            "aaaa".to_owned(),
-            // technically a different path
-            "aa".to_owned(),
-            "ab".to_owned(),
-            "bbbb".to_owned(),
-            "ba".to_owned(),
-            // technically a different path
-            "bb".to_owned(),
+            "bba".to_owned(),
         ];
         assert_eq!(x, y);
     }
@@ -0,0 +1,352 @@
+#![allow(clippy::result_large_err)]
+
+use core::fmt;
+use std::{
+    error,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use regex_automata::dfa::dense;
+
+use crate::DenseDfaIter;
+
+const HEADER: &[u8] = b"regex-utils match log v1";
+
+/// `Recorder` produces the matches of a regex like [`DenseDfaIter`], and writes each of them to
+/// an append-only log file, so that a long generation job can be resumed after it is stopped.
+///
+/// ```
+/// use regex_utils::Recorder;
+///
+/// let path = std::env::temp_dir().join(format!("regex-utils-doc-{}.log", std::process::id()));
+///
+/// let mut recorder = Recorder::create(r"[a-z]{2}", &path, 100).unwrap();
+/// let first: Vec<Vec<u8>> = recorder.by_ref().take(300).map(Result::unwrap).collect();
+/// drop(recorder);
+///
+/// // later, carry on from where the log ends
+/// let recorder = Recorder::resume_from_log(&path, 100).unwrap();
+/// assert_eq!(recorder.recorded(), 300);
+/// let rest: Vec<Vec<u8>> = recorder.map(Result::unwrap).collect();
+/// assert_eq!(first.len() + rest.len(), 26 * 26);
+/// assert_eq!(rest[0], b"lo");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+///
+/// # Log format
+///
+/// The log is a text file with one record per line. Bytes are written in lowercase hex.
+///
+/// * A header line, `regex-utils match log v1`
+/// * `P <pattern>`: the regex
+/// * `M <match>`: a match, in the order they were produced
+/// * `C <count>`: a checkpoint. Every match before it has been synced to disk,
+///   and there are `count` of them.
+///
+/// A checkpoint is written every `checkpoint_every` matches, and when the recorder is dropped.
+/// If the job crashes, the log is resumed from the last checkpoint, and any records after it
+/// are discarded and produced again.
+pub struct Recorder {
+    iter: DenseDfaIter<Vec<u32>>,
+    out: BufWriter<File>,
+    // the number of matches written
+    count: u64,
+    // the number of matches written since the last checkpoint
+    pending: usize,
+    checkpoint_every: usize,
+}
+
+impl Recorder {
+    /// Start producing the matches of the regex, writing them to a new log file at `path`
+    pub fn create(
+        pattern: &str,
+        path: impl AsRef<Path>,
+        checkpoint_every: usize,
+    ) -> Result<Self, RecorderError> {
+        let iter = DenseDfaIter::new(pattern).map_err(RecorderError::Build)?;
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+
+        let mut out = BufWriter::new(file);
+        out.write_all(HEADER)?;
+        writeln!(out)?;
+        writeln!(out, "P {}", Hex(pattern.as_bytes()))?;
+        checkpoint(&mut out, 0)?;
+        Ok(Self {
+            iter,
+            out,
+            count: 0,
+            pending: 0,
+            checkpoint_every,
+        })
+    }
+
+    /// Continue producing the matches of the regex in the log file at `path`, from its last checkpoint
+    pub fn resume_from_log(
+        path: impl AsRef<Path>,
+        checkpoint_every: usize,
+    ) -> Result<Self, RecorderError> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        let mut reader = BufReader::new(&mut file);
+        let mut line = vec![];
+        let mut read_line = |line: &mut Vec<u8>| -> io::Result<bool> {
+            line.clear();
+            reader.read_until(b'\n', line)?;
+            // a line without a newline was not completely written
+            Ok(line.pop() == Some(b'\n'))
+        };
+
+        if !read_line(&mut line)? || line != HEADER {
+            return Err(RecorderError::InvalidLog);
+        }
+        if !read_line(&mut line)? {
+            return Err(RecorderError::InvalidLog);
+        }
+        let pattern = line
+            .strip_prefix(b"P ")
+            .and_then(unhex)
+            .and_then(|p| String::from_utf8(p).ok())
+            .ok_or(RecorderError::InvalidLog)?;
+        let mut offset = (HEADER.len() + 1 + line.len() + 1) as u64;
+
+        // (offset after the checkpoint, matches before it, the last match before it)
+        let mut resume = None;
+        let mut count = 0;
+        let mut last = None;
+        while read_line(&mut line)? {
+            offset += line.len() as u64 + 1;
+            if let Some(m) = line.strip_prefix(b"M ").and_then(unhex) {
+                count += 1;
+                last = Some(m);
+            } else if let Some(c) = line.strip_prefix(b"C ") {
+                let c: u64 = std::str::from_utf8(c)
+                    .ok()
+                    .and_then(|c| c.parse().ok())
+                    .ok_or(RecorderError::InvalidLog)?;
+                if c != count {
+                    return Err(RecorderError::InvalidLog);
+                }
+                resume = Some((offset, count, last.clone()));
+            } else {
+                // the rest of the log was not completely written
+                break;
+            }
+        }
+        let (offset, count, last) = resume.ok_or(RecorderError::InvalidLog)?;
+
+        let mut iter = DenseDfaIter::new(&pattern).map_err(RecorderError::Build)?;
+        if let Some(last) = last {
+            iter = iter.resume_after(&last).ok_or(RecorderError::InvalidLog)?;
+        }
+
+        // drop anything after the checkpoint, and append from there
+        file.set_len(offset)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            iter,
+            out: BufWriter::new(file),
+            count,
+            pending: 0,
+            checkpoint_every,
+        })
+    }
+
+    /// The number of matches that have been written to the log
+    pub fn recorded(&self) -> u64 {
+        self.count
+    }
+
+    /// Write a checkpoint, so that everything produced so far is kept if the job crashes
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        self.pending = 0;
+        checkpoint(&mut self.out, self.count)
+    }
+
+    /// Get the next matching string ref, after writing it to the log
+    pub fn record_next(&mut self) -> io::Result<Option<&[u8]>> {
+        if self.pending >= self.checkpoint_every {
+            self.checkpoint()?;
+        }
+        let Some(next) = self.iter.borrow_next() else {
+            if self.pending > 0 {
+                self.pending = 0;
+                checkpoint(&mut self.out, self.count)?;
+            }
+            return Ok(None);
+        };
+        writeln!(self.out, "M {}", Hex(next))?;
+        self.count += 1;
+        self.pending += 1;
+        Ok(Some(next))
+    }
+}
+
+/// Sync every match written so far, then record that they are complete
+fn checkpoint(out: &mut BufWriter<File>, count: u64) -> io::Result<()> {
+    out.flush()?;
+    out.get_ref().sync_data()?;
+    writeln!(out, "C {count}")?;
+    out.flush()?;
+    out.get_ref().sync_data()
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.pending > 0 {
+            let _ = self.checkpoint();
+        }
+    }
+}
+
+impl Iterator for Recorder {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.record_next()
+            .map(|next| next.map(ToOwned::to_owned))
+            .transpose()
+    }
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+fn unhex(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    hex.chunks_exact(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// The match log could not be created or resumed
+#[derive(Debug)]
+pub enum RecorderError {
+    /// The log file could not be read or written
+    Io(io::Error),
+    /// The pattern could not be compiled
+    Build(dense::BuildError),
+    /// The file is not a match log, or has no checkpoints
+    InvalidLog,
+}
+
+impl From<io::Error> for RecorderError {
+    fn from(err: io::Error) -> Self {
+        RecorderError::Io(err)
+    }
+}
+
+impl fmt::Display for RecorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecorderError::Io(err) => err.fmt(f),
+            RecorderError::Build(err) => err.fmt(f),
+            RecorderError::InvalidLog => f.write_str("invalid match log"),
+        }
+    }
+}
+
+impl error::Error for RecorderError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RecorderError::Io(err) => Some(err),
+            RecorderError::Build(err) => Some(err),
+            RecorderError::InvalidLog => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn log_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("regex-utils-{name}-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn resume() {
+        let path = log_path("resume");
+        let pattern = "(ab|b)*\n?";
+        let all: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().take(200).collect();
+
+        let mut recorder = Recorder::create(pattern, &path, 7).unwrap();
+        let mut x: Vec<Vec<u8>> = recorder.by_ref().take(50).map(Result::unwrap).collect();
+        // simulate a crash after the last checkpoint
+        recorder.out.flush().unwrap();
+        std::mem::forget(recorder);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"M 6").unwrap();
+        drop(file);
+
+        // matches after the checkpoint at 49 are produced again
+        let recorder = Recorder::resume_from_log(&path, 7).unwrap();
+        assert_eq!(recorder.recorded(), 49);
+        x.truncate(49);
+        x.extend(recorder.take(151).map(Result::unwrap));
+        assert_eq!(x, all);
+
+        // every match is in the log once
+        let recorder = Recorder::resume_from_log(&path, 7).unwrap();
+        assert_eq!(recorder.recorded(), 200);
+        drop(recorder);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn finished() {
+        let path = log_path("finished");
+        let recorder = Recorder::create(r"[ab]", &path, 100).unwrap();
+        assert_eq!(recorder.recorded(), 0);
+        assert_eq!(recorder.map(Result::unwrap).count(), 2);
+
+        let recorder = Recorder::resume_from_log(&path, 100).unwrap();
+        assert_eq!(recorder.recorded(), 2);
+        drop(recorder);
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            log,
+            "regex-utils match log v1\nP 5b61625d\nC 0\nM 61\nM 62\nC 2\n"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid() {
+        let path = log_path("invalid");
+        std::fs::write(&path, "not a log\n").unwrap();
+        assert!(matches!(
+            Recorder::resume_from_log(&path, 1),
+            Err(RecorderError::InvalidLog)
+        ));
+        std::fs::write(&path, "regex-utils match log v1\nP 61\nM 62\nC 1\n").unwrap();
+        assert!(matches!(
+            Recorder::resume_from_log(&path, 1),
+            Err(RecorderError::InvalidLog)
+        ));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            Recorder::create("(", &path, 1),
+            Err(RecorderError::Build(_))
+        ));
+    }
+}
@@ -0,0 +1,377 @@
+#![allow(clippy::result_large_err)]
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use regex_automata::util::primitives::PatternID;
+
+use crate::{BorrowNext, EngineBuildError, Exporter, PatternError, RegexIter};
+
+/// `MultiIter` will produce every possible string value that will match with any of the given regexes,
+/// and can have more patterns added while it is being iterated.
+///
+/// ```
+/// use regex_utils::MultiIter;
+///
+/// let mut iter = MultiIter::new_many(&[r"[ab]{2}", r"c"]).unwrap();
+/// assert_eq!(iter.next().unwrap(), b"c");
+/// assert_eq!(iter.next().unwrap(), b"aa");
+///
+/// // the shorter matches of the new pattern are produced first
+/// iter.push_pattern(r"d?").unwrap();
+/// let x: Vec<Vec<u8>> = iter.collect();
+/// assert_eq!(x, [
+///     b"".to_vec(),
+///     b"d".to_vec(),
+///     b"ab".to_vec(),
+///     b"ba".to_vec(),
+///     b"bb".to_vec(),
+/// ]);
+/// ```
+///
+/// # Note
+///
+/// Regexes can be infinite (eg `a*`). Either use this iterator lazily, or limit the number
+/// of iterations.
+///
+/// Each pattern produces its own matches, so a string that matches several patterns is
+/// produced once for each of them.
+///
+/// # Implementation Details
+///
+/// Unlike the `new_many` constructors of the other iterators, which compile every pattern into
+/// one automaton, each pattern here gets its own [`RegexIter`], with the engine chosen by
+/// [`RegexIter::auto`]. Matches are produced in order of length, and matches of the same length
+/// are produced in pattern order.
+///
+/// This means adding a pattern doesn't need to recompile the others, and they keep their place
+/// in the search. The new pattern starts from the shortest length, and catches up before the
+/// others continue.
+#[derive(Default)]
+pub struct MultiIter {
+    // (pattern, iterator, its next match) for each pattern with matches left
+    iters: Vec<(PatternID, RegexIter, Vec<u8>)>,
+    // the source of every pattern added so far, indexed by pattern ID
+    patterns: Vec<String>,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl MultiIter {
+    /// Parse the given regular expressions using the engines that [`RegexIter::auto`] picks for them
+    /// and return the corresponding `MultiIter`.
+    pub fn new_many<P: AsRef<str>>(patterns: &[P]) -> Result<Self, EngineBuildError> {
+        let mut iter = Self::default();
+        for pattern in patterns {
+            iter.push_pattern(pattern.as_ref())?;
+        }
+        Ok(iter)
+    }
+
+    /// Parse the given regular expressions like [`MultiIter::new_many`], skipping the patterns
+    /// that fail to compile, and return the errors for those.
+    ///
+    /// Every pattern still gets the ID of its index, so the skipped patterns are disabled.
+    ///
+    /// ```
+    /// use regex_utils::MultiIter;
+    ///
+    /// let (iter, errors) = MultiIter::new_many_lenient(&[r"a", r"(b", r"c"]);
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].index(), Some(1));
+    /// assert_eq!(errors[0].pattern(), "(b");
+    ///
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x, [b"a".to_vec(), b"c".to_vec()]);
+    /// ```
+    pub fn new_many_lenient<P: AsRef<str>>(
+        patterns: &[P],
+    ) -> (Self, Vec<PatternError<EngineBuildError>>) {
+        let mut iter = Self::default();
+        let mut errors = vec![];
+        for (i, pattern) in patterns.iter().enumerate() {
+            let pattern = pattern.as_ref();
+            if let Err(err) = iter.push_pattern(pattern) {
+                errors.push(PatternError::new(pattern, err).with_index(i));
+                iter.patterns.push(pattern.to_owned());
+            }
+        }
+        (iter, errors)
+    }
+
+    /// Add another pattern to the ongoing iteration, returning its ID.
+    ///
+    /// Any of its matches that are shorter than the current match are produced next.
+    pub fn push_pattern(&mut self, pattern: &str) -> Result<PatternID, EngineBuildError> {
+        let id = PatternID::new(self.patterns.len()).expect("too many patterns");
+        let iter = RegexIter::auto(pattern)?;
+        self.patterns.push(pattern.to_owned());
+        self.add_compiled(id, iter);
+        Ok(id)
+    }
+
+    /// A `MultiIter` that knows the source of every pattern up front, and only iterates the
+    /// patterns given to [`MultiIter::add_compiled`]
+    pub(crate) fn with_patterns(patterns: Vec<String>) -> Self {
+        Self {
+            iters: vec![],
+            patterns,
+            str: vec![],
+        }
+    }
+
+    /// Start iterating the compiled pattern with the given ID.
+    /// Patterns are kept in order of their ID, so ties still go to the first pattern
+    pub(crate) fn add_compiled(&mut self, id: PatternID, mut iter: RegexIter) {
+        if let Some(next) = iter.borrow_next() {
+            let next = next.to_vec();
+            let at = self.iters.partition_point(|(other, _, _)| *other < id);
+            self.iters.insert(at, (id, iter, next));
+        }
+    }
+
+    /// Whether any pattern has matches left
+    pub(crate) fn has_matches(&self) -> bool {
+        !self.iters.is_empty()
+    }
+
+    /// Stop producing matches for the given pattern, and stop searching for them.
+    ///
+    /// Returns `false` if the pattern was already disabled or had no more matches.
+    ///
+    /// ```
+    /// use regex_utils::MultiIter;
+    ///
+    /// let mut iter = MultiIter::new_many(&[r"a+", r"b+"]).unwrap();
+    /// let a = iter.borrow_next_with_pattern().unwrap().pattern;
+    /// assert!(iter.disable_pattern(a));
+    ///
+    /// let x: Vec<Vec<u8>> = iter.take(3).collect();
+    /// assert_eq!(x, [b"b".to_vec(), b"bb".to_vec(), b"bbb".to_vec()]);
+    /// ```
+    pub fn disable_pattern(&mut self, id: PatternID) -> bool {
+        let len = self.iters.len();
+        self.iters.retain(|(pattern, _, _)| *pattern != id);
+        self.iters.len() != len
+    }
+
+    /// The source of the pattern with the given ID, even if it is disabled.
+    /// Returns `None` if there is no such pattern.
+    pub fn pattern_text(&self, id: PatternID) -> Option<&str> {
+        self.patterns.get(id.as_usize()).map(String::as_str)
+    }
+
+    /// Collect up to `limit_per_pattern` matches of every pattern, in the order they are produced.
+    ///
+    /// Each pattern is disabled once it reaches its limit, so the search stops as soon as every
+    /// pattern has its matches. Every pattern has an entry in the map, even if it has no matches.
+    ///
+    /// ```
+    /// use regex_utils::MultiIter;
+    ///
+    /// let mut iter = MultiIter::new_many(&[r"a*", r"[0-9]"]).unwrap();
+    /// let ids: Vec<_> = (0..2).map(|i| regex_automata::PatternID::new(i).unwrap()).collect();
+    /// let x = iter.collect_by_pattern(3);
+    /// assert_eq!(x[&ids[0]], [b"".to_vec(), b"a".to_vec(), b"aa".to_vec()]);
+    /// assert_eq!(x[&ids[1]], [b"0".to_vec(), b"1".to_vec(), b"2".to_vec()]);
+    /// ```
+    pub fn collect_by_pattern(
+        &mut self,
+        limit_per_pattern: usize,
+    ) -> HashMap<PatternID, Vec<Vec<u8>>> {
+        let mut matches: HashMap<PatternID, Vec<Vec<u8>>> = (0..self.patterns.len())
+            .map(|i| (PatternID::new_unchecked(i), vec![]))
+            .collect();
+
+        if limit_per_pattern == 0 {
+            self.iters.clear();
+        }
+        while let Some(m) = self.borrow_next_with_pattern() {
+            let pattern = m.pattern;
+            let found = matches.get_mut(&pattern).unwrap();
+            found.push(m.bytes.to_vec());
+            if found.len() >= limit_per_pattern {
+                self.disable_pattern(pattern);
+            }
+        }
+        matches
+    }
+
+    /// Get the next matching string ref from this regex iterator,
+    /// along with the pattern that it matches
+    ///
+    /// ```
+    /// use regex_utils::MultiIter;
+    ///
+    /// let mut iter = MultiIter::new_many(&[r"[0-9]{2}", r"[a-z]"]).unwrap();
+    /// let m = iter.borrow_next_with_pattern().unwrap();
+    /// assert_eq!(m.bytes, b"a");
+    /// assert_eq!(m.pattern.as_usize(), 1);
+    /// assert_eq!(m.pattern_text, r"[a-z]");
+    /// ```
+    pub fn borrow_next_with_pattern(&mut self) -> Option<PatternMatch<'_>> {
+        // the shortest next match, with ties going to the first pattern
+        let (_, i) = self
+            .iters
+            .iter()
+            .enumerate()
+            .map(|(i, (_, _, next))| (next.len(), i))
+            .min()?;
+
+        let (id, iter, next) = &mut self.iters[i];
+        let id = *id;
+        std::mem::swap(&mut self.str, next);
+        match iter.borrow_next() {
+            Some(s) => {
+                next.clear();
+                next.extend_from_slice(s);
+            }
+            // this pattern has no more matches
+            None => {
+                self.iters.remove(i);
+            }
+        }
+        Some(PatternMatch {
+            pattern: id,
+            pattern_text: &self.patterns[id],
+            bytes: &self.str,
+        })
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        self.borrow_next_with_pattern().map(|m| m.bytes)
+    }
+
+    /// Write up to `limit` of the next matches, across all patterns, as records of `exporter`,
+    /// and return how many were written. See [`Exporter`]
+    pub fn export_to<W: Write>(
+        &mut self,
+        exporter: &mut Exporter<W>,
+        limit: usize,
+    ) -> io::Result<usize> {
+        let mut n = 0;
+        while n < limit {
+            let Some(m) = self.borrow_next_with_pattern() else {
+                break;
+            };
+            exporter.write_match(m)?;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// A matching string from a [`MultiIter`], with the pattern that it matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PatternMatch<'a> {
+    /// The ID of the pattern, in the order the patterns were added
+    pub pattern: PatternID,
+    /// The source of the pattern
+    pub pattern_text: &'a str,
+    /// The matching string
+    pub bytes: &'a [u8],
+}
+
+impl BorrowNext for MultiIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        MultiIter::borrow_next(self)
+    }
+}
+
+impl Iterator for MultiIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push() {
+        let mut iter = MultiIter::new_many(&[r"a+"]).unwrap();
+        let x: Vec<Vec<u8>> = iter.by_ref().take(3).collect();
+        assert_eq!(x, [b"a".to_vec(), b"aa".to_vec(), b"aaa".to_vec()]);
+
+        let id = iter.push_pattern(r"[0-9]{2}|x").unwrap();
+        assert_eq!(id, PatternID::new(1).unwrap());
+
+        // the new pattern catches up before `a+` continues
+        let m = iter.borrow_next_with_pattern().unwrap();
+        assert_eq!((m.pattern, m.bytes), (id, &b"x"[..]));
+        assert_eq!(m.pattern_text, r"[0-9]{2}|x");
+        let x: Vec<Vec<u8>> = iter.by_ref().take(101).collect();
+        assert_eq!(x[..2], [b"00".to_vec(), b"01".to_vec()]);
+        assert_eq!(x[100], b"aaaa");
+    }
+
+    #[test]
+    fn disable() {
+        let mut iter = MultiIter::new_many(&[r"a", r"b{1,2}", r"c{0,3}"]).unwrap();
+        assert_eq!(iter.next().unwrap(), b"");
+
+        let b = PatternID::new(1).unwrap();
+        assert!(iter.disable_pattern(b));
+        assert!(!iter.disable_pattern(b));
+        assert!(!iter.disable_pattern(PatternID::new(3).unwrap()));
+        assert_eq!(iter.pattern_text(b), Some(r"b{1,2}"));
+        assert_eq!(iter.pattern_text(PatternID::new(3).unwrap()), None);
+
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(
+            x,
+            [
+                b"a".to_vec(),
+                b"c".to_vec(),
+                b"cc".to_vec(),
+                b"ccc".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn collect_by_pattern() {
+        let mut iter = MultiIter::new_many(&[r"a+", r"b{1,2}", r"[^\s\S]"]).unwrap();
+        let x = iter.collect_by_pattern(3);
+        let id = |i| PatternID::new(i).unwrap();
+        assert_eq!(x.len(), 3);
+        assert_eq!(x[&id(0)], [b"a".to_vec(), b"aa".to_vec(), b"aaa".to_vec()]);
+        assert_eq!(x[&id(1)], [b"b".to_vec(), b"bb".to_vec()]);
+        assert!(x[&id(2)].is_empty());
+        // every pattern reached its limit or ran out
+        assert!(iter.next().is_none());
+
+        let mut iter = MultiIter::new_many(&[r"a+"]).unwrap();
+        assert!(iter.collect_by_pattern(0)[&id(0)].is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let mut iter = MultiIter::new_many(&[r"a"]).unwrap();
+        assert!(iter.push_pattern(r"(").is_err());
+        // failed patterns don't take an ID
+        assert_eq!(iter.push_pattern(r"b").unwrap(), PatternID::new(1).unwrap());
+        assert!(MultiIter::new_many(&[r"a", r"("]).is_err());
+    }
+
+    #[test]
+    fn add_compiled() {
+        let patterns = [r"b", r"a", r"c"];
+        let mut iter = MultiIter::with_patterns(patterns.map(String::from).to_vec());
+        assert!(!iter.has_matches());
+        // compiled out of order, but ties still go to the first pattern
+        for i in [2, 0, 1] {
+            let compiled = RegexIter::auto(patterns[i]).unwrap();
+            iter.add_compiled(PatternID::new(i).unwrap(), compiled);
+        }
+        assert!(iter.has_matches());
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(x, [b"b".to_vec(), b"a".to_vec(), b"c".to_vec()]);
+    }
+}
@@ -1,16 +1,24 @@
 #![allow(clippy::result_large_err)]
 
+use std::collections::HashSet;
+
 use regex_automata::{
+    dfa::dense,
     nfa::thompson::{BuildError, State, NFA},
-    util::{look::Look, primitives::StateID},
+    util::{
+        look::{Look, LookSet},
+        primitives::{PatternID, StateID},
+    },
+};
+
+use crate::{
+    dfa::Prune, diagnostic::build_many_checked, shrink::is_match, BorrowNext, BoundedIter,
+    DenseDfaIter, PatternError, SyntaxConfig,
 };
-use tinyvec::TinyVec;
 
-/// For Look/Union/BinaryUnion/Capture/Fail/Match: meaningless (should be empty)
-/// For ByteRange: indicates the current byte
-/// For Sparse: indicates the current byte for each ByteRange
-/// For Dense: indicates the current byte (0..=255)
-type SearchRange = TinyVec<[u16; 12]>;
+/// The states that can be reached from a state without matching any bytes.
+/// (state, look-arounds that must hold)
+type Closure = Vec<(StateID, LookSet)>;
 
 /// `NfaIter` will produce every possible string value that will match with the given nfa regex.
 ///
@@ -18,21 +26,53 @@ type SearchRange = TinyVec<[u16; 12]>;
 ///
 /// Regexes can be infinite (eg `a*`). Either use this iterator lazily, or limit the number
 /// of iterations.
+///
+/// Strings are produced in order of length. Strings of the same length are produced in the
+/// priority order of the regex, eg `b|a` produces `b` before `a`, unless [`NfaIter::sorted`] is set.
 pub struct NfaIter {
     // the graph to search
     pub(crate) regex: NFA,
     // the start node of the graph
     start: StateID,
-    start_range: SearchRange,
-    // the max depth we currently want to search
+    // the capture slot that counts as a match, instead of the match states
+    end_slot: Option<usize>,
+    // the epsilon closure of each state, indexed by state id, computed when first needed
+    closures: Vec<Option<Closure>>,
+    // the length of string we currently want to search for
     depth: usize,
-    // the max depth observed in the graph
+    // the max length observed in the graph
     max_depth: usize,
-    // (state, search_range, byte depth, search depth)
-    // the search_range is used differently depending on what state we are exploring
-    stack: Vec<(StateID, SearchRange, usize, usize)>,
-    // the current path
+    // the max length to search, see [`NfaIter::max_depth`]
+    limit: usize,
+    // (state, offset into ranges, byte depth)
+    stack: Vec<(StateID, usize, usize)>,
+    // the search ranges of the frames on the stack, allocated in stack order.
+    // the search range is used differently depending on what state we are exploring
+    //
+    // For Match: meaningless (should be empty)
+    // For ByteRange: indicates the current byte
+    // For Sparse: indicates the current byte for each ByteRange
+    // For Dense: indicates the current byte (0..=255)
+    ranges: Vec<u16>,
+    // the current path. frames record their byte depth, so backtracking is just a truncate
     str: Vec<u8>,
+    // whether `str` is the last match produced
+    matched: bool,
+    // prefixes that the search should not continue from
+    prune: Option<Prune>,
+    // if set, the strings of each length are buffered to produce them in sorted order
+    sorted: Option<SortedLen>,
+}
+
+/// The strings of one length, buffered so they can be produced in sorted order
+#[derive(Default)]
+struct SortedLen {
+    // the strings of the current length, sorted and deduplicated
+    strs: Vec<Vec<u8>>,
+    // the index of the next string to produce
+    next: usize,
+    // the first string of the next length, found when the current length ran out
+    pending: Option<Vec<u8>>,
 }
 
 impl From<NFA> for NfaIter {
@@ -40,35 +80,125 @@ impl From<NFA> for NfaIter {
         // anchored because if we didn't anchor our search we would have an infinite amount of prefixes that were valid
         // and that isn't very interesting
         let start = nfa.start_anchored();
-        let start_range = range_for(nfa.state(start));
+        NfaIter::new_at(nfa, start, None)
+    }
+}
+
+impl NfaIter {
+    fn new_at(nfa: NFA, start: StateID, end_slot: Option<usize>) -> Self {
+        let closures = vec![None; nfa.states().len()];
 
-        Self {
+        let mut iter = Self {
             regex: nfa,
-            stack: vec![(start, start_range.clone(), 0, 0)],
+            stack: vec![],
+            ranges: vec![],
             start,
-            start_range,
+            end_slot,
+            closures,
             depth: 0,
             max_depth: 0,
+            limit: usize::MAX,
             str: vec![],
-        }
+            matched: false,
+            prune: None,
+            sorted: None,
+        };
+        iter.push_closure(start, 0);
+        iter
     }
 }
 
-fn range_for(s: &State) -> SearchRange {
+fn push_range(s: &State, ranges: &mut Vec<u16>) {
     match s {
-        State::ByteRange { trans } => tinyvec::tiny_vec![trans.start as u16],
-        State::Sparse(s) => s
-            .transitions
-            .iter()
-            .map(|trans| trans.start as u16)
-            .collect(),
-        State::Dense(_) => tinyvec::tiny_vec![0],
-        State::Look { .. } => tinyvec::tiny_vec![],
-        State::Union { .. } => tinyvec::tiny_vec![],
-        State::BinaryUnion { .. } => tinyvec::tiny_vec![],
-        State::Capture { .. } => tinyvec::tiny_vec![],
-        State::Fail => tinyvec::tiny_vec![],
-        State::Match { .. } => tinyvec::tiny_vec![],
+        State::ByteRange { trans } => ranges.push(trans.start as u16),
+        State::Sparse(s) => ranges.extend(s.transitions.iter().map(|trans| trans.start as u16)),
+        State::Dense(_) => ranges.push(0),
+        _ => {}
+    }
+}
+
+fn range_len(s: &State) -> usize {
+    match s {
+        State::ByteRange { .. } => 1,
+        State::Sparse(s) => s.transitions.len(),
+        State::Dense(_) => 1,
+        _ => 0,
+    }
+}
+
+/// Find all the byte matching states and match states reachable from `start` through
+/// Union/BinaryUnion/Capture/Look states, in priority order.
+///
+/// If `end_slot` is set, then the capture states for that slot are treated as match states.
+///
+/// Each state is only visited once per set of look-arounds, so epsilon cycles are not followed
+/// and every closure is finite.
+fn closure(nfa: &NFA, start: StateID, end_slot: Option<usize>) -> Closure {
+    let mut closure = vec![];
+    let mut seen = HashSet::new();
+    let mut stack = vec![(start, LookSet::empty())];
+    while let Some((current, looks)) = stack.pop() {
+        if !seen.insert((current, looks.bits)) {
+            continue;
+        }
+        match nfa.state(current) {
+            State::ByteRange { .. } | State::Sparse(_) | State::Dense(_) | State::Match { .. } => {
+                closure.push((current, looks));
+            }
+            State::Look { look, next } => stack.push((*next, looks.insert(*look))),
+            State::Union { alternates } => {
+                for &alt in alternates.iter().rev() {
+                    stack.push((alt, looks));
+                }
+            }
+            State::BinaryUnion { alt1, alt2 } => {
+                stack.push((*alt2, looks));
+                stack.push((*alt1, looks));
+            }
+            State::Capture { slot, .. } if Some(slot.as_usize()) == end_slot => {
+                closure.push((current, looks));
+            }
+            State::Capture { next, .. } => stack.push((*next, looks)),
+            State::Fail => {}
+        }
+    }
+    closure
+}
+
+/// A part of the regex that [`NfaIter`] can't produce matches for faithfully.
+/// See [`NfaIter::supports_generation`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unsupported {
+    /// A word boundary assertion, like `\b`, which `NfaIter` panics on
+    WordBoundary {
+        /// The kind of word boundary
+        look: Look,
+        /// The NFA state of the assertion
+        state: StateID,
+    },
+    /// An end of text or end of line assertion that can be followed by more bytes.
+    /// `NfaIter` assumes these hold, so it can produce strings that don't match
+    EndFollowedByBytes {
+        /// The kind of assertion
+        look: Look,
+        /// The NFA state of the assertion
+        state: StateID,
+    },
+}
+
+/// Whether the look-around holds after matching `str`
+fn look_holds(look: Look, str: &[u8]) -> bool {
+    match look {
+        Look::Start => str.is_empty(),
+        Look::StartLF => matches!(str.last(), None | Some(b'\n')),
+        Look::StartCRLF => matches!(str.last(), None | Some(b'\n' | b'\r')),
+        Look::End => true,
+        Look::EndLF => true,
+        Look::EndCRLF => true,
+        Look::WordAscii => todo!(),
+        Look::WordAsciiNegate => todo!(),
+        Look::WordUnicode => todo!(),
+        Look::WordUnicodeNegate => todo!(),
     }
 }
 
@@ -95,145 +225,394 @@ impl NfaIter {
         NFA::compiler().build_many(patterns).map(Self::from)
     }
 
-    fn range_for(&self, s: StateID) -> SearchRange {
-        range_for(self.regex.state(s))
+    /// Like [`NfaIter::new_many`], but if the patterns fail to compile, the error says
+    /// which pattern is to blame.
+    ///
+    /// See [`DenseDfaIter::new_many_checked`] for details
+    pub fn new_many_checked<P: AsRef<str>>(
+        patterns: &[P],
+    ) -> Result<Self, PatternError<BuildError>> {
+        build_many_checked(patterns, Self::new_many)
+    }
+
+    /// Parse the given regular expression with the given syntax options, like case insensitivity,
+    /// and return the corresponding `NfaIter`.
+    ///
+    /// ```
+    /// use regex_utils::{NfaIter, SyntaxConfig};
+    ///
+    /// let iter = NfaIter::new_with_syntax(r"ab", SyntaxConfig::new().case_insensitive(true)).unwrap();
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x, [b"AB".to_vec(), b"Ab".to_vec(), b"aB".to_vec(), b"ab".to_vec()]);
+    /// ```
+    pub fn new_with_syntax(pattern: &str, syntax: SyntaxConfig) -> Result<Self, BuildError> {
+        NFA::compiler()
+            .syntax(syntax)
+            .build(pattern)
+            .map(Self::from)
+    }
+
+    /// Check an NFA for assertions that `NfaIter` can't handle faithfully,
+    /// instead of finding out by a panic or a wrong match during iteration.
+    ///
+    /// This takes the NFA rather than an iterator, since creating the iterator can already panic.
+    ///
+    /// ```
+    /// use regex_automata::nfa::thompson::NFA;
+    /// use regex_utils::{NfaIter, Unsupported};
+    ///
+    /// let nfa = NFA::new(r"^[a-z]+$").unwrap();
+    /// assert!(NfaIter::supports_generation(&nfa).is_ok());
+    /// let iter = NfaIter::from(nfa);
+    ///
+    /// let nfa = NFA::new(r"a$b|\bc").unwrap();
+    /// let unsupported = NfaIter::supports_generation(&nfa).unwrap_err();
+    /// assert!(matches!(unsupported[0], Unsupported::EndFollowedByBytes { .. }));
+    /// assert!(matches!(unsupported[1], Unsupported::WordBoundary { .. }));
+    /// ```
+    pub fn supports_generation(nfa: &NFA) -> Result<(), Vec<Unsupported>> {
+        let mut unsupported = vec![];
+        for (i, state) in nfa.states().iter().enumerate() {
+            let &State::Look { look, next } = state else {
+                continue;
+            };
+            let state = StateID::new(i).unwrap();
+            match look {
+                Look::WordAscii
+                | Look::WordAsciiNegate
+                | Look::WordUnicode
+                | Look::WordUnicodeNegate => {
+                    unsupported.push(Unsupported::WordBoundary { look, state });
+                }
+                Look::End | Look::EndLF | Look::EndCRLF => {
+                    let followed = closure(nfa, next, None).iter().any(|&(id, _)| {
+                        matches!(
+                            nfa.state(id),
+                            State::ByteRange { .. } | State::Sparse(_) | State::Dense(_)
+                        )
+                    });
+                    if followed {
+                        unsupported.push(Unsupported::EndFollowedByBytes { look, state });
+                    }
+                }
+                Look::Start | Look::StartLF | Look::StartCRLF => {}
+            }
+        }
+        if unsupported.is_empty() {
+            Ok(())
+        } else {
+            Err(unsupported)
+        }
+    }
+
+    /// Iterate over the strings matched by just the capture group with the given index,
+    /// reusing the already compiled regex. Returns `None` if there is no such group.
+    ///
+    /// For multi-pattern regexes, this uses the groups of the first pattern.
+    ///
+    /// ```
+    /// use regex_utils::NfaIter;
+    ///
+    /// let iter = NfaIter::new(r"([a-z]+)@([a-z]+\.(?:com|org))").unwrap();
+    /// let domains: Vec<Vec<u8>> = iter.group_iter(2).unwrap().take(3).collect();
+    /// assert_eq!(domains, [b"a.com".to_vec(), b"a.org".to_vec(), b"b.com".to_vec()]);
+    /// ```
+    pub fn group_iter(&self, group_index: usize) -> Option<NfaIter> {
+        let (start_slot, end_slot) = self
+            .regex
+            .group_info()
+            .slots(PatternID::ZERO, group_index)?;
+
+        // repetitions can duplicate the group, but every copy matches the same strings
+        let start = self.regex.states().iter().find_map(|state| match state {
+            State::Capture { next, slot, .. } if slot.as_usize() == start_slot => Some(*next),
+            _ => None,
+        })?;
+        let mut iter = NfaIter::new_at(self.regex.clone(), start, Some(end_slot));
+        iter.sorted = self.sorted.is_some().then(SortedLen::default);
+        Some(iter)
+    }
+
+    /// The NFA that this iterator searches
+    ///
+    /// ```
+    /// use regex_utils::NfaIter;
+    ///
+    /// let iter = NfaIter::new(r"[a-z]+@[a-z]+").unwrap();
+    /// assert_eq!(iter.as_nfa().pattern_len(), 1);
+    /// ```
+    pub fn as_nfa(&self) -> &NFA {
+        &self.regex
+    }
+
+    /// Recover the NFA that this iterator searches, to reuse it elsewhere
+    pub fn into_inner(self) -> NFA {
+        self.regex
+    }
+
+    /// Build a dense DFA from this iterator's NFA, to produce each string only once.
+    ///
+    /// The [`DfaIter`](crate::DfaIter) keeps the [`NfaIter::prune_if`] predicate and the
+    /// [`NfaIter::max_depth`] limit, and continues from just after the last match that this
+    /// iterator produced, like [`DfaIter::resume_after`](crate::DfaIter::resume_after).
+    /// The DFA produces each length in byte order, so this continues exactly where a
+    /// [`NfaIter::sorted`] iterator left off. In priority order, the rest of the current length
+    /// is the matches that sort after the last one.
+    ///
+    /// # Panics
+    ///
+    /// If this iterator is for a capture group from [`NfaIter::group_iter`], since DFAs can only
+    /// search from the start of the regex.
+    ///
+    /// ```
+    /// use regex_utils::NfaIter;
+    ///
+    /// let mut iter = NfaIter::new(r"(a|ab)(c|bcd)").unwrap();
+    /// let x: Vec<Vec<u8>> = iter.by_ref().take(3).collect();
+    /// assert_eq!(x, [b"ac".to_vec(), b"abc".to_vec(), b"abcd".to_vec()]);
+    ///
+    /// // continues after `abcd`
+    /// let x: Vec<Vec<u8>> = iter.determinize().unwrap().collect();
+    /// assert_eq!(x, [b"abbcd".to_vec()]);
+    /// ```
+    pub fn determinize(&self) -> Result<DenseDfaIter<Vec<u32>>, dense::BuildError> {
+        assert!(
+            self.end_slot.is_none(),
+            "capture group iterators can't be determinized"
+        );
+        let dfa = dense::Builder::new()
+            .configure(dense::Config::new().accelerate(false))
+            .build_from_nfa(&self.regex)?;
+        let mut iter = DenseDfaIter::from(dfa);
+        iter.prune = self.prune.clone();
+        let last = match &self.sorted {
+            Some(sorted) => sorted.next.checked_sub(1).map(|i| &sorted.strs[i][..]),
+            None => self.matched.then_some(&self.str[..]),
+        };
+        match last {
+            // the DFA might not accept every path of the NFA, like `cc` in `(a)?|cc`
+            Some(last) if is_match(&iter.graph, last) => {
+                iter = iter.resume_after(last).expect("the last match is a match");
+            }
+            // every match of the current length has been produced
+            _ if self.sorted.is_none() && self.stack.is_empty() => iter.depth = self.depth + 1,
+            _ => iter.depth = self.depth,
+        }
+        Ok(iter.max_depth(self.limit).into_inner())
+    }
+
+    fn is_match(&self, state: &State) -> bool {
+        match (state, self.end_slot) {
+            (State::Match { .. }, None) => true,
+            (State::Capture { slot, .. }, Some(end_slot)) => slot.as_usize() == end_slot,
+            _ => false,
+        }
+    }
+
+    /// Don't search any further from the prefixes that `prune` returns `true` for, and don't
+    /// produce them. This restarts the iteration. See [`DfaIter::prune_if`](crate::DfaIter::prune_if)
+    ///
+    /// ```
+    /// use regex_utils::NfaIter;
+    ///
+    /// let iter = NfaIter::new(r"a+b+").unwrap().prune_if(|prefix| prefix.ends_with(b"aaa"));
+    /// let x: Vec<Vec<u8>> = iter.take(5).collect();
+    /// assert_eq!(x, [
+    ///     b"ab".to_vec(),
+    ///     b"aab".to_vec(),
+    ///     b"abb".to_vec(),
+    ///     // `aaab` is pruned
+    ///     b"aabb".to_vec(),
+    ///     b"abbb".to_vec(),
+    /// ]);
+    /// ```
+    pub fn prune_if(mut self, prune: impl Fn(&[u8]) -> bool + Send + Sync + 'static) -> Self {
+        self.prune = Some(std::sync::Arc::new(prune));
+        self.depth = 0;
+        self.max_depth = 0;
+        self.stack.clear();
+        self.ranges.clear();
+        self.str.clear();
+        self.matched = false;
+        self.push_closure(self.start, 0);
+        self
+    }
+
+    /// Produce the strings of each length in sorted byte order, rather than in the priority order
+    /// of the regex. Since each length is sorted, duplicate strings are also removed.
+    ///
+    /// The priority order depends on how the regex is compiled, which can change between versions
+    /// of regex-automata, so use this for golden tests that compare against generated output.
+    /// All the strings of one length are buffered at a time, so this needs more memory for
+    /// large languages.
+    ///
+    /// ```
+    /// use regex_utils::NfaIter;
+    ///
+    /// let iter = NfaIter::new(r"b|a|(?:a|c)").unwrap().sorted(true);
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x, [b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    /// ```
+    pub fn sorted(mut self, yes: bool) -> Self {
+        self.sorted = yes.then(SortedLen::default);
+        self
+    }
+
+    /// Only search for matches of up to `n` bytes, so the iteration is always finite.
+    ///
+    /// The search stops once it has searched the matches of length `n`, rather than
+    /// filtering out the longer matches after searching for them.
+    ///
+    /// ```
+    /// use regex_utils::NfaIter;
+    ///
+    /// let iter = NfaIter::new(r"(a|b)*").unwrap().max_depth(2);
+    /// let x: Vec<Vec<u8>> = iter.collect();
+    /// assert_eq!(x.len(), 1 + 2 + 4);
+    /// ```
+    pub fn max_depth(mut self, n: usize) -> BoundedIter<Self> {
+        self.limit = n;
+        if self.depth > n {
+            // already past the bound
+            self.stack.clear();
+            self.ranges.clear();
+            if let Some(sorted) = &mut self.sorted {
+                sorted.pending = None;
+                if sorted.strs.first().is_some_and(|s| s.len() > n) {
+                    sorted.next = sorted.strs.len();
+                }
+            }
+        }
+        BoundedIter::new(self, n)
+    }
+
+    /// Push every state in the closure of `state` whose look-arounds hold
+    fn push_closure(&mut self, state: StateID, byte_depth: usize) {
+        if let Some(prune) = &self.prune {
+            if prune(&self.str[..byte_depth]) {
+                return;
+            }
+        }
+        let closure =
+            self.closures[state].get_or_insert_with(|| closure(&self.regex, state, self.end_slot));
+        for &(next, looks) in closure.iter().rev() {
+            if !looks
+                .iter()
+                .all(|look| look_holds(look, &self.str[..byte_depth]))
+            {
+                continue;
+            }
+            let offset = self.ranges.len();
+            push_range(self.regex.state(next), &mut self.ranges);
+            self.stack.push((next, offset, byte_depth));
+        }
     }
 
     /// Get the next matching string ref from this regex iterator
     pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        let Some(mut sorted) = self.sorted.take() else {
+            return self.next_by_priority();
+        };
+        if sorted.next == sorted.strs.len() {
+            // buffer the next length
+            sorted.strs.clear();
+            sorted.next = 0;
+            sorted.strs.extend(sorted.pending.take());
+            if sorted.strs.is_empty() {
+                sorted
+                    .strs
+                    .extend(self.next_by_priority().map(ToOwned::to_owned));
+            }
+            if let Some(len) = sorted.strs.first().map(Vec::len) {
+                while let Some(s) = self.next_by_priority() {
+                    if s.len() != len {
+                        sorted.pending = Some(s.to_owned());
+                        break;
+                    }
+                    sorted.strs.push(s.to_owned());
+                }
+            }
+            sorted.strs.sort_unstable();
+            sorted.strs.dedup();
+        }
+        let sorted = self.sorted.insert(sorted);
+        let s = sorted.strs.get(sorted.next)?;
+        sorted.next += 1;
+        Some(s)
+    }
+
+    /// Get the next matching string ref in the priority order of the regex
+    fn next_by_priority(&mut self) -> Option<&[u8]> {
+        self.matched = false;
         loop {
-            let Some((current, range, byte_depth, depth)) = self.stack.pop() else {
+            let Some((current, offset, byte_depth)) = self.stack.pop() else {
                 // we didn't get any deeper. no more search space
-                if self.max_depth < self.depth {
+                if self.max_depth < self.depth || self.depth >= self.limit {
                     break None;
                 }
 
                 self.depth += 1;
                 self.stack.clear();
-                self.stack.push((self.start, self.start_range.clone(), 0, 0));
+                self.ranges.clear();
+                self.push_closure(self.start, 0);
                 continue;
             };
 
             // update recorded max depth
-            self.max_depth = usize::max(self.max_depth, depth);
+            self.max_depth = usize::max(self.max_depth, byte_depth);
             self.str.truncate(byte_depth);
 
             let state = self.regex.state(current);
+            // free the ranges of any frames that have since been popped
+            self.ranges.truncate(offset + range_len(state));
+            let range = &mut self.ranges[offset..];
 
             // check we can explore deeper
-            if depth < self.depth {
+            if byte_depth < self.depth {
                 match state {
                     State::ByteRange { trans } => {
+                        let b = range[0] as u8;
                         // make sure we revisit this state
-                        if (range[0] as u8) < trans.end {
-                            self.stack.push((
-                                current,
-                                tinyvec::tiny_vec![range[0] + 1],
-                                byte_depth,
-                                depth,
-                            ));
+                        if b < trans.end {
+                            range[0] += 1;
+                            self.stack.push((current, offset, byte_depth));
                         }
-                        self.str.push(range[0] as u8);
-                        self.stack.push((
-                            trans.next,
-                            self.range_for(trans.next),
-                            byte_depth + 1,
-                            depth + 1,
-                        ));
+                        self.str.push(b);
+                        self.push_closure(trans.next, byte_depth + 1);
                     }
                     State::Sparse(s) => {
-                        for (i, &r) in range.iter().enumerate() {
+                        for (i, r) in range.iter_mut().enumerate() {
                             let t = s.transitions[i];
-                            if r <= t.end as u16 {
+                            if *r <= t.end as u16 {
+                                let b = *r as u8;
                                 // make sure we revisit this state
-                                let mut new_range = range.clone();
-                                new_range[i] += 1;
-                                self.stack.push((current, new_range, byte_depth, depth));
+                                *r += 1;
+                                self.stack.push((current, offset, byte_depth));
 
-                                self.str.push(r as u8);
+                                self.str.push(b);
                                 // add the new state
-                                self.stack.push((
-                                    t.next,
-                                    self.range_for(t.next),
-                                    byte_depth + 1,
-                                    depth + 1,
-                                ));
+                                self.push_closure(t.next, byte_depth + 1);
                                 break;
                             }
                         }
                     }
                     State::Dense(d) => {
+                        let b = range[0] as u8;
                         // make sure we revisit this state
-                        if range[0] < 255 {
-                            self.stack.push((
-                                current,
-                                tinyvec::tiny_vec![range[0] + 1],
-                                byte_depth,
-                                depth,
-                            ));
+                        if b < 255 {
+                            range[0] += 1;
+                            self.stack.push((current, offset, byte_depth));
                         }
-                        self.str.push(range[0] as u8);
-                        self.stack.push((
-                            d.transitions[range[0] as usize],
-                            self.range_for(d.transitions[range[0] as usize]),
-                            byte_depth + 1,
-                            depth + 1,
-                        ));
+                        self.str.push(b);
+                        self.push_closure(d.transitions[b as usize], byte_depth + 1);
                     }
-                    State::Look { look, next } => {
-                        let should = match look {
-                            Look::Start if byte_depth == 0 => true,
-                            Look::StartLF
-                                if byte_depth == 0 || self.str[byte_depth - 1] == b'\n' =>
-                            {
-                                true
-                            }
-                            Look::StartCRLF
-                                if byte_depth == 0
-                                    || self.str[byte_depth - 1] == b'\n'
-                                    || self.str[byte_depth - 1] == b'\r' =>
-                            {
-                                true
-                            }
-                            Look::End => true,
-                            Look::EndLF => true,
-                            Look::EndCRLF => true,
-                            Look::WordAscii => todo!(),
-                            Look::WordAsciiNegate => todo!(),
-                            Look::WordUnicode => todo!(),
-                            Look::WordUnicodeNegate => todo!(),
-                            _ => false,
-                        };
-                        if should {
-                            self.stack
-                                .push((*next, self.range_for(*next), byte_depth, depth + 1));
-                        }
-                    }
-                    State::Union { alternates } => {
-                        // same byte_depth because we matched no bytes
-                        for &alt in alternates.iter().rev() {
-                            self.stack
-                                .push((alt, self.range_for(alt), byte_depth, depth + 1));
-                        }
-                    }
-                    State::BinaryUnion { alt1, alt2 } => {
-                        // same byte_depth because we matched no bytes
-                        for &alt in [alt1, alt2].into_iter().rev() {
-                            self.stack
-                                .push((alt, self.range_for(alt), byte_depth, depth + 1));
-                        }
-                    }
-                    State::Capture { next, .. } => {
-                        // same byte_depth because we matched no bytes
-                        self.stack
-                            .push((*next, self.range_for(*next), byte_depth, depth + 1));
-                    }
-                    State::Fail => {}
-                    State::Match { .. } => {}
+                    // closures only contain byte matching states and match states
+                    _ => {}
                 }
             } else {
                 // test that this state is final
-                if matches!(state, State::Match { .. }) {
+                if self.is_match(state) {
+                    self.matched = true;
                     break Some(&self.str);
                 }
             }
@@ -241,6 +620,12 @@ impl NfaIter {
     }
 }
 
+impl BorrowNext for NfaIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        NfaIter::borrow_next(self)
+    }
+}
+
 impl Iterator for NfaIter {
     type Item = Vec<u8>;
 
@@ -259,10 +644,11 @@ mod tests {
     fn set() {
         let iter = NfaIter::new(r"b|(a)?|cc").unwrap();
 
+        // shortest first, then in order of the alternation
         let x: Vec<Vec<u8>> = iter.collect();
         assert_eq!(
             x,
-            [b"b".to_vec(), b"".to_vec(), b"cc".to_vec(), b"a".to_vec(),]
+            [b"".to_vec(), b"b".to_vec(), b"a".to_vec(), b"cc".to_vec()]
         );
     }
 
@@ -321,21 +707,66 @@ mod tests {
             b"a".to_vec(),
             b"b".to_vec(),
             b"aa".to_vec(),
+            b"ab".to_vec(),
             b"bb".to_vec(),
+            b"ba".to_vec(),
             b"aaa".to_vec(),
+            b"aab".to_vec(),
+            b"abb".to_vec(),
+            b"aba".to_vec(),
             b"bbb".to_vec(),
-            b"aaaa".to_vec(),
-            // technically a different path
-            b"aa".to_vec(),
-            b"ab".to_vec(),
-            b"bbbb".to_vec(),
-            b"ba".to_vec(),
-            // technically a different path
-            b"bb".to_vec(),
+            b"bba".to_vec(),
         ];
         assert_eq!(x, y);
     }
 
+    #[test]
+    fn epsilon_cycle() {
+        // the outer repetition can loop without matching any bytes
+        let nfa = NFA::new(r"(?:a*)*").unwrap();
+
+        // each string is only found once, no matter how many times we go around the outer repetition
+        let x: Vec<Vec<u8>> = NfaIter::from(nfa).take(4).collect();
+        assert_eq!(
+            x,
+            [b"".to_vec(), b"a".to_vec(), b"aa".to_vec(), b"aaa".to_vec()]
+        );
+    }
+
+    #[test]
+    fn many_states() {
+        // only the closures that the search reaches are computed
+        let mut iter = NfaIter::new(r"(?:a?){5000}").unwrap();
+        assert_eq!(iter.next().unwrap(), b"");
+        assert_eq!(iter.next().unwrap(), b"a");
+    }
+
+    #[test]
+    fn unicode() {
+        // multi-byte classes compile to sparse states
+        let x: HashSet<Vec<u8>> = NfaIter::new(r"[а-яё0-9]{2}").unwrap().collect();
+        let y: HashSet<Vec<u8>> = crate::DenseDfaIter::new(r"[а-яё0-9]{2}").unwrap().collect();
+        assert_eq!(x.len(), 43 * 43);
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn group() {
+        let iter = NfaIter::new(r"(?:x(?<digits>[0-9]{2})y)+|(z)").unwrap();
+
+        let x: Vec<Vec<u8>> = iter.group_iter(1).unwrap().collect();
+        assert_eq!(x.len(), 100);
+        assert_eq!(x[0], b"00");
+
+        let x: Vec<Vec<u8>> = iter.group_iter(2).unwrap().collect();
+        assert_eq!(x, [b"z".to_vec()]);
+
+        let x: Vec<Vec<u8>> = iter.group_iter(0).unwrap().take(2).collect();
+        assert_eq!(x, [b"z".to_vec(), b"x00y".to_vec()]);
+
+        assert!(iter.group_iter(3).is_none());
+    }
+
     #[test]
     fn many() {
         let search = NfaIter::new_many(&["[0-1]+", "^[a-b]+"]).unwrap();
@@ -356,4 +787,126 @@ mod tests {
         ];
         assert_eq!(x, y);
     }
+
+    #[test]
+    fn prune() {
+        let iter = NfaIter::new(r"(ab|b)*").unwrap().prune_if(|p| p.len() > 3);
+        let x: HashSet<Vec<u8>> = iter.collect();
+        let y: HashSet<Vec<u8>> = crate::DenseDfaIter::new(r"(ab|b)*")
+            .unwrap()
+            .take_while(|s| s.len() <= 3)
+            .collect();
+        assert_eq!(x, y);
+
+        let mut iter = NfaIter::new(r"a*").unwrap().prune_if(|_| true);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn determinize() {
+        let iter = NfaIter::new(r"(a|aa)*").unwrap().prune_if(|p| p.len() > 4);
+        let x: Vec<Vec<u8>> = iter.determinize().unwrap().collect();
+        assert_eq!(x.len(), 5);
+
+        // sorted iterators continue exactly where they left off
+        let pattern = r"[ab]{2}|b|a";
+        let all: Vec<Vec<u8>> = crate::DenseDfaIter::new(pattern).unwrap().collect();
+        for n in 0..all.len() {
+            let mut iter = NfaIter::new(pattern).unwrap().sorted(true);
+            let mut x: Vec<Vec<u8>> = iter.by_ref().take(n).collect();
+            x.extend(iter.determinize().unwrap());
+            assert_eq!(x, all, "{n}");
+        }
+
+        // the limit is kept, and nothing is produced again once the length is done
+        let mut iter = NfaIter::new(r"a+").unwrap().max_depth(2);
+        assert_eq!(iter.by_ref().count(), 2);
+        let iter = iter.into_inner();
+        assert_eq!(iter.determinize().unwrap().next(), None);
+        let mut iter = NfaIter::new(r"[ab]").unwrap();
+        assert_eq!(iter.next(), Some(b"a".to_vec()));
+        assert_eq!(iter.next(), Some(b"b".to_vec()));
+        assert_eq!(iter.determinize().unwrap().next(), None);
+    }
+
+    #[test]
+    #[should_panic = "capture group iterators can't be determinized"]
+    fn determinize_group() {
+        let iter = NfaIter::new(r"(a)b").unwrap();
+        let _ = iter.group_iter(1).unwrap().determinize();
+    }
+
+    #[test]
+    fn supports_generation() {
+        for pattern in [r"^a+$", r"(?m)^a$", r"(?Rm)^a$", r"a$|b"] {
+            let nfa = NFA::new(pattern).unwrap();
+            assert_eq!(NfaIter::supports_generation(&nfa), Ok(()), "{pattern}");
+        }
+
+        let nfa = NFA::new(r"(?m)a$\nb").unwrap();
+        let unsupported = NfaIter::supports_generation(&nfa).unwrap_err();
+        assert!(matches!(
+            unsupported[..],
+            [Unsupported::EndFollowedByBytes {
+                look: Look::EndLF,
+                ..
+            }]
+        ));
+
+        let nfa = NFA::new(r"(?-u:\B)a\b").unwrap();
+        let unsupported = NfaIter::supports_generation(&nfa).unwrap_err();
+        let looks: Vec<Look> = unsupported
+            .iter()
+            .map(|u| match *u {
+                Unsupported::WordBoundary { look, .. } => look,
+                Unsupported::EndFollowedByBytes { look, .. } => look,
+            })
+            .collect();
+        assert_eq!(looks, [Look::WordAsciiNegate, Look::WordUnicode]);
+    }
+
+    #[test]
+    fn sorted() {
+        let pattern = r"(?:z|[a-c]|y)(?:9|[0-2]|x)*";
+        let x: Vec<Vec<u8>> = NfaIter::new(pattern)
+            .unwrap()
+            .sorted(true)
+            .take(500)
+            .collect();
+        let mut y = x.clone();
+        y.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+        y.dedup();
+        assert_eq!(x, y);
+
+        // the same as the dfa, which is always sorted
+        let z: Vec<Vec<u8>> = crate::DenseDfaIter::new(pattern)
+            .unwrap()
+            .take(500)
+            .collect();
+        assert_eq!(x, z);
+
+        // duplicates are removed
+        let x: Vec<Vec<u8>> = NfaIter::new(r"a|a|b").unwrap().sorted(true).collect();
+        assert_eq!(x, [b"a".to_vec(), b"b".to_vec()]);
+
+        let group = NfaIter::new(r"x(b|a)").unwrap().sorted(true).group_iter(1);
+        let x: Vec<Vec<u8>> = group.unwrap().collect();
+        assert_eq!(x, [b"a".to_vec(), b"b".to_vec()]);
+    }
+
+    #[test]
+    fn max_depth() {
+        let x: Vec<Vec<u8>> = NfaIter::new(r"(?:b|a)+").unwrap().max_depth(3).collect();
+        assert_eq!(x.len(), 2 + 4 + 8);
+
+        // the rest of a sorted length is still produced
+        let mut iter = NfaIter::new(r"(?:b|a)+").unwrap().sorted(true);
+        assert_eq!(iter.by_ref().take(3).last().unwrap(), b"aa");
+        let x: Vec<Vec<u8>> = iter.max_depth(2).collect();
+        assert_eq!(x, [b"ab".to_vec(), b"ba".to_vec(), b"bb".to_vec()]);
+
+        let mut iter = NfaIter::new(r"(?:b|a)+").unwrap();
+        assert_eq!(iter.by_ref().take(3).count(), 3);
+        assert_eq!(iter.max_depth(1).next(), None);
+    }
 }
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use regex_automata::dfa::Automaton;
+
+use crate::{count::Counts, graph::Graph, DfaIter};
+
+/// `NthCache` answers repeated "what is the `k`th match" queries for one regex, sharing work
+/// between them. See [`DfaIter::nth_cache`]
+///
+/// ```
+/// use regex_utils::DenseDfaIter;
+///
+/// let iter = DenseDfaIter::new(r"[a-z]+").unwrap();
+/// let mut cache = iter.nth_cache();
+/// assert_eq!(cache.nth_match(0), Some(&b"a"[..]));
+/// assert_eq!(cache.nth_match(26), Some(&b"aa"[..]));
+/// assert_eq!(cache.nth_match(1_000_000), Some(&b"bdwgo"[..]));
+/// ```
+///
+/// # Implementation Details
+///
+/// Ranks are found like [`DfaIter::spread`], by counting the matches of each length from every state.
+/// The counting table only grows to the longest length that has been queried, and is kept between queries.
+///
+/// The path taken by the last query is also kept, along with the range of ranks below each prefix of it.
+/// A query for a nearby rank of the same length can start from the longest prefix they share.
+pub struct NthCache {
+    // the reachable states of the graph
+    graph: Arc<Graph>,
+    // the number of matches of each length from each state
+    counts: Counts,
+    // [len]: the number of matches of up to `len` bytes
+    totals: Vec<u128>,
+    // the longest match, or None if the language is infinite
+    longest: Option<usize>,
+    // the length of the last match
+    len: usize,
+    // [depth]: (graph index after the first `depth` bytes of the last match,
+    // the ranks of the matches of length `len` with those first `depth` bytes)
+    path: Vec<(usize, u128, u128)>,
+    // the last match
+    str: Vec<u8>,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// A cache for finding matches by their rank in the order this iterator produces them.
+    ///
+    /// See [`NthCache`] for details
+    pub fn nth_cache(&self) -> NthCache {
+        let analysis = self.analysis();
+        NthCache {
+            graph: self.graph.clone(),
+            counts: Counts::new(&self.graph, 0),
+            totals: vec![],
+            // a finite language's matches are simple paths through its live states
+            longest: (!analysis.infinite).then(|| analysis.states.saturating_sub(1)),
+            len: 0,
+            path: vec![],
+            str: vec![],
+        }
+    }
+}
+
+impl NthCache {
+    /// The `k`th match (counting from 0) in the order [`DfaIter`] produces them,
+    /// or `None` if there are not that many matches.
+    ///
+    /// # Note
+    ///
+    /// The counting table grows to the length of the match, so with infinite regexes that have
+    /// very few matches of each length, like `a*`, large ranks are expensive.
+    pub fn nth_match(&mut self, k: u128) -> Option<&[u8]> {
+        // find the length of the match
+        let len = loop {
+            if let Some(len) = self.totals.iter().position(|&t| k < t) {
+                break len;
+            }
+            let len = self.totals.len();
+            if self.longest.is_some_and(|longest| len > longest) {
+                return None;
+            }
+            self.counts.extend_to(&self.graph, len);
+            let prev = self.totals.last().copied().unwrap_or(0);
+            self.totals
+                .push(prev.saturating_add(self.counts.of_len(len)));
+            if self.totals[len] == u128::MAX && k == u128::MAX {
+                // the counts have saturated, so no later rank is reachable
+                return None;
+            }
+        };
+        let rank = k - len.checked_sub(1).map_or(0, |l| self.totals[l]);
+
+        // start from the longest prefix shared with the last match
+        if self.len != len || self.path.is_empty() {
+            self.len = len;
+            self.path.clear();
+            self.path.push((0, 0, self.counts.of_len(len)));
+        }
+        while let Some(&(_, lo, hi)) = self.path.last() {
+            if lo <= rank && rank < hi {
+                break;
+            }
+            self.path.pop();
+        }
+        let depth = self.path.len() - 1;
+        self.str.truncate(depth);
+
+        let (mut current, mut lo, _) = self.path[depth];
+        for remaining in (0..len - depth).rev() {
+            let (b, next, block_lo, each) = self.graph.edges[current]
+                .iter()
+                .find_map(|&(start, end, t)| {
+                    let each = self.counts.starting_at(remaining, t);
+                    let bytes = (end - start) as u128 + 1;
+                    let block = bytes.saturating_mul(each);
+                    if rank - lo < block {
+                        let b = start + ((rank - lo) / each) as u8;
+                        Some((b, t, lo + (b - start) as u128 * each, each))
+                    } else {
+                        lo += block;
+                        None
+                    }
+                })
+                .expect("rank out of range");
+            self.str.push(b);
+            self.path
+                .push((next, block_lo, block_lo.saturating_add(each)));
+            current = next;
+            lo = block_lo;
+        }
+        Some(&self.str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DenseDfaIter;
+
+    #[test]
+    fn nth_match() {
+        let pattern = r"(?:foo|[a-c]{2}|x*)[0-2]";
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern)
+            .unwrap()
+            .take_while(|s| s.len() <= 6)
+            .collect();
+
+        let mut cache = DenseDfaIter::new(pattern).unwrap().nth_cache();
+        for (k, s) in x.iter().enumerate() {
+            assert_eq!(cache.nth_match(k as u128), Some(&s[..]));
+        }
+        // queries don't have to be in order
+        for (k, s) in x.iter().enumerate().rev().step_by(3) {
+            assert_eq!(cache.nth_match(k as u128), Some(&s[..]));
+        }
+    }
+
+    #[test]
+    fn finite() {
+        let pattern = r"[a-c]{2,3}|d";
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().collect();
+        let mut cache = DenseDfaIter::new(pattern).unwrap().nth_cache();
+        assert_eq!(cache.nth_match(x.len() as u128), None);
+        assert_eq!(
+            cache.nth_match(x.len() as u128 - 1),
+            x.last().map(|s| &s[..])
+        );
+        assert_eq!(cache.nth_match(u128::MAX), None);
+
+        let mut cache = DenseDfaIter::new(r"[^\s\S]").unwrap().nth_cache();
+        assert_eq!(cache.nth_match(0), None);
+
+        let mut cache = DenseDfaIter::new(r"").unwrap().nth_cache();
+        assert_eq!(cache.nth_match(0), Some(&b""[..]));
+        assert_eq!(cache.nth_match(1), None);
+    }
+}
@@ -0,0 +1,125 @@
+use std::ops::Range;
+
+use regex_automata::dfa::Automaton;
+
+use crate::DfaIter;
+
+/// `PaddedIter` produces each match embedded in a larger haystack, along with where it is.
+/// See [`DfaIter::padded`]
+pub struct PaddedIter<A> {
+    iter: DfaIter<A>,
+    // the number of padding bytes before and after each match
+    left: usize,
+    right: usize,
+    // the padding byte
+    pad: u8,
+    // the current haystack
+    str: Vec<u8>,
+}
+
+/// Padding bytes to try first, so the haystacks are readable
+const PREFERRED_PADS: &[u8] = b" #~-_.";
+
+impl<A: Automaton> DfaIter<A> {
+    /// Embed each match in a haystack, with `left` padding bytes before it and `right` after,
+    /// and report the span of the match in the haystack.
+    ///
+    /// The padding byte is chosen so that an unanchored leftmost search for the regex finds
+    /// exactly the embedded match: no match can start with it, and no match can continue into it.
+    /// Returns `None` if there is no such byte, or if the regex matches the empty string,
+    /// since a search would find the empty match at the start of the haystack.
+    ///
+    /// Look-around assertions at the edges of the match, like `\b`, are not taken into account.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+[0-9]").unwrap().padded(2, 3).unwrap();
+    /// let x: Vec<_> = iter.take(2).collect();
+    /// assert_eq!(x, [(2..4, b"  a0   ".to_vec()), (2..4, b"  a1   ".to_vec())]);
+    /// ```
+    pub fn padded(self, left: usize, right: usize) -> Option<PaddedIter<A>> {
+        let graph = &self.graph;
+        let live = |i: usize| self.dist[i] != usize::MAX;
+        if graph.accept[0] {
+            return None;
+        }
+
+        // whether `b` leads to a state that can reach a match
+        let continues = |i: usize, b: u8| {
+            graph.edges[i]
+                .iter()
+                .any(|&(start, end, next)| (start..=end).contains(&b) && live(next))
+        };
+        let pad = PREFERRED_PADS.iter().copied().chain(0..=255).find(|&b| {
+            (0..graph.states.len())
+                .filter(|&i| live(i))
+                .all(|i| !continues(i, b))
+        })?;
+
+        Some(PaddedIter {
+            iter: self,
+            left,
+            right,
+            pad,
+            str: vec![],
+        })
+    }
+}
+
+impl<A: Automaton> PaddedIter<A> {
+    /// The byte that the matches are padded with
+    pub fn pad(&self) -> u8 {
+        self.pad
+    }
+
+    /// Get the next haystack ref, with the span of the match inside it
+    pub fn borrow_next(&mut self) -> Option<(Range<usize>, &[u8])> {
+        let next = self.iter.borrow_next()?;
+        self.str.clear();
+        self.str.resize(self.left, self.pad);
+        self.str.extend_from_slice(next);
+        self.str.resize(self.str.len() + self.right, self.pad);
+        Some((self.left..self.left + next.len(), &self.str))
+    }
+}
+
+impl<A: Automaton> Iterator for PaddedIter<A> {
+    type Item = (Range<usize>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(|(span, s)| (span, s.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::dfa::regex::Regex;
+
+    use crate::DenseDfaIter;
+
+    #[test]
+    fn padded() {
+        for pattern in [r"(ab|b)+c?", r"[^ ]{1,3}", r"x|xy+|[0-9]{2}"] {
+            let re = Regex::new(pattern).unwrap();
+            let iter = DenseDfaIter::new(pattern).unwrap().padded(3, 2).unwrap();
+            for (span, haystack) in iter.take(500) {
+                let m = re.find(&haystack).unwrap();
+                assert_eq!(m.range(), span, "{pattern} {haystack:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn pad() {
+        assert!(DenseDfaIter::new(r"a*").unwrap().padded(1, 1).is_none());
+
+        let iter = DenseDfaIter::new(r"[ #]").unwrap().padded(1, 1).unwrap();
+        assert_eq!(iter.pad(), b'~');
+        let iter = DenseDfaIter::new(r"[ #~\-_.]")
+            .unwrap()
+            .padded(1, 1)
+            .unwrap();
+        assert_eq!(iter.pad(), 0);
+    }
+}
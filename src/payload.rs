@@ -0,0 +1,71 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::BorrowNext;
+
+/// The least capacity to allocate for the shared buffer at a time
+const CHUNK: usize = 4096;
+
+/// An iterator over the matches of a regex iterator as [`Bytes`]. See [`BorrowNext::into_bytes`]
+///
+/// The matches are written into a shared buffer, and each is a slice of it, so many short matches
+/// share one allocation, and can be sent on without copying.
+pub struct BytesIter<I> {
+    iter: I,
+    // the unused capacity of the current chunk
+    buf: BytesMut,
+}
+
+impl<I> BytesIter<I> {
+    pub(crate) fn new(iter: I) -> Self {
+        Self {
+            iter,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Recover the regex iterator that this produces the matches of
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I: BorrowNext> Iterator for BytesIter<I> {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.iter.borrow_next()?;
+        if self.buf.capacity() < next.len() {
+            // start a new chunk. the old one is freed once its matches are dropped
+            self.buf = BytesMut::with_capacity(usize::max(CHUNK, next.len()));
+        }
+        self.buf.extend_from_slice(next);
+        Some(self.buf.split().freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::{BorrowNext, DenseDfaIter};
+
+    #[test]
+    fn bytes() {
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(r"[a-z]{1,2}").unwrap().collect();
+        let y: Vec<Bytes> = DenseDfaIter::new(r"[a-z]{1,2}")
+            .unwrap()
+            .into_bytes()
+            .collect();
+        assert!(x.iter().map(Vec::as_slice).eq(y.iter().map(|b| &b[..])));
+
+        // consecutive matches are next to each other in the same chunk
+        let end = y[0].as_ptr_range().end;
+        assert_eq!(end, y[1].as_ptr());
+
+        // matches longer than a chunk get their own
+        let mut iter = DenseDfaIter::new(r"a+").unwrap().into_bytes();
+        let long = iter.nth(super::CHUNK).unwrap();
+        assert_eq!(long.len(), super::CHUNK + 1);
+        assert_eq!(iter.next().unwrap().len(), super::CHUNK + 2);
+    }
+}
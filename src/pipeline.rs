@@ -0,0 +1,259 @@
+#![allow(clippy::result_large_err)]
+
+use std::{
+    io::{self, Write},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use regex_automata::util::primitives::PatternID;
+
+use crate::{
+    BorrowNext, EngineBuildError, Exporter, MultiIter, PatternError, PatternMatch, RegexIter,
+};
+
+/// `PipelineIter` compiles many patterns on a pool of worker threads, and produces the matches
+/// of the patterns that have finished compiling while the rest are still being compiled.
+///
+/// ```
+/// use regex_utils::PipelineIter;
+///
+/// let mut iter = PipelineIter::new_many(&[r"[0-9]{2}", r"[a-z]", r"(b"]);
+/// let mut x: Vec<Vec<u8>> = iter.by_ref().collect();
+/// x.sort();
+/// assert_eq!(x.len(), 126);
+/// assert_eq!(x[0], b"00");
+///
+/// // the patterns that failed to compile
+/// assert_eq!(iter.errors().len(), 1);
+/// assert_eq!(iter.errors()[0].index(), Some(2));
+/// ```
+///
+/// # Note
+///
+/// Regexes can be infinite (eg `a*`). Either use this iterator lazily, or limit the number
+/// of iterations.
+///
+/// Like [`MultiIter`](crate::MultiIter), each pattern gets its own [`RegexIter`], with the engine
+/// chosen by [`RegexIter::auto`], and produces its own matches. Of the patterns that have compiled,
+/// matches are produced in order of length, then in pattern order. A pattern that finishes compiling
+/// later starts from its shortest matches, so the order across patterns depends on how long each
+/// takes to compile. [`PipelineIter::borrow_next_with_pattern`] says which pattern each match is for.
+pub struct PipelineIter {
+    // the compiled patterns with matches left, and the source of every pattern
+    multi: MultiIter,
+    // the compiled patterns, sent by the workers
+    compiled: mpsc::Receiver<(usize, Result<RegexIter, EngineBuildError>)>,
+    // the number of patterns that haven't been received from the workers yet
+    pending: usize,
+    // the patterns that failed to compile
+    errors: Vec<PatternError<EngineBuildError>>,
+}
+
+impl PipelineIter {
+    /// Compile the given regular expressions on as many threads as are available,
+    /// and return the corresponding `PipelineIter`.
+    pub fn new_many<P: AsRef<str>>(patterns: &[P]) -> Self {
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::with_threads(patterns, threads)
+    }
+
+    /// Compile the given regular expressions on `threads` worker threads,
+    /// and return the corresponding `PipelineIter`.
+    ///
+    /// # Panics
+    ///
+    /// If `threads` is 0
+    pub fn with_threads<P: AsRef<str>>(patterns: &[P], threads: usize) -> Self {
+        assert!(threads > 0, "a pipeline needs at least one worker thread");
+        let patterns: Arc<[String]> = patterns.iter().map(|p| p.as_ref().to_owned()).collect();
+        let next = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = mpsc::channel();
+
+        for _ in 0..threads.min(patterns.len()) {
+            let patterns = Arc::clone(&patterns);
+            let next = Arc::clone(&next);
+            let tx = tx.clone();
+            thread::spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(pattern) = patterns.get(i) else {
+                    break;
+                };
+                // the iterator was dropped, so nobody wants the rest
+                if tx.send((i, RegexIter::auto(pattern))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            multi: MultiIter::with_patterns(patterns.to_vec()),
+            pending: patterns.len(),
+            compiled: rx,
+            errors: vec![],
+        }
+    }
+
+    /// The patterns that failed to compile so far, with their index.
+    ///
+    /// This is only complete once the iterator has finished.
+    pub fn errors(&self) -> &[PatternError<EngineBuildError>] {
+        &self.errors
+    }
+
+    /// The number of patterns that are still being compiled
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// The source of the pattern with the given ID.
+    /// Returns `None` if there is no such pattern.
+    pub fn pattern_text(&self, id: PatternID) -> Option<&str> {
+        self.multi.pattern_text(id)
+    }
+
+    /// Start iterating a compiled pattern
+    fn add(&mut self, i: usize, compiled: Result<RegexIter, EngineBuildError>) {
+        self.pending -= 1;
+        let id = PatternID::new_unchecked(i);
+        match compiled {
+            Ok(iter) => self.multi.add_compiled(id, iter),
+            Err(err) => {
+                let pattern = self.multi.pattern_text(id).unwrap_or_default();
+                self.errors
+                    .push(PatternError::new(pattern, err).with_index(i));
+            }
+        }
+    }
+
+    /// Get the next matching string ref from this regex iterator,
+    /// along with the pattern that it matches
+    pub fn borrow_next_with_pattern(&mut self) -> Option<PatternMatch<'_>> {
+        // take whatever has finished compiling, without waiting
+        while let Ok((i, compiled)) = self.compiled.try_recv() {
+            self.add(i, compiled);
+        }
+        // nothing to iterate yet, so wait for the next pattern to compile
+        while !self.multi.has_matches() && self.pending > 0 {
+            let (i, compiled) = self
+                .compiled
+                .recv()
+                .expect("the workers should send every pattern");
+            self.add(i, compiled);
+        }
+        self.multi.borrow_next_with_pattern()
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        self.borrow_next_with_pattern().map(|m| m.bytes)
+    }
+
+    /// Write up to `limit` of the next matches, across all patterns, as records of `exporter`,
+    /// and return how many were written. See [`Exporter`]
+    pub fn export_to<W: Write>(
+        &mut self,
+        exporter: &mut Exporter<W>,
+        limit: usize,
+    ) -> io::Result<usize> {
+        let mut n = 0;
+        while n < limit {
+            let Some(m) = self.borrow_next_with_pattern() else {
+                break;
+            };
+            exporter.write_match(m)?;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+impl BorrowNext for PipelineIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        PipelineIter::borrow_next(self)
+    }
+}
+
+impl Iterator for PipelineIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::MultiIter;
+
+    use super::*;
+
+    #[test]
+    fn same_matches() {
+        let patterns = [
+            r"[ab]{1,3}",
+            r"x|yz",
+            r"[0-9]{2}",
+            r"a+b",
+            r"(?:foo|bar)[0-9]?",
+        ];
+
+        for threads in [1, 2, 8] {
+            let mut pipeline = PipelineIter::with_threads(&patterns, threads);
+            let mut x: HashMap<PatternID, Vec<Vec<u8>>> = HashMap::new();
+            while let Some(m) = pipeline.borrow_next_with_pattern() {
+                let pattern = m.pattern;
+                assert_eq!(m.pattern_text, patterns[pattern.as_usize()]);
+                let found = x.entry(pattern).or_default();
+                // each pattern is still in order of length
+                if let Some(last) = found.last() {
+                    assert!(last.len() <= m.bytes.len());
+                }
+                found.push(m.bytes.to_vec());
+                if found.len() >= 20 {
+                    pipeline.multi.disable_pattern(pattern);
+                }
+            }
+            assert_eq!(pipeline.pending(), 0);
+            assert!(pipeline.errors().is_empty());
+
+            let y = MultiIter::new_many(&patterns)
+                .unwrap()
+                .collect_by_pattern(20);
+            assert_eq!(x, y);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut iter = PipelineIter::with_threads(&[r"(a", r"b", r"[z-a]"], 2);
+        let x: Vec<Vec<u8>> = iter.by_ref().collect();
+        assert_eq!(x, [b"b".to_vec()]);
+
+        let mut errors: Vec<_> = iter.errors().iter().map(|e| e.index()).collect();
+        errors.sort();
+        assert_eq!(errors, [Some(0), Some(2)]);
+    }
+
+    #[test]
+    fn empty() {
+        let mut iter = PipelineIter::new_many::<&str>(&[]);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.pending(), 0);
+    }
+
+    #[test]
+    fn drop_early() {
+        // the workers stop once the iterator is dropped
+        let patterns: Vec<String> = (0..100).map(|i| format!("x{i}[a-z]*")).collect();
+        let mut iter = PipelineIter::with_threads(&patterns, 2);
+        assert!(iter.next().is_some());
+        drop(iter);
+    }
+}
@@ -0,0 +1,388 @@
+//! Ready-made patterns for common data shapes.
+//!
+//! Every preset restricts itself to a small ASCII alphabet and caps the length of each part,
+//! so the languages are finite and the outputs look like realistic data.
+//!
+//! ```
+//! use regex_utils::presets;
+//!
+//! let emails: Vec<String> = presets::email().take(3).collect();
+//! assert_eq!(emails, ["0@0.io", "0@1.io", "0@2.io"]);
+//! ```
+
+use std::ops::RangeInclusive;
+
+use crate::{BorrowNext, DenseDfaIter, Utf8Iter, Validated};
+
+/// An iterator over the strings of a preset pattern
+pub type PresetIter = Utf8Iter<DenseDfaIter<Vec<u32>>>;
+
+/// An iterator over the strings of a preset pattern that are also semantically valid
+pub type ValidatedPresetIter = Utf8Iter<Validated<DenseDfaIter<Vec<u32>>, fn(&[u8]) -> bool>>;
+
+/// Email addresses, with a local part of up to 25 characters and a domain of up to 16 characters
+pub const EMAIL: &str =
+    r"[a-z0-9]{1,16}(?:[._-][a-z0-9]{1,8})?@[a-z0-9]{1,16}\.(?:io|com|dev|net|org)";
+
+/// Usernames, starting with a letter and up to 16 characters long
+pub const USERNAME: &str = r"[a-z][a-z0-9_]{2,15}";
+
+/// ISO-8601 calendar dates, `YYYY-MM-DD`.
+///
+/// The pattern alone allows days that don't exist, like February 30th. See [`date`]
+pub const DATE: &str = r"[0-9]{4}-(?:0[1-9]|1[0-2])-(?:0[1-9]|[12][0-9]|3[01])";
+
+/// ISO-8601 times of day, `hh:mm:ss`
+pub const TIME: &str = r"(?:[01][0-9]|2[0-3]):[0-5][0-9]:[0-5][0-9]";
+
+/// ISO-8601 UTC date-times, `YYYY-MM-DDThh:mm:ssZ`.
+///
+/// The pattern alone allows days that don't exist, like February 30th. See [`datetime`]
+pub const DATETIME: &str = r"[0-9]{4}-(?:0[1-9]|1[0-2])-(?:0[1-9]|[12][0-9]|3[01])T(?:[01][0-9]|2[0-3]):[0-5][0-9]:[0-5][0-9]Z";
+
+/// IPv4 addresses in dotted decimal, without leading zeros
+pub const IPV4: &str = r"(?:1[0-9]{2}|2[0-4][0-9]|25[0-5]|[1-9][0-9]|[0-9])(?:\.(?:1[0-9]{2}|2[0-4][0-9]|25[0-5]|[1-9][0-9]|[0-9])){3}";
+
+/// IPv4 networks in CIDR notation, an [`IPV4`] address with a prefix length of up to 32
+pub const IPV4_CIDR: &str = r"(?:1[0-9]{2}|2[0-4][0-9]|25[0-5]|[1-9][0-9]|[0-9])(?:\.(?:1[0-9]{2}|2[0-4][0-9]|25[0-5]|[1-9][0-9]|[0-9])){3}/(?:3[0-2]|[12][0-9]|[0-9])";
+
+/// IPv6 addresses in full form, eight groups of up to 4 lowercase hex digits.
+///
+/// The `::` compressed form is not produced
+pub const IPV6: &str = r"[0-9a-f]{1,4}(?::[0-9a-f]{1,4}){7}";
+
+/// IPv6 networks in CIDR notation, an [`IPV6`] address with a prefix length of up to 128
+pub const IPV6_CIDR: &str =
+    r"[0-9a-f]{1,4}(?::[0-9a-f]{1,4}){7}/(?:12[0-8]|1[01][0-9]|[1-9][0-9]|[0-9])";
+
+/// MAC addresses, six pairs of lowercase hex digits separated by colons
+pub const MAC: &str = r"[0-9a-f]{2}(?::[0-9a-f]{2}){5}";
+
+/// Semantic versions, with an optional pre-release tag
+pub const SEMVER: &str = r"(?:0|[1-9][0-9]{0,2})\.(?:0|[1-9][0-9]{0,2})\.(?:0|[1-9][0-9]{0,2})(?:-(?:alpha|beta|rc)\.(?:0|[1-9][0-9]?))?";
+
+fn preset(pattern: &str) -> PresetIter {
+    let iter = DenseDfaIter::new(pattern).expect("preset patterns should be valid");
+    Utf8Iter::try_from(iter).expect("preset patterns should be utf8")
+}
+
+fn validated_preset(pattern: &str, validate: fn(&[u8]) -> bool) -> ValidatedPresetIter {
+    let iter = DenseDfaIter::new(pattern).expect("preset patterns should be valid");
+    let Utf8Iter(iter) = Utf8Iter::try_from(iter).expect("preset patterns should be utf8");
+    Utf8Iter(iter.with_validator(validate))
+}
+
+/// Email addresses. See [`EMAIL`]
+pub fn email() -> PresetIter {
+    preset(EMAIL)
+}
+
+/// Usernames. See [`USERNAME`]
+pub fn username() -> PresetIter {
+    preset(USERNAME)
+}
+
+/// The regex for lowercase hex tokens of exactly `len` characters
+pub fn hex_token_pattern(len: usize) -> String {
+    format!("[0-9a-f]{{{len}}}")
+}
+
+/// Lowercase hex tokens of exactly `len` characters. See [`hex_token_pattern`]
+pub fn hex_token(len: usize) -> PresetIter {
+    preset(&hex_token_pattern(len))
+}
+
+/// IPv4 addresses. See [`IPV4`]
+///
+/// ```
+/// use regex_utils::presets;
+///
+/// let x: Vec<String> = presets::ipv4().skip(10).take(2).collect();
+/// assert_eq!(x, ["0.0.1.0", "0.0.1.1"]);
+/// ```
+pub fn ipv4() -> PresetIter {
+    preset(IPV4)
+}
+
+/// IPv4 networks. See [`IPV4_CIDR`]
+pub fn ipv4_cidr() -> PresetIter {
+    preset(IPV4_CIDR)
+}
+
+/// IPv6 addresses. See [`IPV6`]
+pub fn ipv6() -> PresetIter {
+    preset(IPV6)
+}
+
+/// IPv6 networks. See [`IPV6_CIDR`]
+pub fn ipv6_cidr() -> PresetIter {
+    preset(IPV6_CIDR)
+}
+
+/// MAC addresses. See [`MAC`]
+pub fn mac() -> PresetIter {
+    preset(MAC)
+}
+
+/// Semantic versions. See [`SEMVER`]
+pub fn semver() -> PresetIter {
+    preset(SEMVER)
+}
+
+/// Calendar dates that exist. See [`DATE`]
+///
+/// ```
+/// use regex_utils::presets;
+///
+/// let dates: Vec<String> = presets::date().skip(58).take(2).collect();
+/// // 0000 is a leap year
+/// assert_eq!(dates, ["0000-02-28", "0000-02-29"]);
+///
+/// let dates: Vec<String> = presets::date().skip(366).take(2).collect();
+/// assert_eq!(dates, ["0001-01-01", "0001-01-02"]);
+/// ```
+pub fn date() -> ValidatedPresetIter {
+    validated_preset(DATE, |s| is_valid_date(&s[..10]))
+}
+
+/// Times of day. See [`TIME`]
+pub fn time() -> PresetIter {
+    preset(TIME)
+}
+
+/// Date-times on days that exist. See [`DATETIME`]
+pub fn datetime() -> ValidatedPresetIter {
+    validated_preset(DATETIME, |s| is_valid_date(&s[..10]))
+}
+
+/// Whether a `YYYY-MM-DD` date matched by [`DATE`] is a day that exists in the Gregorian calendar
+fn is_valid_date(date: &[u8]) -> bool {
+    let number = |digits: &[u8]| digits.iter().fold(0u32, |n, &d| n * 10 + (d - b'0') as u32);
+    let year = number(&date[0..4]);
+    let month = number(&date[5..7]);
+    let day = number(&date[8..10]);
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    day <= days
+}
+
+/// The regex for the decimal integers in `range`, without leading zeros.
+///
+/// ```
+/// use regex_utils::presets;
+///
+/// assert_eq!(
+///     presets::int_range_pattern(0..=255),
+///     "(?:1[0-9]{2}|2[0-4][0-9]|25[0-5]|[1-9][0-9]|[0-9])"
+/// );
+/// ```
+///
+/// # Panics
+///
+/// If the range is empty
+pub fn int_range_pattern(range: RangeInclusive<u64>) -> String {
+    let (lo, hi) = range.into_inner();
+    assert!(lo <= hi, "the range {lo}..={hi} is empty");
+
+    let mut alternatives = vec![];
+    let digits = |n: u64| n.checked_ilog10().unwrap_or(0) + 1;
+    // longer numbers come first, or leftmost-first matching would stop at their shorter prefixes
+    for len in (digits(lo)..=digits(hi)).rev() {
+        // the numbers in range with exactly `len` digits
+        let smallest = if len == 1 { 0 } else { 10u64.pow(len - 1) };
+        let largest = 10u64.checked_pow(len).map_or(u64::MAX, |p| p - 1);
+        let lo = u64::max(lo, smallest).to_string();
+        let hi = u64::min(hi, largest).to_string();
+        same_len_range(
+            lo.as_bytes(),
+            hi.as_bytes(),
+            String::new(),
+            &mut alternatives,
+        );
+    }
+    format!("(?:{})", alternatives.join("|"))
+}
+
+/// Push the alternatives that match the digit strings from `lo` to `hi`, which have the same length
+fn same_len_range(lo: &[u8], hi: &[u8], prefix: String, out: &mut Vec<String>) {
+    let digit = |lo: u8, hi: u8| {
+        if lo == hi {
+            (lo as char).to_string()
+        } else {
+            format!("[{}-{}]", lo as char, hi as char)
+        }
+    };
+    let any = |n: usize| match n {
+        0 => String::new(),
+        1 => "[0-9]".to_owned(),
+        n => format!("[0-9]{{{n}}}"),
+    };
+
+    let [l, lo_rest @ ..] = lo else {
+        out.push(prefix);
+        return;
+    };
+    let [h, hi_rest @ ..] = hi else {
+        unreachable!("the bounds should have the same length")
+    };
+    if l == h {
+        return same_len_range(lo_rest, hi_rest, format!("{prefix}{}", *l as char), out);
+    }
+
+    // the first digits whose suffixes can be any digits
+    let mut start = *l;
+    let mut end = *h;
+    if lo_rest.iter().any(|&d| d != b'0') {
+        let nines = vec![b'9'; lo_rest.len()];
+        same_len_range(lo_rest, &nines, format!("{prefix}{}", *l as char), out);
+        start += 1;
+    }
+    if hi_rest.iter().any(|&d| d != b'9') {
+        end -= 1;
+    }
+    if start <= end {
+        out.push(format!(
+            "{prefix}{}{}",
+            digit(start, end),
+            any(lo_rest.len())
+        ));
+    }
+    if end < *h {
+        let zeros = vec![b'0'; hi_rest.len()];
+        same_len_range(&zeros, hi_rest, format!("{prefix}{}", *h as char), out);
+    }
+}
+
+/// The decimal integers in `range`, in numeric order. See [`int_range_pattern`]
+///
+/// ```
+/// use regex_utils::presets;
+///
+/// let ports: Vec<String> = presets::int_range(1024..=49151).take(3).collect();
+/// assert_eq!(ports, ["1024", "1025", "1026"]);
+/// assert_eq!(presets::int_range(1024..=49151).count(), 49151 - 1024 + 1);
+/// ```
+///
+/// # Panics
+///
+/// If the range is empty
+pub fn int_range(range: RangeInclusive<u64>) -> PresetIter {
+    // without leading zeros, shorter numbers are smaller, and numbers of the same length
+    // are in lexicographic order, which is the order that the iterator produces them in
+    preset(&int_range_pattern(range))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn username() {
+        let x: Vec<String> = super::username().take(3).collect();
+        assert_eq!(x, ["a00", "a01", "a02"]);
+    }
+
+    #[test]
+    fn hex_token() {
+        let x: Vec<String> = super::hex_token(2).collect();
+        assert_eq!(x.len(), 256);
+        assert_eq!(x[0], "00");
+        assert_eq!(x[255], "ff");
+    }
+
+    #[test]
+    fn semver() {
+        let x: Vec<String> = super::semver().take(3).collect();
+        assert_eq!(x, ["0.0.0", "0.0.1", "0.0.2"]);
+
+        // all 1000 of the single digit versions come first
+        let x = super::semver().nth(1000).unwrap();
+        assert_eq!(x, "0.0.10");
+    }
+
+    #[test]
+    fn int_range() {
+        for (lo, hi) in [
+            (0, 255),
+            (7, 1234),
+            (0, 0),
+            (10, 99),
+            (999, 1001),
+            (12345, 12345),
+            (190, 210),
+        ] {
+            let x: Vec<String> = super::int_range(lo..=hi).collect();
+            let y: Vec<String> = (lo..=hi).map(|n| n.to_string()).collect();
+            assert_eq!(x, y, "{lo}..={hi}");
+        }
+
+        let x: Vec<String> = super::int_range(u64::MAX - 2..=u64::MAX).collect();
+        assert_eq!(x.last().unwrap(), &u64::MAX.to_string());
+        assert_eq!(x.len(), 3);
+    }
+
+    #[test]
+    fn date() {
+        // every day of a leap year and a common year
+        assert_eq!(
+            super::date().take_while(|d| d.as_str() < "0002").count(),
+            366 + 365
+        );
+
+        let x: Vec<String> = super::date()
+            .skip_while(|d| d.as_str() < "1900-02-28")
+            .take(2)
+            .collect();
+        // 1900 is not a leap year
+        assert_eq!(x, ["1900-02-28", "1900-03-01"]);
+        let x: Vec<String> = super::date()
+            .skip_while(|d| d.as_str() < "2000-02-28")
+            .take(2)
+            .collect();
+        assert_eq!(x, ["2000-02-28", "2000-02-29"]);
+    }
+
+    #[test]
+    fn datetime() {
+        assert_eq!(super::time().count(), 24 * 60 * 60);
+        let x = super::datetime().nth(24 * 60 * 60 * 31).unwrap();
+        assert_eq!(x, "0000-02-01T00:00:00Z");
+    }
+
+    #[test]
+    fn network() {
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        use crate::DenseDfaIter;
+
+        // the octets are exactly the numbers up to 255
+        let octet = super::int_range_pattern(0..=255);
+        assert_eq!(super::IPV4, format!(r"{octet}(?:\.{octet}){{3}}"));
+
+        // addresses from across the whole language are valid, and written canonically
+        let iter = DenseDfaIter::new(super::IPV4).unwrap().spread(15);
+        for ip in iter.take(1000) {
+            let ip = String::from_utf8(ip).unwrap();
+            let addr: Ipv4Addr = ip.parse().unwrap();
+            assert_eq!(addr.to_string(), ip);
+        }
+        let iter = DenseDfaIter::new(super::IPV6).unwrap().spread(39);
+        for ip in iter.take(1000) {
+            let ip = String::from_utf8(ip).unwrap();
+            assert!(ip.parse::<Ipv6Addr>().is_ok(), "{ip}");
+        }
+
+        let x: Vec<String> = super::ipv4_cidr().take(11).collect();
+        assert_eq!(x[0], "0.0.0.0/0");
+        assert_eq!(x[10], "0.0.0.1/0");
+        let x = super::ipv6_cidr().nth(10).unwrap();
+        assert_eq!(x, "0:0:0:0:0:0:0:1/0");
+
+        let x: Vec<String> = super::mac().take(2).collect();
+        assert_eq!(x, ["00:00:00:00:00:00", "00:00:00:00:00:01"]);
+    }
+}
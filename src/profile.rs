@@ -0,0 +1,99 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// The work done while searching for the matches of one length. See [`ProfileReport`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepthProfile {
+    /// The length of the matches being searched for
+    pub depth: usize,
+    /// The time spent searching, not counting the time between matches
+    pub elapsed: Duration,
+    /// The number of search states that were visited
+    pub states_expanded: u64,
+    /// The number of matches that were produced
+    pub matches: u64,
+}
+
+/// Where the time of an iteration went, per match length. See [`DfaIter::profile`](crate::DfaIter::profile)
+///
+/// Each length is a separate search over every prefix up to that length, so the
+/// states expanded per length show how quickly the search space grows.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    // indexed by depth
+    depths: Vec<DepthProfile>,
+}
+
+impl ProfileReport {
+    /// The work done for each length that has been searched so far, shortest first
+    pub fn depths(&self) -> &[DepthProfile] {
+        &self.depths
+    }
+
+    /// The time spent searching over all lengths
+    pub fn elapsed(&self) -> Duration {
+        self.depths.iter().map(|d| d.elapsed).sum()
+    }
+
+    fn depth_mut(&mut self, depth: usize) -> &mut DepthProfile {
+        if self.depths.len() <= depth {
+            let len = self.depths.len();
+            self.depths.extend((len..=depth).map(|depth| DepthProfile {
+                depth,
+                ..DepthProfile::default()
+            }));
+        }
+        &mut self.depths[depth]
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "depth  elapsed  states  matches")?;
+        for d in &self.depths {
+            writeln!(
+                f,
+                "{}  {:?}  {}  {}",
+                d.depth, d.elapsed, d.states_expanded, d.matches
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Records a [`ProfileReport`] as an iterator searches
+#[derive(Default)]
+pub(crate) struct Profiler {
+    report: ProfileReport,
+    // when the current search started, if it is running
+    since: Option<Instant>,
+}
+
+impl Profiler {
+    pub(crate) fn report(&self) -> &ProfileReport {
+        &self.report
+    }
+
+    /// The search started, or continued
+    pub(crate) fn start(&mut self) {
+        self.since = Some(Instant::now());
+    }
+
+    /// Count the time since the search started towards `depth`, and keep going
+    pub(crate) fn lap(&mut self, depth: usize) {
+        let now = Instant::now();
+        if let Some(since) = self.since.replace(now) {
+            self.report.depth_mut(depth).elapsed += now - since;
+        }
+    }
+
+    pub(crate) fn expand(&mut self, depth: usize) {
+        self.report.depth_mut(depth).states_expanded += 1;
+    }
+
+    pub(crate) fn matched(&mut self, depth: usize) {
+        self.report.depth_mut(depth).matches += 1;
+    }
+}
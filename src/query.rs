@@ -0,0 +1,671 @@
+use std::collections::HashMap;
+
+use regex_automata::dfa::Automaton;
+
+use crate::{count::Counts, DfaIter, InfiniteLanguage};
+
+/// Facts about the shape of a regex's DFA. See [`DfaIter::analysis`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    /// The number of states reachable from the start that can reach a match
+    pub states: usize,
+    /// The number of byte transitions between those states
+    pub transitions: usize,
+    /// The number of strongly connected components of those states
+    pub components: usize,
+    /// Whether a cycle can reach a match, so the regex matches infinitely many strings
+    pub infinite: bool,
+    /// The average number of byte transitions out of each state
+    pub branching_factor: f64,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// The `k` shortest matching strings, with ties broken in lexicographic byte order.
+    ///
+    /// If the regex has fewer than `k` matches, they are all returned.
+    /// These are the first `k` matches that a new iterator would produce, with the same restrictions as this one.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[ab]+c").unwrap();
+    /// assert_eq!(iter.k_shortest(3), [b"ac".to_vec(), b"bc".to_vec(), b"aac".to_vec()]);
+    /// ```
+    pub fn k_shortest(&self, k: usize) -> Vec<Vec<u8>> {
+        // the iterator searches shortest first, only exploring paths that can still
+        // reach a match within the current length, so this only does the work needed for `k` matches
+        let mut iter = self.restarted();
+        let mut out = Vec::with_capacity(k);
+        while out.len() < k {
+            let Some(next) = iter.borrow_next() else {
+                break;
+            };
+            out.push(next.to_vec());
+        }
+        out
+    }
+
+    /// A shortest matching string, or `None` if the regex matches nothing.
+    ///
+    /// Of the shortest matches, this is the lexicographically smallest.
+    /// Like [`DfaIter::k_shortest`], the restrictions on this iterator still apply.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+@[a-z]+\.(?:com|io)").unwrap();
+    /// assert_eq!(iter.shortest_match().unwrap(), b"a@a.io");
+    /// ```
+    pub fn shortest_match(&self) -> Option<Vec<u8>> {
+        // the first match of a new iterator. at the shortest length, every path that the search
+        // explores is on its way to a match, so this walks straight to it
+        self.restarted().borrow_next().map(<[u8]>::to_vec)
+    }
+
+    /// A longest matching string, or `None` if the regex matches nothing.
+    ///
+    /// Of the longest matches, this is the lexicographically smallest.
+    /// This follows the graph that the iterator searches, so restrictions like [`DfaIter::length_mod`]
+    /// still apply, except for [`DfaIter::prune_if`], which can't be known without running the search.
+    ///
+    /// # Errors
+    ///
+    /// If the regex matches infinitely many strings, then there is no longest match.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-c]{2,4}|x{6}").unwrap();
+    /// assert_eq!(iter.longest_match().unwrap().unwrap(), b"xxxxxx");
+    ///
+    /// let iter = DenseDfaIter::new(r"a+").unwrap();
+    /// assert!(iter.longest_match().is_err());
+    /// ```
+    pub fn longest_match(&self) -> Result<Option<Vec<u8>>, InfiniteLanguage> {
+        let (graph, dist) = self.search_graph();
+        if dist[0] == usize::MAX {
+            return Ok(None);
+        }
+
+        // longest path through the live states, which must be acyclic for the language to be finite
+        let mut longest = vec![0; graph.states.len()];
+        // 0 = unvisited, 1 = on the stack, 2 = done
+        let mut mark = vec![0u8; graph.states.len()];
+        let mut stack = vec![(0, 0)];
+        mark[0] = 1;
+        while let Some((current, i)) = stack.last_mut() {
+            let current = *current;
+            if let Some(&(_, _, next)) = graph.edges[current].get(*i) {
+                *i += 1;
+                if dist[next] == usize::MAX {
+                    continue;
+                }
+                match mark[next] {
+                    0 => {
+                        mark[next] = 1;
+                        stack.push((next, 0));
+                    }
+                    1 => return Err(InfiniteLanguage),
+                    _ => {}
+                }
+            } else {
+                stack.pop();
+                mark[current] = 2;
+                longest[current] = graph.edges[current]
+                    .iter()
+                    .filter(|&&(_, _, next)| dist[next] != usize::MAX)
+                    .map(|&(_, _, next)| longest[next] + 1)
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+
+        let mut current = 0;
+        let mut out = Vec::with_capacity(longest[current]);
+        while longest[current] > 0 {
+            let &(b, _, next) = graph.edges[current]
+                .iter()
+                .find(|&&(_, _, next)| {
+                    dist[next] != usize::MAX && longest[next] + 1 == longest[current]
+                })
+                .expect("the longest path should continue");
+            out.push(b);
+            current = next;
+        }
+        Ok(Some(out))
+    }
+
+    /// The lexicographically smallest matching string, or `None` if the regex matches nothing.
+    ///
+    /// Like [`DfaIter::longest_match`], this follows the graph that the iterator searches,
+    /// without [`DfaIter::prune_if`].
+    ///
+    /// # Errors
+    ///
+    /// There might be no smallest match, if there are infinitely many matches that are each smaller
+    /// than the last. For example, `a*b` matches `b`, `ab`, `aab`, ...
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[b-z][a-z]*|b").unwrap();
+    /// assert_eq!(iter.lex_min().unwrap().unwrap(), b"b");
+    /// ```
+    pub fn lex_min(&self) -> Result<Option<Vec<u8>>, InfiniteLanguage> {
+        let (graph, dist) = self.search_graph();
+
+        let mut current = 0;
+        if dist[current] == usize::MAX {
+            return Ok(None);
+        }
+        let mut visited = vec![false; graph.states.len()];
+        let mut out = vec![];
+        // stopping is always smaller than continuing
+        while !graph.accept[current] {
+            if std::mem::replace(&mut visited[current], true) {
+                return Err(InfiniteLanguage);
+            }
+            let &(b, _, next) = graph.edges[current]
+                .iter()
+                .find(|&&(_, _, next)| dist[next] != usize::MAX)
+                .expect("a live state should have a path to an accepting state");
+            out.push(b);
+            current = next;
+        }
+        Ok(Some(out))
+    }
+
+    /// The lexicographically largest matching string of up to `max_len` bytes,
+    /// or `None` if the regex has no matches that short.
+    ///
+    /// Like [`DfaIter::longest_match`], this follows the graph that the iterator searches,
+    /// without [`DfaIter::prune_if`].
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+[0-9]").unwrap();
+    /// assert_eq!(iter.lex_max(4).unwrap(), b"zzz9");
+    /// ```
+    pub fn lex_max(&self, max_len: usize) -> Option<Vec<u8>> {
+        let (graph, dist) = self.search_graph();
+
+        let mut current = 0;
+        if dist[current] > max_len {
+            return None;
+        }
+        let mut out = vec![];
+        // continuing is always larger than stopping, if we can still reach a match in time
+        loop {
+            let remaining = max_len - out.len();
+            let next = graph.edges[current]
+                .iter()
+                .rev()
+                .find(|&&(_, _, next)| dist[next] < remaining);
+            let Some(&(_, b, next)) = next else {
+                break;
+            };
+            out.push(b);
+            current = next;
+        }
+        Some(out)
+    }
+
+    /// Count the states and transitions of the DFA, and find whether the regex matches
+    /// infinitely many strings.
+    ///
+    /// Only the states that the iterator can search through are counted, so states
+    /// that can never lead to a match are left out.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let analysis = DenseDfaIter::new(r"(?:ab)+c").unwrap().analysis();
+    /// assert_eq!(analysis.states, 4);
+    /// assert_eq!(analysis.transitions, 4);
+    /// assert!(analysis.infinite);
+    /// ```
+    pub fn analysis(&self) -> Analysis {
+        let graph = &self.graph;
+        let component = graph.components();
+        let live = |i: usize| self.dist[i] != usize::MAX;
+
+        let states = (0..graph.states.len()).filter(|&i| live(i)).count();
+        let mut components: Vec<usize> = (0..graph.states.len())
+            .filter(|&i| live(i))
+            .map(|i| component[i])
+            .collect();
+        components.sort_unstable();
+        components.dedup();
+
+        let mut transitions = 0;
+        let mut infinite = false;
+        for (i, edges) in graph.edges.iter().enumerate().filter(|&(i, _)| live(i)) {
+            for &(start, end, next) in edges.iter().filter(|&&(_, _, next)| live(next)) {
+                transitions += (end - start) as usize + 1;
+                // an edge within a component is part of a cycle
+                infinite |= component[next] == component[i];
+            }
+        }
+
+        Analysis {
+            states,
+            transitions,
+            components: components.len(),
+            infinite,
+            branching_factor: if states == 0 {
+                0.0
+            } else {
+                transitions as f64 / states as f64
+            },
+        }
+    }
+
+    /// The shortest prefix that leads to each state of the DFA that can still reach a match,
+    /// in the order the iterator would produce them.
+    ///
+    /// Each prefix is where the DFA commits to a state that no shorter prefix reaches, so these
+    /// are the points where the regex branches. Of the prefixes of the same length, the
+    /// lexicographically smallest is used.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-c]x|[b-d]y").unwrap();
+    /// let x = iter.distinguishing_prefixes();
+    /// // `b` and `c` lead to the same state, and so do `x` and `y`
+    /// assert_eq!(x, [b"".to_vec(), b"a".to_vec(), b"b".to_vec(), b"d".to_vec(), b"ax".to_vec()]);
+    /// ```
+    pub fn distinguishing_prefixes(&self) -> Vec<Vec<u8>> {
+        self.access_prefixes().into_iter().map(|(_, p)| p).collect()
+    }
+
+    /// One string that leads to each state of the minimal DFA of the regex, in the order
+    /// the iterator would produce them. Only the states that can still reach a match are included.
+    ///
+    /// Two prefixes lead to the same state of the minimal DFA exactly when every suffix
+    /// completes either both of them or neither to a match, so these are representatives of the
+    /// [Myhill–Nerode](https://en.wikipedia.org/wiki/Myhill%E2%80%93Nerode_theorem) equivalence
+    /// classes of the language. Each is the shortest, then lexicographically smallest, of its class.
+    ///
+    /// Unlike [`DfaIter::distinguishing_prefixes`], this doesn't depend on how the regex was written
+    /// or compiled, so equivalent regexes have the same representatives.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let a = DenseDfaIter::new(r"ab+|ac").unwrap();
+    /// let b = DenseDfaIter::new(r"a(?:bb+|c|b)").unwrap();
+    /// let x = a.state_representatives();
+    /// assert_eq!(x, [b"".to_vec(), b"a".to_vec(), b"ab".to_vec(), b"ac".to_vec()]);
+    /// assert_eq!(x, b.state_representatives());
+    /// ```
+    pub fn state_representatives(&self) -> Vec<Vec<u8>> {
+        let class = self.equivalence_classes();
+        let mut seen = vec![false; self.graph.states.len()];
+        self.access_prefixes()
+            .into_iter()
+            .filter(|&(i, _)| !std::mem::replace(&mut seen[class[i]], true))
+            .map(|(_, p)| p)
+            .collect()
+    }
+
+    /// The live states, each with the shortest, then lexicographically smallest, prefix
+    /// that leads to it, in that order
+    pub(crate) fn access_prefixes(&self) -> Vec<(usize, Vec<u8>)> {
+        let graph = &self.graph;
+        if self.dist[0] == usize::MAX {
+            return vec![];
+        }
+
+        // breadth first, taking the smallest byte into each state first
+        let mut prefix: Vec<Option<Vec<u8>>> = vec![None; graph.states.len()];
+        prefix[0] = Some(vec![]);
+        let mut order = vec![0];
+        let mut i = 0;
+        while let Some(&current) = order.get(i) {
+            i += 1;
+            for &(b, _, next) in &graph.edges[current] {
+                if self.dist[next] == usize::MAX || prefix[next].is_some() {
+                    continue;
+                }
+                let mut p = prefix[current].clone().unwrap();
+                p.push(b);
+                prefix[next] = Some(p);
+                order.push(next);
+            }
+        }
+        order
+            .into_iter()
+            .map(|i| (i, prefix[i].take().unwrap()))
+            .collect()
+    }
+
+    /// Number the live states by which state of the minimal DFA they belong to.
+    /// States that can't reach a match all belong to the dead state, numbered `usize::MAX`.
+    fn equivalence_classes(&self) -> Vec<usize> {
+        let graph = &self.graph;
+        let live = |i: usize| self.dist[i] != usize::MAX;
+
+        // moore's algorithm: split the states by whether they accept, then by which classes
+        // their transitions lead to, until no class splits any further
+        let mut class: Vec<usize> = (0..graph.states.len())
+            .map(|i| match live(i) {
+                true => graph.accept[i] as usize,
+                false => usize::MAX,
+            })
+            .collect();
+        let mut classes = 0;
+        loop {
+            let mut ids = HashMap::new();
+            let next: Vec<usize> = (0..graph.states.len())
+                .map(|i| {
+                    if !live(i) {
+                        return usize::MAX;
+                    }
+                    let mut signature: Vec<(u8, u8, usize)> = vec![];
+                    for &(start, end, t) in &graph.edges[i] {
+                        if !live(t) {
+                            continue;
+                        }
+                        match signature.last_mut() {
+                            Some((_, e, c))
+                                if *c == class[t] && *e as usize + 1 == start as usize =>
+                            {
+                                *e = end
+                            }
+                            _ => signature.push((start, end, class[t])),
+                        }
+                    }
+                    let len = ids.len();
+                    *ids.entry((class[i], signature)).or_insert(len)
+                })
+                .collect();
+            class = next;
+            if ids.len() == classes {
+                return class;
+            }
+            classes = ids.len();
+        }
+    }
+
+    /// The length at or below which `p` percent of the matches of up to `max_len` bytes fall,
+    /// or `None` if the regex has no matches that short.
+    ///
+    /// This can help choose a realistic maximum length for sampling. Only the matches that follow
+    /// the graph the iterator searches are counted, like [`DfaIter::spread`] ranks them.
+    ///
+    /// # Panics
+    ///
+    /// If `p` is not in the range `0.0..=100.0`
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// // almost all of the matches are the longest ones
+    /// let iter = DenseDfaIter::new(r"[a-z]{1,8}").unwrap();
+    /// assert_eq!(iter.length_percentile(50.0, 8), Some(8));
+    /// assert_eq!(iter.length_percentile(1.0, 8), Some(7));
+    /// ```
+    pub fn length_percentile(&self, p: f64, max_len: usize) -> Option<usize> {
+        assert!(
+            (0.0..=100.0).contains(&p),
+            "p={p:?} is not in range 0.0..=100.0"
+        );
+        let (graph, _) = self.search_graph();
+        let counts = Counts::new(&graph, max_len);
+        let threshold = counts.total() as f64 * p / 100.0;
+
+        let mut seen = 0u128;
+        (0..=max_len).find(|&len| {
+            seen = seen.saturating_add(counts.of_len(len));
+            seen > 0 && seen as f64 >= threshold
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DenseDfaIter;
+
+    #[test]
+    fn analysis() {
+        let analysis = DenseDfaIter::new(r"[a-c]{2}|d").unwrap().analysis();
+        assert_eq!(analysis.states, 3);
+        assert_eq!(analysis.transitions, 4 + 3);
+        assert_eq!(analysis.components, 3);
+        assert!(!analysis.infinite);
+        assert!((analysis.branching_factor - 7.0 / 3.0).abs() < 1e-9);
+
+        // the cycle can't reach a match
+        let analysis = DenseDfaIter::new(r"a|b+(?-u:\b\B)").unwrap().analysis();
+        assert!(!analysis.infinite);
+
+        let analysis = DenseDfaIter::new(r"a|b+c").unwrap().analysis();
+        assert!(analysis.infinite);
+
+        let analysis = DenseDfaIter::new(r"[^\s\S]").unwrap().analysis();
+        assert_eq!(analysis.states, 0);
+        assert_eq!(analysis.branching_factor, 0.0);
+    }
+
+    #[test]
+    fn k_shortest() {
+        let iter = DenseDfaIter::new(r"zz|[a-c]{3}|y").unwrap();
+        assert_eq!(
+            iter.k_shortest(4),
+            [
+                b"y".to_vec(),
+                b"zz".to_vec(),
+                b"aaa".to_vec(),
+                b"aab".to_vec()
+            ]
+        );
+        assert_eq!(iter.k_shortest(100).len(), 29);
+
+        // the iterator itself is left untouched
+        assert_eq!(iter.count(), 29);
+
+        let iter = DenseDfaIter::new(r"a*").unwrap().length_mod(2, 1);
+        assert_eq!(
+            iter.k_shortest(3),
+            [b"a".to_vec(), b"aaa".to_vec(), b"aaaaa".to_vec()]
+        );
+
+        let iter = DenseDfaIter::new(r"[a-c]x|y").unwrap();
+        let parts: Vec<_> = iter
+            .partition_by_first()
+            .into_iter()
+            .map(|(_, part)| part.k_shortest(2))
+            .collect();
+        assert_eq!(
+            parts,
+            [vec![b"ax".to_vec(), b"bx".to_vec()], vec![b"y".to_vec()]]
+        );
+
+        let iter = DenseDfaIter::new(r"[0-9]+")
+            .unwrap()
+            .prune_if(|prefix| prefix.starts_with(b"0"));
+        assert_eq!(iter.k_shortest(10)[9], b"10");
+    }
+
+    #[test]
+    fn longest_match() {
+        let iter = DenseDfaIter::new(r"(?:foo|ba[rz])?quux").unwrap();
+        assert_eq!(iter.longest_match().unwrap().unwrap(), b"barquux");
+
+        let iter = DenseDfaIter::new(r"a(?-u:\b)b").unwrap();
+        assert_eq!(iter.longest_match().unwrap(), None);
+
+        // the cycle is not live, since it can never reach a match
+        let iter = DenseDfaIter::new(r"ab|a[0-9]+(?-u:\b)c").unwrap();
+        assert_eq!(iter.longest_match().unwrap().unwrap(), b"ab");
+
+        let iter = DenseDfaIter::new(r"x|(?:ab)+c").unwrap();
+        assert!(iter.longest_match().is_err());
+
+        let iter = DenseDfaIter::new(r"a{1,5}").unwrap().length_mod(2, 0);
+        assert_eq!(iter.longest_match().unwrap().unwrap(), b"aaaa");
+        let iter = DenseDfaIter::new(r"a*").unwrap().length_mod(2, 0);
+        assert!(iter.longest_match().is_err());
+
+        let iter = DenseDfaIter::new(r"a[0-9]{2}|b+|c").unwrap();
+        let parts: Vec<_> = iter
+            .partition_by_first()
+            .into_iter()
+            .map(|(_, part)| part.longest_match().ok())
+            .collect();
+        assert_eq!(
+            parts,
+            [Some(Some(b"a00".to_vec())), None, Some(Some(b"c".to_vec()))]
+        );
+    }
+
+    #[test]
+    fn lex_min() {
+        let iter = DenseDfaIter::new(r"b[0-9]+|ab?c|ab").unwrap();
+        assert_eq!(iter.lex_min().unwrap().unwrap(), b"ab");
+
+        let iter = DenseDfaIter::new(r"[ab]*c").unwrap();
+        assert!(iter.lex_min().is_err());
+
+        let iter = DenseDfaIter::new(r"a(?-u:\b)b").unwrap();
+        assert_eq!(iter.lex_min().unwrap(), None);
+    }
+
+    #[test]
+    fn lex_max() {
+        let iter = DenseDfaIter::new(r"b[0-9]+|ab?c|ab").unwrap();
+        assert_eq!(iter.lex_max(3).unwrap(), b"b99");
+        assert_eq!(iter.lex_max(1), None);
+
+        // `z` is larger than every match starting with `a`, however long
+        let iter = DenseDfaIter::new(r"z|a+zz").unwrap();
+        assert_eq!(iter.lex_max(4).unwrap(), b"z");
+
+        let iter = DenseDfaIter::new(r"a*").unwrap().length_mod(2, 1);
+        assert_eq!(iter.lex_max(4).unwrap(), b"aaa");
+        assert_eq!(iter.lex_min().unwrap().unwrap(), b"a");
+    }
+
+    #[test]
+    fn lex_partitioned() {
+        let iter = DenseDfaIter::new(r"[a-c]x?|y[0-9]+").unwrap();
+        let parts: Vec<_> = iter
+            .partition_by_first()
+            .into_iter()
+            .map(|(_, part)| (part.lex_min().unwrap().unwrap(), part.lex_max(3).unwrap()))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (b"a".to_vec(), b"cx".to_vec()),
+                (b"y0".to_vec(), b"y99".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn length_percentile() {
+        // 1 + 2 + 4 + 8 + 16 matches
+        let iter = DenseDfaIter::new(r"[ab]{0,4}").unwrap();
+        assert_eq!(iter.length_percentile(0.0, 4), Some(0));
+        assert_eq!(iter.length_percentile(20.0, 4), Some(2));
+        assert_eq!(iter.length_percentile(48.0, 4), Some(3));
+        assert_eq!(iter.length_percentile(50.0, 4), Some(4));
+        assert_eq!(iter.length_percentile(100.0, 4), Some(4));
+
+        // only counts up to max_len
+        assert_eq!(iter.length_percentile(100.0, 2), Some(2));
+
+        let iter = DenseDfaIter::new(r"a{5}").unwrap();
+        assert_eq!(iter.length_percentile(50.0, 4), None);
+
+        // 1 + 4 + 16 matches
+        let iter = DenseDfaIter::new(r"[ab]{0,4}").unwrap().length_mod(2, 0);
+        assert_eq!(iter.length_percentile(20.0, 4), Some(2));
+        assert_eq!(iter.length_percentile(50.0, 4), Some(4));
+        assert_eq!(iter.length_percentile(50.0, 3), Some(2));
+
+        // without the empty match
+        let iter = DenseDfaIter::new(r"[ab]{0,4}").unwrap();
+        let parts = iter.partition_by_first();
+        assert_eq!(parts[0].1.length_percentile(100.0, 4), Some(0));
+        assert_eq!(parts[1].1.length_percentile(0.0, 4), Some(1));
+    }
+
+    #[test]
+    fn distinguishing_prefixes() {
+        let iter = DenseDfaIter::new(r"ab+|ac").unwrap();
+        let x = iter.distinguishing_prefixes();
+        // the DFA is not minimal, `ab` and `abb` lead to different states
+        // that accept the same suffixes
+        assert_eq!(
+            x,
+            [
+                b"".to_vec(),
+                b"a".to_vec(),
+                b"ab".to_vec(),
+                b"ac".to_vec(),
+                b"abb".to_vec()
+            ]
+        );
+
+        let iter = DenseDfaIter::new(r"a(?-u:\b)b").unwrap();
+        assert!(iter.distinguishing_prefixes().is_empty());
+    }
+
+    #[test]
+    fn state_representatives() {
+        // `000` is in the same class as `c`, since nothing can follow either
+        let iter = DenseDfaIter::new(r"(?:ab)*c|[0-9]{2,3}").unwrap();
+        let x = iter.state_representatives();
+        assert_eq!(
+            x,
+            [
+                b"".to_vec(),
+                b"0".to_vec(),
+                b"a".to_vec(),
+                b"c".to_vec(),
+                b"00".to_vec(),
+                // unlike the start, can't be followed by digits
+                b"ab".to_vec()
+            ]
+        );
+
+        // the product with the length counter is minimized away
+        let iter = DenseDfaIter::new(r"a*").unwrap().length_mod(2, 0);
+        assert_eq!(iter.state_representatives(), [b"".to_vec(), b"a".to_vec()]);
+
+        let iter = DenseDfaIter::new(r"a(?-u:\b)b").unwrap();
+        assert!(iter.state_representatives().is_empty());
+    }
+
+    #[test]
+    fn shortest_match() {
+        let iter = DenseDfaIter::new(r"x{3,}|[yz]{2}").unwrap();
+        assert_eq!(iter.shortest_match().unwrap(), b"yy");
+
+        let iter = DenseDfaIter::new(r"").unwrap();
+        assert_eq!(iter.shortest_match().unwrap(), b"");
+
+        let iter = DenseDfaIter::new(r"a(?-u:\b)b").unwrap();
+        assert_eq!(iter.shortest_match(), None);
+
+        let iter = DenseDfaIter::new(r"a*").unwrap().length_mod(2, 1);
+        assert_eq!(iter.shortest_match().unwrap(), b"a");
+
+        let iter = DenseDfaIter::new(r"[a-c]|x{2}").unwrap();
+        let parts: Vec<_> = iter
+            .partition_by_first()
+            .into_iter()
+            .map(|(_, part)| part.shortest_match().unwrap())
+            .collect();
+        assert_eq!(parts, [b"a".to_vec(), b"xx".to_vec()]);
+
+        let iter = DenseDfaIter::new(r"[a-c]+")
+            .unwrap()
+            .prune_if(|prefix| prefix.starts_with(b"a"));
+        assert_eq!(iter.shortest_match().unwrap(), b"b");
+    }
+}
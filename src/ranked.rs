@@ -0,0 +1,553 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use regex_automata::dfa::Automaton;
+
+use crate::{count::Counts, dfa::Prune, graph::Graph, BorrowNext, DfaIter};
+
+/// `SpreadIter` will produce every string value up to a maximum length that will match with
+/// the given regex, in an order that spreads evenly over the language.
+///
+/// Every match has a rank in the order that [`DfaIter`] produces them. The ranks are visited in
+/// [van der Corput](https://en.wikipedia.org/wiki/Van_der_Corput_sequence) order, so the first few
+/// outputs sample the whole language instead of only the shortest or lexicographically smallest matches.
+/// The ranks only count the matches the iterator would produce, so restrictions like [`DfaIter::length_mod`]
+/// still apply. Matches removed by [`DfaIter::prune_if`] are ranked, but skipped.
+///
+/// ```
+/// use regex_utils::DenseDfaIter;
+///
+/// let iter = DenseDfaIter::new(r"[a-z]{2}").unwrap().spread(2);
+/// let x: Vec<Vec<u8>> = iter.take(4).collect();
+/// assert_eq!(x, [
+///     b"aa".to_vec(),
+///     b"ts".to_vec(),
+///     b"jw".to_vec(),
+///     b"ey".to_vec(),
+/// ]);
+/// ```
+pub struct SpreadIter {
+    // the reachable states of the graph that the iterator searches
+    graph: Arc<Graph>,
+    // the number of matches from each state
+    counts: Counts,
+    // the number of bits needed to represent every rank
+    bits: u32,
+    // the next index in the sequence, or None if the sequence has finished
+    next: Option<u128>,
+    // prefixes whose matches are skipped
+    prune: Option<Prune>,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Produce every match of up to `max_len` bytes, in an order that spreads evenly over the language.
+    ///
+    /// See [`SpreadIter`] for details
+    pub fn spread(self, max_len: usize) -> SpreadIter {
+        let (graph, _) = self.search_graph();
+        let counts = Counts::new(&graph, max_len);
+        let total = counts.total();
+        // the fewest bits such that every rank below total fits
+        let bits = 128 - total.saturating_sub(1).leading_zeros();
+
+        SpreadIter {
+            next: (total > 0).then_some(0),
+            graph,
+            counts,
+            bits,
+            prune: self.prune,
+            str: vec![],
+        }
+    }
+}
+
+impl SpreadIter {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        let total = self.counts.total();
+        loop {
+            let i = self.next?;
+            self.next = i
+                .checked_add(1)
+                .filter(|&n| n.checked_shr(self.bits).unwrap_or(0) == 0);
+
+            // reverse the bits of the index to get the next rank
+            let rank = match self.bits {
+                0 => 0,
+                bits => i.reverse_bits() >> (128 - bits),
+            };
+            if rank < total {
+                self.counts.unrank(&self.graph, rank, &mut self.str);
+                if !is_pruned(&self.prune, &self.str) {
+                    break Some(&self.str);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the iterator would have stopped searching at a prefix of `s`
+fn is_pruned(prune: &Option<Prune>, s: &[u8]) -> bool {
+    prune
+        .as_ref()
+        .is_some_and(|prune| (0..=s.len()).any(|i| prune(&s[..i])))
+}
+
+impl BorrowNext for SpreadIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        SpreadIter::borrow_next(self)
+    }
+}
+
+impl Iterator for SpreadIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+/// `ShuffledIter` will produce every string value up to a maximum length that will match with
+/// the given regex, in a pseudo-random order.
+///
+/// Every match has a rank in the order that [`DfaIter`] produces them. The ranks are shuffled using a
+/// keyed [Feistel network](https://en.wikipedia.org/wiki/Feistel_cipher), which is a permutation
+/// that can be computed one rank at a time, so no matches need to be buffered.
+/// Like [`SpreadIter`], restrictions on the iterator still apply.
+///
+/// ```
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use regex_utils::DenseDfaIter;
+///
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let iter = DenseDfaIter::new(r"[0-9]{3}").unwrap().shuffled(3, &mut rng);
+///
+/// let x: Vec<String> = iter.map(|s| String::from_utf8(s).unwrap()).collect();
+/// assert_eq!(x.len(), 1000);
+/// assert_ne!(x[..3], ["000", "001", "002"]);
+/// ```
+pub struct ShuffledIter {
+    // the reachable states of the graph that the iterator searches
+    graph: Arc<Graph>,
+    // the number of matches from each state
+    counts: Counts,
+    // the round keys of the permutation
+    keys: [u64; FEISTEL_ROUNDS],
+    // the number of bits in each half of the permuted value
+    half: u32,
+    // the next index to permute
+    next: u128,
+    // prefixes whose matches are skipped
+    prune: Option<Prune>,
+    // the current match
+    str: Vec<u8>,
+}
+
+const FEISTEL_ROUNDS: usize = 4;
+
+impl<A: Automaton> DfaIter<A> {
+    /// Produce every match of up to `max_len` bytes, in a pseudo-random order decided by `rng`.
+    ///
+    /// See [`ShuffledIter`] for details
+    pub fn shuffled<R: Rng + ?Sized>(self, max_len: usize, rng: &mut R) -> ShuffledIter {
+        let (graph, _) = self.search_graph();
+        let counts = Counts::new(&graph, max_len);
+        let bits = 128 - counts.total().saturating_sub(1).leading_zeros();
+
+        ShuffledIter {
+            graph,
+            counts,
+            keys: rng.gen(),
+            // round up so the halves are balanced
+            half: bits.div_ceil(2),
+            next: 0,
+            prune: self.prune,
+            str: vec![],
+        }
+    }
+}
+
+impl ShuffledIter {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        let total = self.counts.total();
+        loop {
+            if self.next >= total {
+                break None;
+            }
+
+            // cycle walk until the permuted rank is in range.
+            // this always terminates because the permutation cycles back to `next` eventually
+            let mut rank = self.permute(self.next);
+            while rank >= total {
+                rank = self.permute(rank);
+            }
+            self.next += 1;
+
+            self.counts.unrank(&self.graph, rank, &mut self.str);
+            if !is_pruned(&self.prune, &self.str) {
+                break Some(&self.str);
+            }
+        }
+    }
+
+    fn permute(&self, x: u128) -> u128 {
+        if self.half == 0 {
+            return x;
+        }
+        let mask = u64::MAX >> (64 - self.half);
+        let mut left = (x >> self.half) as u64 & mask;
+        let mut right = x as u64 & mask;
+        for key in self.keys {
+            (left, right) = (right, left ^ (mix(right ^ key) & mask));
+        }
+        ((left as u128) << self.half) | right as u128
+    }
+}
+
+/// splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+impl BorrowNext for ShuffledIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        ShuffledIter::borrow_next(self)
+    }
+}
+
+impl Iterator for ShuffledIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+/// `RankedIter` produces the matches of a [`DfaIter`] along with their ranks.
+/// See [`DfaIter::enumerated_ranked`]
+pub struct RankedIter<A> {
+    iter: DfaIter<A>,
+    // the reachable states of the graph that the iterator searches
+    graph: Arc<Graph>,
+    // the number of matches of each length from each state
+    counts: Counts,
+    // [len]: the number of matches shorter than `len` bytes
+    shorter: Vec<u128>,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Pair each match with its rank, its index in the order that a new iterator produces the matches.
+    ///
+    /// The rank of a match doesn't depend on where this iterator started, so it stays the same
+    /// after [`DfaIter::resume_after`], or in the parts of [`DfaIter::partition_by_first`].
+    /// It is the same rank that [`NthCache::nth_match`](crate::NthCache::nth_match) takes, so matches
+    /// can be referred to by their rank and recovered later.
+    ///
+    /// Ranks saturate at [`u128::MAX`].
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+").unwrap().resume_after(b"zz").unwrap();
+    /// let x: Vec<(u128, Vec<u8>)> = iter.enumerated_ranked().take(2).collect();
+    /// assert_eq!(x, [(702, b"aaa".to_vec()), (703, b"aab".to_vec())]);
+    ///
+    /// let mut cache = DenseDfaIter::new(r"[a-z]+").unwrap().nth_cache();
+    /// assert_eq!(cache.nth_match(703), Some(&b"aab"[..]));
+    /// ```
+    pub fn enumerated_ranked(self) -> RankedIter<A> {
+        RankedIter {
+            graph: Arc::clone(&self.graph),
+            counts: Counts::new(&self.graph, 0),
+            iter: self,
+            shorter: vec![0],
+        }
+    }
+}
+
+impl<A: Automaton> RankedIter<A> {
+    /// Get the next matching string ref from this regex iterator, with its rank
+    pub fn borrow_next(&mut self) -> Option<(u128, &[u8])> {
+        let next = self.iter.borrow_next()?;
+        let len = next.len();
+        self.counts.extend_to(&self.graph, len);
+        while self.shorter.len() <= len {
+            let l = self.shorter.len() - 1;
+            self.shorter
+                .push(self.shorter[l].saturating_add(self.counts.of_len(l)));
+        }
+        let rank = self.shorter[len].saturating_add(self.counts.rank_len(&self.graph, next));
+        Some((rank, next))
+    }
+}
+
+impl<A: Automaton> Iterator for RankedIter<A> {
+    type Item = (u128, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(|(rank, s)| (rank, s.to_owned()))
+    }
+}
+
+/// `ThenRandomIter` produces every match up to a length, and then random longer matches forever.
+/// See [`DfaIter::then_random`]
+pub struct ThenRandomIter<A, R> {
+    // the exhaustive iterator, until it passes the length limit
+    iter: Option<DfaIter<A>>,
+    // the length limit of the exhaustive iterator
+    exhaustive_len: usize,
+    // the reachable states of the graph that the iterator searches
+    graph: Arc<Graph>,
+    // the number of matches of each length from each state
+    counts: Counts,
+    // the lengths with matches to sample from
+    lengths: Vec<usize>,
+    rng: R,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Produce every match of up to `exhaustive_len` bytes, and then random matches that are longer,
+    /// up to `max_len` bytes.
+    ///
+    /// Each random match first picks one of the longer lengths uniformly, and then a match of that length
+    /// uniformly, so the longer lengths are all covered rather than only the longest.
+    /// Random matches can repeat. The iterator only ends if there are no longer matches to sample.
+    ///
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+").unwrap();
+    /// let x: Vec<Vec<u8>> = iter.then_random(1, 10, StdRng::seed_from_u64(0)).take(100).collect();
+    /// assert_eq!(x[..2], [b"a".to_vec(), b"b".to_vec()]);
+    /// assert!(x[26..].iter().all(|s| (2..=10).contains(&s.len())));
+    /// ```
+    pub fn then_random<R: Rng>(
+        self,
+        exhaustive_len: usize,
+        max_len: usize,
+        rng: R,
+    ) -> ThenRandomIter<A, R> {
+        let counts = Counts::new(&self.graph, max_len);
+        let lengths = (exhaustive_len + 1..=max_len)
+            .filter(|&len| counts.of_len(len) > 0)
+            .collect();
+        ThenRandomIter {
+            graph: Arc::clone(&self.graph),
+            iter: Some(self),
+            exhaustive_len,
+            counts,
+            lengths,
+            rng,
+            str: vec![],
+        }
+    }
+}
+
+impl<A: Automaton, R: Rng> ThenRandomIter<A, R> {
+    /// Whether the iterator has moved on to random matches
+    pub fn is_random(&self) -> bool {
+        self.iter.is_none()
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        if let Some(iter) = &mut self.iter {
+            match iter.borrow_next() {
+                // matches are produced shortest first
+                Some(next) if next.len() <= self.exhaustive_len => {
+                    self.str.clear();
+                    self.str.extend_from_slice(next);
+                    return Some(&self.str);
+                }
+                _ => self.iter = None,
+            }
+        }
+
+        if self.lengths.is_empty() {
+            return None;
+        }
+        let len = self.lengths[self.rng.gen_range(0..self.lengths.len())];
+        let rank = self.rng.gen_range(0..self.counts.of_len(len));
+        self.counts
+            .unrank_len(&self.graph, len, rank, &mut self.str);
+        Some(&self.str)
+    }
+}
+
+impl<A: Automaton, R: Rng> BorrowNext for ThenRandomIter<A, R> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        ThenRandomIter::borrow_next(self)
+    }
+}
+
+impl<A: Automaton, R: Rng> Iterator for ThenRandomIter<A, R> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::DenseDfaIter;
+
+    #[test]
+    fn permutation() {
+        let pattern = r"[a-c]{1,3}x?|foo";
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().spread(3).collect();
+        let y: HashSet<Vec<u8>> = DenseDfaIter::new(pattern)
+            .unwrap()
+            .take_while(|s| s.len() <= 3)
+            .collect();
+        assert_eq!(x.len(), y.len());
+        assert_eq!(x.into_iter().collect::<HashSet<_>>(), y);
+    }
+
+    #[test]
+    fn spread_restricted() {
+        let iter = DenseDfaIter::new(r"a*").unwrap().length_mod(2, 1);
+        let x: HashSet<Vec<u8>> = iter.spread(4).collect();
+        assert_eq!(x, HashSet::from([b"a".to_vec(), b"aaa".to_vec()]));
+
+        let iter = DenseDfaIter::new(r"[a-c]{0,2}").unwrap();
+        for (_, part) in iter.partition_by_first() {
+            let y: HashSet<Vec<u8>> = part.restarted().collect();
+            let x: HashSet<Vec<u8>> = part.spread(2).collect();
+            assert_eq!(x, y);
+        }
+
+        let iter = DenseDfaIter::new(r"[a-c]{1,2}")
+            .unwrap()
+            .prune_if(|prefix| prefix.starts_with(b"b"));
+        let x: Vec<Vec<u8>> = iter.spread(2).collect();
+        assert_eq!(x.len(), 8);
+        assert!(x.iter().all(|s| s[0] != b'b'));
+    }
+
+    #[test]
+    fn shuffled() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let pattern = r"[a-c]{1,3}x?|foo";
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(pattern)
+            .unwrap()
+            .shuffled(4, &mut rng)
+            .collect();
+        let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern)
+            .unwrap()
+            .take_while(|s| s.len() <= 4)
+            .collect();
+        assert_eq!(x.len(), y.len());
+        assert_ne!(x, y);
+        assert_eq!(
+            x.into_iter().collect::<HashSet<_>>(),
+            y.into_iter().collect::<HashSet<_>>()
+        );
+
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(r"a")
+            .unwrap()
+            .shuffled(4, &mut rng)
+            .collect();
+        assert_eq!(x, [b"a".to_vec()]);
+    }
+
+    #[test]
+    fn shuffled_restricted() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let iter = DenseDfaIter::new(r"[ab]*").unwrap().length_mod(3, 2);
+        let x: Vec<Vec<u8>> = iter.shuffled(8, &mut rng).collect();
+        assert_eq!(x.len(), 4 + 32 + 256);
+        assert!(x.iter().all(|s| s.len() % 3 == 2));
+
+        let iter = DenseDfaIter::new(r"[a-c]{0,3}").unwrap();
+        for (class, part) in iter.partition_by_first() {
+            let y: HashSet<Vec<u8>> = part.restarted().collect();
+            let x: Vec<Vec<u8>> = part.shuffled(3, &mut rng).collect();
+            assert_eq!(x.len(), y.len());
+            assert!(x.iter().all(|s| y.contains(s)), "{class:?}");
+        }
+
+        let iter = DenseDfaIter::new(r"[a-c]{1,2}")
+            .unwrap()
+            .prune_if(|prefix| prefix.ends_with(b"c"));
+        let x: HashSet<Vec<u8>> = iter.shuffled(2, &mut rng).collect();
+        assert_eq!(x.len(), 2 + 4);
+        assert!(x.iter().all(|s| !s.contains(&b'c')));
+    }
+
+    #[test]
+    fn small() {
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(r"a").unwrap().spread(3).collect();
+        assert_eq!(x, [b"a".to_vec()]);
+
+        let x: Vec<Vec<u8>> = DenseDfaIter::new(r"a{5}").unwrap().spread(3).collect();
+        assert!(x.is_empty());
+    }
+
+    #[test]
+    fn then_random() {
+        let rng = StdRng::seed_from_u64(1);
+        let pattern = r"(?:ab|[0-9])+";
+        let mut iter = DenseDfaIter::new(pattern).unwrap().then_random(2, 6, rng);
+        let x: Vec<Vec<u8>> = iter.by_ref().take(111 + 500).collect();
+        let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern)
+            .unwrap()
+            .take_while(|s| s.len() <= 2)
+            .collect();
+        assert_eq!(y.len(), 111);
+        assert_eq!(x[..111], y);
+        assert!(iter.is_random());
+
+        let re = regex_automata::dfa::regex::Regex::new(&format!("^(?:{pattern})$")).unwrap();
+        let mut lengths = HashSet::new();
+        for s in &x[111..] {
+            assert!(re.is_match(s), "{s:?}");
+            lengths.insert(s.len());
+        }
+        assert_eq!(lengths, HashSet::from([3, 4, 5, 6]));
+
+        let rng = StdRng::seed_from_u64(2);
+        let iter = DenseDfaIter::new(r"a|bc").unwrap().then_random(1, 10, rng);
+        let x: Vec<Vec<u8>> = iter.take(3).collect();
+        assert_eq!(x, [b"a".to_vec(), b"bc".to_vec(), b"bc".to_vec()]);
+        // no longer matches to sample
+        let rng = StdRng::seed_from_u64(3);
+        let iter = DenseDfaIter::new(r"a|bc").unwrap().then_random(2, 10, rng);
+        assert_eq!(iter.count(), 2);
+    }
+
+    #[test]
+    fn enumerated_ranked() {
+        let pattern = r"(?:foo|[a-c]{2}|x*)[0-2]";
+        let x: Vec<(u128, Vec<u8>)> = DenseDfaIter::new(pattern)
+            .unwrap()
+            .enumerated_ranked()
+            .take(100)
+            .collect();
+        for (i, (rank, _)) in x.iter().enumerate() {
+            assert_eq!(*rank, i as u128);
+        }
+
+        // the ranks of each partition are the ranks in the whole language
+        let iter = DenseDfaIter::new(pattern).unwrap();
+        let mut y: Vec<(u128, Vec<u8>)> = iter
+            .partition_by_first()
+            .into_iter()
+            .flat_map(|(_, iter)| iter.enumerated_ranked().take_while(|(rank, _)| *rank < 100))
+            .collect();
+        y.sort();
+        assert_eq!(x, y);
+    }
+}
@@ -0,0 +1,661 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::BTreeSet;
+
+use rand::Rng;
+use regex_automata::dfa::{dense, Automaton};
+
+use crate::{count::Counts, dfa::anchored_start, graph::Graph, DfaIter};
+
+/// `Sampler` will produce random strings that match the given regex.
+///
+/// Each sample is a random walk through the DFA. At every step, the next byte is chosen
+/// in proportion to its weight (all bytes default to a weight of 1), and at an accepting state,
+/// stopping is one more choice with a weight of 1.
+///
+/// Repetitions like `a+` fall out of these choices, which for wide classes tends to produce
+/// long outputs. Use [`Sampler::geometric_repetitions`] to control the number of repetitions instead.
+///
+/// ```
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use regex_utils::Sampler;
+///
+/// // vowels are much more likely than other letters
+/// let sampler = Sampler::new(r"[a-z]{8}").unwrap().weight_bytes(*b"aeiou", 20);
+///
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let word = sampler.sample(&mut rng).unwrap();
+/// assert_eq!(word.len(), 8);
+/// ```
+pub struct Sampler {
+    // the reachable states of the graph
+    graph: Graph,
+    // the distance from each state to an accepting state
+    dist: Vec<usize>,
+    // the weight of each byte
+    weights: [u32; 256],
+    // the length after which we take the shortest way out
+    max_len: usize,
+    // the probability of repeating a cycle again
+    repeat: Option<f64>,
+}
+
+impl<A: Automaton> From<A> for Sampler {
+    fn from(dfa: A) -> Self {
+        let graph = Graph::new(&dfa, anchored_start(&dfa));
+        let dist = graph.distances();
+        Self {
+            graph,
+            dist,
+            weights: [1; 256],
+            max_len: 64,
+            repeat: None,
+        }
+    }
+}
+
+impl Sampler {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `Sampler`.
+    ///
+    /// If you want a non-default configuration, then use the
+    /// [`dense::Builder`](dense::Builder) to set your own configuration.
+    pub fn new(pattern: &str) -> Result<Self, dense::BuildError> {
+        dense::DFA::builder()
+            .configure(dense::Config::new().accelerate(false))
+            .build(pattern)
+            .map(Self::from)
+    }
+}
+
+impl Sampler {
+    /// Set the weight of every byte.
+    pub fn byte_weights(mut self, weights: [u32; 256]) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Set the weight of a class of bytes.
+    ///
+    /// A byte with weight 0 will only be chosen if there is no other way to complete the match.
+    pub fn weight_bytes(mut self, bytes: impl IntoIterator<Item = u8>, weight: u32) -> Self {
+        for b in bytes {
+            self.weights[b as usize] = weight;
+        }
+        self
+    }
+
+    /// Weight bytes by how often they occur, like the letter frequencies of a language.
+    ///
+    /// Each ASCII letter in the table sets the weight of both its cases. Bytes that are not
+    /// in the table keep their weight, which defaults to 1, so a table with large counts makes them rare.
+    ///
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use regex_utils::{Sampler, ENGLISH_LETTER_FREQUENCIES};
+    ///
+    /// let sampler = Sampler::new(r"\w+")
+    ///     .unwrap()
+    ///     .max_len(200)
+    ///     .frequencies(ENGLISH_LETTER_FREQUENCIES);
+    ///
+    /// let mut rng = StdRng::seed_from_u64(0);
+    /// let word = sampler.sample(&mut rng).unwrap();
+    /// let e = word.iter().filter(|b| b.eq_ignore_ascii_case(&b'e')).count();
+    /// let z = word.iter().filter(|b| b.eq_ignore_ascii_case(&b'z')).count();
+    /// assert!(e > z);
+    /// ```
+    pub fn frequencies(mut self, table: impl IntoIterator<Item = (u8, u32)>) -> Self {
+        for (b, weight) in table {
+            self.weights[b.to_ascii_lowercase() as usize] = weight;
+            self.weights[b.to_ascii_uppercase() as usize] = weight;
+        }
+        self
+    }
+
+    /// Favour the characters of a locale's script wherever the regex allows them, like in `\w` or `.`.
+    ///
+    /// This weights the bytes that start those characters, so it composes with the other weights.
+    ///
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use regex_utils::{Locale, Sampler};
+    ///
+    /// let sampler = Sampler::new(r"\w{10}").unwrap().locale(Locale::Cyrillic);
+    ///
+    /// let mut rng = StdRng::seed_from_u64(0);
+    /// let word = String::from_utf8(sampler.sample(&mut rng).unwrap()).unwrap();
+    /// assert!(word.chars().any(|c| ('\u{400}'..='\u{4ff}').contains(&c)));
+    /// ```
+    pub fn locale(self, locale: Locale) -> Self {
+        self.weight_bytes(locale.bytes(), LOCALE_WEIGHT)
+    }
+
+    /// Set the length at which samples will stop making random choices,
+    /// and take the shortest path to a match. Defaults to 64.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Draw the number of repetitions from a geometric distribution.
+    ///
+    /// Whenever the walk can repeat part of the regex it has already been through (eg the `a` in `a+`),
+    /// it will do so with probability `p`, and otherwise continue on with the rest of the regex.
+    /// Each repetition therefore repeats on average `p / (1 - p)` extra times.
+    ///
+    /// # Panics
+    ///
+    /// If `p` is not in the range `0.0..1.0`
+    pub fn geometric_repetitions(mut self, p: f64) -> Self {
+        assert!((0.0..1.0).contains(&p), "p={p:?} is not in range 0.0..1.0");
+        self.repeat = Some(p);
+        self
+    }
+
+    /// Sample a random matching string.
+    ///
+    /// Returns `None` if the regex matches no strings at all
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec<u8>> {
+        let mut out = vec![];
+        self.sample_into(rng, &mut out).then_some(out)
+    }
+
+    /// Sample a random matching string, written into `out`.
+    ///
+    /// Returns `false` if the regex matches no strings at all
+    pub fn sample_into<R: Rng + ?Sized>(&self, rng: &mut R, out: &mut Vec<u8>) -> bool {
+        out.clear();
+        let mut current = 0;
+        if self.dist[current] == usize::MAX {
+            return false;
+        }
+
+        let mut visited = vec![false; self.graph.states.len()];
+        visited[current] = true;
+
+        while out.len() < self.max_len {
+            let mut stop = if self.graph.accept[current] { 1 } else { 0 };
+
+            // decide whether we are repeating or moving on
+            let mut mode = Mode::Any;
+            if let Some(p) = self.repeat {
+                let repeat = self.total_weight(current, |t| visited[t]);
+                let forward = stop + self.total_weight(current, |t| !visited[t]);
+                if repeat > 0 && (forward == 0 || rng.gen_bool(p)) {
+                    mode = Mode::Repeat;
+                    stop = 0;
+                } else {
+                    mode = Mode::Forward;
+                }
+            }
+            let filter = |t: usize| match mode {
+                Mode::Any => true,
+                Mode::Repeat => visited[t],
+                Mode::Forward => !visited[t],
+            };
+
+            let total = stop + self.total_weight(current, filter);
+            if total == 0 {
+                break;
+            }
+
+            let choice = rng.gen_range(0..total);
+            if choice < stop {
+                return true;
+            }
+            let (b, next) = self.choose(current, choice - stop, filter);
+            out.push(b);
+            current = next;
+            visited[current] = true;
+        }
+
+        // take the shortest way out, still respecting the weights where we can
+        while self.dist[current] > 0 {
+            let shortest = |next: usize| self.dist[next] + 1 == self.dist[current];
+            let total = self.total_weight(current, shortest);
+            let (b, next) = if total == 0 {
+                self.choose_unweighted(current, shortest)
+            } else {
+                self.choose(current, rng.gen_range(0..total), shortest)
+            };
+            out.push(b);
+            current = next;
+        }
+        true
+    }
+
+    /// Sample `n` distinct matching strings of up to `max_len` bytes, uniformly at random.
+    ///
+    /// If the regex has fewer than `n` matches up to that length, they are all returned.
+    /// The samples are returned shortest first, then in lexicographic byte order.
+    ///
+    /// This ignores the byte weights, and the other configuration of the sampler.
+    pub fn sample_distinct<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        n: usize,
+        max_len: usize,
+    ) -> Vec<Vec<u8>> {
+        let counts = Counts::new(&self.graph, max_len);
+        choose_distinct(rng, n, counts.total())
+            .into_iter()
+            .map(|rank| {
+                let mut out = vec![];
+                counts.unrank(&self.graph, rank, &mut out);
+                out
+            })
+            .collect()
+    }
+
+    /// Sample `k` distinct matching strings of each length up to `max_len` bytes, uniformly at random
+    /// within each length.
+    ///
+    /// Lengths with fewer than `k` matches have all of their matches returned.
+    /// The samples are returned shortest first, then in lexicographic byte order.
+    ///
+    /// This ignores the byte weights, and the other configuration of the sampler.
+    pub fn sample_stratified<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        k: usize,
+        max_len: usize,
+    ) -> Vec<Vec<u8>> {
+        let counts = Counts::new(&self.graph, max_len);
+        let mut samples = vec![];
+        for len in 0..=max_len {
+            for rank in choose_distinct(rng, k, counts.of_len(len)) {
+                let mut out = vec![];
+                counts.unrank_len(&self.graph, len, rank, &mut out);
+                samples.push(out);
+            }
+        }
+        samples
+    }
+
+    /// Sample random matching strings
+    pub fn samples<R: Rng>(&self, rng: R) -> Samples<'_, R> {
+        Samples { sampler: self, rng }
+    }
+
+    fn live_bytes<'a>(
+        &'a self,
+        state: usize,
+        filter: impl Fn(usize) -> bool + 'a,
+    ) -> impl Iterator<Item = (u8, usize)> + 'a {
+        self.graph.edges[state]
+            .iter()
+            .filter(move |&&(_, _, t)| self.dist[t] != usize::MAX && filter(t))
+            .flat_map(|&(start, end, t)| (start..=end).map(move |b| (b, t)))
+    }
+
+    fn total_weight(&self, state: usize, filter: impl Fn(usize) -> bool) -> u64 {
+        self.live_bytes(state, filter)
+            .map(|(b, _)| self.weights[b as usize] as u64)
+            .sum()
+    }
+
+    fn choose(&self, state: usize, mut choice: u64, filter: impl Fn(usize) -> bool) -> (u8, usize) {
+        for (b, next) in self.live_bytes(state, filter) {
+            let weight = self.weights[b as usize] as u64;
+            if choice < weight {
+                return (b, next);
+            }
+            choice -= weight;
+        }
+        unreachable!("choice should be less than the total weight")
+    }
+
+    fn choose_unweighted(&self, state: usize, filter: impl Fn(usize) -> bool) -> (u8, usize) {
+        self.live_bytes(state, filter)
+            .next()
+            .expect("a live state should have a path to an accepting state")
+    }
+}
+
+/// The frequency of each letter in English text, per 100,000 letters. See [`Sampler::frequencies`]
+pub const ENGLISH_LETTER_FREQUENCIES: [(u8, u32); 26] = [
+    (b'a', 8167),
+    (b'b', 1492),
+    (b'c', 2782),
+    (b'd', 4253),
+    (b'e', 12702),
+    (b'f', 2228),
+    (b'g', 2015),
+    (b'h', 6094),
+    (b'i', 6966),
+    (b'j', 153),
+    (b'k', 772),
+    (b'l', 4025),
+    (b'm', 2406),
+    (b'n', 6749),
+    (b'o', 7507),
+    (b'p', 1929),
+    (b'q', 95),
+    (b'r', 5987),
+    (b's', 6327),
+    (b't', 9056),
+    (b'u', 2758),
+    (b'v', 978),
+    (b'w', 2360),
+    (b'x', 150),
+    (b'y', 1974),
+    (b'z', 74),
+];
+
+/// The weight of the bytes of a [`Locale`], against the default weight of 1
+const LOCALE_WEIGHT: u32 = 1000;
+
+/// The scripts that [`Sampler::locale`] can favour
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Locale {
+    /// ASCII letters, and the accented letters of Latin-1 and Latin Extended-A, like `é` and `ł`
+    Latin,
+    /// Greek and Coptic, like `λ`
+    Greek,
+    /// Cyrillic, like `ж`
+    Cyrillic,
+    /// Chinese, Japanese and Korean: the CJK unified ideographs, kana and hangul
+    Cjk,
+}
+
+impl Locale {
+    /// The bytes that start the characters of this locale in UTF-8
+    fn bytes(self) -> Vec<u8> {
+        match self {
+            // U+00C0..=U+017F
+            Locale::Latin => (b'a'..=b'z')
+                .chain(b'A'..=b'Z')
+                .chain(0xc3..=0xc5)
+                .collect(),
+            // U+0380..=U+03FF
+            Locale::Greek => vec![0xce, 0xcf],
+            // U+0400..=U+04FF
+            Locale::Cyrillic => (0xd0..=0xd3).collect(),
+            // U+3000..=U+9FFF and U+A000..=U+DFFF, which is mostly hangul
+            Locale::Cjk => (0xe3..=0xed).collect(),
+        }
+    }
+}
+
+/// Floyd's algorithm for choosing `n` distinct ranks below `total`
+fn choose_distinct<R: Rng + ?Sized>(rng: &mut R, n: usize, total: u128) -> BTreeSet<u128> {
+    let n = u128::min(n as u128, total);
+    let mut ranks = BTreeSet::new();
+    for j in total - n..total {
+        let rank = rng.gen_range(0..=j);
+        if !ranks.insert(rank) {
+            ranks.insert(j);
+        }
+    }
+    ranks
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Any,
+    Repeat,
+    Forward,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Choose `k` matching strings of up to `max_len` bytes, uniformly at random.
+    ///
+    /// This streams through every match up to `max_len` bytes long, keeping a reservoir of `k` of them,
+    /// so it needs no memory beyond the reservoir, but takes time proportional to the size of the language.
+    /// See [`Sampler::sample_distinct`] for a faster alternative.
+    ///
+    /// If the regex has fewer than `k` matches up to that length, they are all returned.
+    /// The samples are returned shortest first, then in lexicographic byte order.
+    pub fn reservoir_sample<R: Rng + ?Sized>(
+        mut self,
+        k: usize,
+        max_len: usize,
+        rng: &mut R,
+    ) -> Vec<Vec<u8>> {
+        let mut reservoir: Vec<Vec<u8>> = Vec::with_capacity(k);
+        let mut seen = 0;
+        while let Some(next) = self.borrow_next() {
+            // matches are produced shortest first
+            if next.len() > max_len {
+                break;
+            }
+            if reservoir.len() < k {
+                reservoir.push(next.to_vec());
+            } else {
+                let i = rng.gen_range(0..=seen);
+                if let Some(slot) = reservoir.get_mut(i) {
+                    slot.clear();
+                    slot.extend_from_slice(next);
+                }
+            }
+            seen += 1;
+        }
+
+        reservoir.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+        reservoir
+    }
+}
+
+/// An iterator of random matching strings. See [`Sampler::samples`]
+pub struct Samples<'a, R> {
+    sampler: &'a Sampler,
+    rng: R,
+}
+
+impl<R: Rng> Iterator for Samples<'_, R> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.sampler.sample(&mut self.rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn is_match(sampler: &Sampler, s: &[u8]) -> bool {
+        let mut state = 0;
+        for &b in s {
+            let edges = &sampler.graph.edges[state];
+            match edges
+                .iter()
+                .find(|&&(start, end, _)| start <= b && b <= end)
+            {
+                Some(&(_, _, next)) => state = next,
+                None => return false,
+            }
+        }
+        sampler.graph.accept[state]
+    }
+
+    #[test]
+    fn matches() {
+        let sampler = Sampler::new(r"[a-z]+@[a-z]+\.(com|org)").unwrap();
+        let rng = StdRng::seed_from_u64(1);
+
+        for x in sampler.samples(rng).take(100) {
+            assert!(is_match(&sampler, &x), "{x:?}");
+        }
+    }
+
+    #[test]
+    fn weights() {
+        let sampler = Sampler::new(r"[a-z]{100}")
+            .unwrap()
+            .max_len(100)
+            .weight_bytes(*b"aeiou", 100);
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let x = sampler.sample(&mut rng).unwrap();
+        let vowels = x.iter().filter(|b| b"aeiou".contains(b)).count();
+        // 500 / 521 of the weight is on vowels
+        assert!(vowels > 90, "{vowels}");
+    }
+
+    #[test]
+    fn frequencies() {
+        let sampler = Sampler::new(r"\w+")
+            .unwrap()
+            .max_len(500)
+            .frequencies(ENGLISH_LETTER_FREQUENCIES);
+        let mut rng = StdRng::seed_from_u64(13);
+
+        let x = sampler.sample(&mut rng).unwrap();
+        assert!(is_match(&sampler, &x));
+        // digits, `_` and everything outside of ascii only have a weight of 1
+        let letters = x.iter().filter(|b| b.is_ascii_alphabetic()).count();
+        assert!(letters + 10 > x.len(), "{letters}/{}", x.len());
+        let count = |l: u8| x.iter().filter(|b| b.eq_ignore_ascii_case(&l)).count();
+        assert!(count(b'e') > count(b'k') + count(b'j') + count(b'q') + count(b'x') + count(b'z'));
+        assert!(x.iter().any(u8::is_ascii_uppercase));
+    }
+
+    #[test]
+    fn locale() {
+        let script = |locale| match locale {
+            Locale::Latin => |c: char| c.is_ascii_alphabetic() || ('À'..='ſ').contains(&c),
+            Locale::Greek => |c: char| ('\u{370}'..='\u{3ff}').contains(&c),
+            Locale::Cyrillic => |c: char| ('\u{400}'..='\u{4ff}').contains(&c),
+            Locale::Cjk => {
+                |c: char| ('\u{3000}'..='\u{9fff}').contains(&c) || ('가'..='힣').contains(&c)
+            }
+        };
+        for locale in [Locale::Latin, Locale::Greek, Locale::Cyrillic, Locale::Cjk] {
+            let sampler = Sampler::new(r"\w+").unwrap().max_len(200).locale(locale);
+            let mut rng = StdRng::seed_from_u64(14);
+
+            let x = String::from_utf8(sampler.sample(&mut rng).unwrap()).unwrap();
+            let total = x.chars().count();
+            let matching = x.chars().filter(|&c| script(locale)(c)).count();
+            assert!(
+                matching * 10 > total * 8,
+                "{locale:?} {matching}/{total} {x}"
+            );
+        }
+    }
+
+    #[test]
+    fn zero_weight() {
+        // `b` is only chosen when it's needed to complete the match
+        let sampler = Sampler::new(r"[ab]{3}").unwrap().weight_bytes([b'b'], 0);
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(sampler.sample(&mut rng).unwrap(), b"aaa");
+
+        let sampler = Sampler::new(r"b{3}").unwrap().weight_bytes([b'b'], 0);
+        assert_eq!(sampler.sample(&mut rng).unwrap(), b"bbb");
+    }
+
+    #[test]
+    fn max_len() {
+        let sampler = Sampler::new(r"a+b").unwrap().max_len(10);
+        let rng = StdRng::seed_from_u64(4);
+
+        for x in sampler.samples(rng).take(100) {
+            assert!(x.len() <= 11, "{x:?}");
+        }
+    }
+
+    #[test]
+    fn geometric() {
+        let sampler = Sampler::new(r"a+").unwrap().geometric_repetitions(0.5);
+        let rng = StdRng::seed_from_u64(6);
+
+        // 1 required + 1 extra on average
+        let total: usize = sampler.samples(rng).take(10000).map(|x| x.len()).sum();
+        let mean = total as f64 / 10000.0;
+        assert!((1.9..2.1).contains(&mean), "{mean}");
+    }
+
+    #[test]
+    fn no_repetitions() {
+        let sampler = Sampler::new(r"(?:ab)+c|[0-9]+")
+            .unwrap()
+            .geometric_repetitions(0.0);
+        let rng = StdRng::seed_from_u64(7);
+
+        for x in sampler.samples(rng).take(100) {
+            assert!(x.len() <= 3, "{x:?}");
+        }
+    }
+
+    #[test]
+    fn distinct() {
+        let sampler = Sampler::new(r"[a-z]{1,3}").unwrap();
+        let mut rng = StdRng::seed_from_u64(8);
+
+        let x = sampler.sample_distinct(&mut rng, 1000, 3);
+        assert_eq!(x.len(), 1000);
+        assert!(x
+            .windows(2)
+            .all(|w| (w[0].len(), &w[0]) < (w[1].len(), &w[1])));
+        // most of the language is 3 bytes long
+        let long = x.iter().filter(|s| s.len() == 3).count();
+        assert!(long > 900, "{long}");
+    }
+
+    #[test]
+    fn distinct_all() {
+        // fewer matches than requested
+        let sampler = Sampler::new(r"a+").unwrap();
+        let mut rng = StdRng::seed_from_u64(9);
+
+        let x = sampler.sample_distinct(&mut rng, 10, 3);
+        assert_eq!(x, [b"a".to_vec(), b"aa".to_vec(), b"aaa".to_vec()]);
+    }
+
+    #[test]
+    fn stratified() {
+        let sampler = Sampler::new(r"[0-9]+").unwrap();
+        let mut rng = StdRng::seed_from_u64(12);
+
+        let x = sampler.sample_stratified(&mut rng, 5, 4);
+        assert_eq!(x.len(), 5 + 5 + 5 + 5);
+        for (i, s) in x.iter().enumerate() {
+            assert_eq!(s.len(), i / 5 + 1);
+        }
+
+        // only 3 strings of length 1
+        let sampler = Sampler::new(r"[a-c]{3}|[a-c]").unwrap();
+        let x = sampler.sample_stratified(&mut rng, 5, 3);
+        assert_eq!(x.len(), 3 + 5);
+    }
+
+    #[test]
+    fn reservoir() {
+        let mut rng = StdRng::seed_from_u64(10);
+
+        // every 1 byte string should be chosen about 1/4 of the time
+        let mut chosen = [0; 4];
+        let dfa = dense::DFA::new(r"[a-d]{3}|[a-d]").unwrap();
+        for _ in 0..200 {
+            for s in DfaIter::from(&dfa).reservoir_sample(17, 3, &mut rng) {
+                if let [b] = s[..] {
+                    chosen[(b - b'a') as usize] += 1;
+                }
+            }
+        }
+        for c in chosen {
+            assert!((30..70).contains(&c), "{chosen:?}");
+        }
+    }
+
+    #[test]
+    fn reservoir_all() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let iter = crate::DenseDfaIter::new(r"a+").unwrap();
+        let x = iter.reservoir_sample(10, 3, &mut rng);
+        assert_eq!(x, [b"a".to_vec(), b"aa".to_vec(), b"aaa".to_vec()]);
+    }
+
+    #[test]
+    fn empty() {
+        let sampler = Sampler::new(r"a(?-u:\b)b").unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        assert_eq!(sampler.sample(&mut rng), None);
+    }
+}
@@ -0,0 +1,456 @@
+use core::fmt::Write;
+use std::sync::Arc;
+
+use rand::Rng;
+use regex_automata::dfa::Automaton;
+
+use crate::{BorrowNext, ByteClass, DfaIter};
+
+/// [state]: (the bytes that lead to the target, target) for each target that can reach a match,
+/// in order of their lowest byte
+fn shape_edges<A: Automaton>(iter: &DfaIter<A>) -> Vec<Vec<(ByteClass, usize)>> {
+    let live = |i: usize| iter.dist[i] != usize::MAX;
+    iter.graph
+        .edges
+        .iter()
+        .map(|edges| {
+            let mut targets: Vec<(ByteClass, usize)> = vec![];
+            for &(start, end, next) in edges.iter().filter(|e| live(e.2)) {
+                let i = match targets.iter().position(|&(_, t)| t == next) {
+                    Some(i) => i,
+                    None => {
+                        targets.push((ByteClass::empty(), next));
+                        targets.len() - 1
+                    }
+                };
+                for b in start..=end {
+                    targets[i].0.insert(b);
+                }
+            }
+            targets
+        })
+        .collect()
+}
+
+/// `ShapeIter` produces the paths through a DFA, with each step described by the class of bytes
+/// that take it. See [`DfaIter::shapes`]
+pub struct ShapeIter {
+    // [state]: (the bytes that lead to the target, target) for each target that can reach a match,
+    // in order of their lowest byte
+    edges: Arc<[Vec<(ByteClass, usize)>]>,
+    // whether the input can end in each state
+    accept: Arc<[bool]>,
+    // the fewest steps needed to reach a match from each state
+    dist: Arc<[usize]>,
+    // the length of shapes currently being produced
+    depth: usize,
+    // whether there could be longer shapes than the current depth
+    more: bool,
+    // (state, next edge to explore) for each step of the current path
+    stack: Vec<(usize, usize)>,
+    // the classes of the current path
+    path: Vec<ByteClass>,
+}
+
+impl ShapeIter {
+    fn new<A: Automaton>(iter: &DfaIter<A>) -> Self {
+        Self {
+            edges: shape_edges(iter).into(),
+            accept: iter.graph.accept.clone().into(),
+            dist: iter.dist.clone(),
+            depth: 0,
+            more: false,
+            stack: if iter.dist[0] != usize::MAX {
+                vec![(0, 0)]
+            } else {
+                vec![]
+            },
+            path: vec![],
+        }
+    }
+
+    /// Get the next shape ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[ByteClass]> {
+        loop {
+            let Some(&(state, edge)) = self.stack.last() else {
+                if !self.more {
+                    return None;
+                }
+                self.depth += 1;
+                self.more = false;
+                self.stack.push((0, 0));
+                continue;
+            };
+            let pos = self.stack.len() - 1;
+            self.path.truncate(pos);
+
+            if pos == self.depth {
+                self.stack.pop();
+                self.more |= !self.edges[state].is_empty();
+                if self.accept[state] {
+                    return Some(&self.path);
+                }
+                continue;
+            }
+            let Some(&(class, next)) = self.edges[state].get(edge) else {
+                self.stack.pop();
+                continue;
+            };
+            self.stack[pos].1 += 1;
+            if pos + 1 + self.dist[next] > self.depth {
+                // this step leads to longer shapes
+                self.more = true;
+                continue;
+            }
+            self.path.push(class);
+            self.stack.push((next, 0));
+        }
+    }
+}
+
+impl Iterator for ShapeIter {
+    type Item = Vec<ByteClass>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+impl ShapeIter {
+    /// Turn each shape into one match, picking the byte for each class with `pick`.
+    ///
+    /// `pick` must return a byte from the class it is given.
+    pub fn fill<F: FnMut(&ByteClass) -> u8>(self, pick: F) -> FilledIter<F> {
+        FilledIter {
+            shapes: self,
+            pick,
+            str: vec![],
+        }
+    }
+
+    /// Turn each shape into one match, using the lowest byte of each class.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+[0-9]|#").unwrap();
+    /// let x: Vec<Vec<u8>> = iter.shapes().fill_lowest().take(3).collect();
+    /// assert_eq!(x, [b"#".to_vec(), b"a0".to_vec(), b"aa0".to_vec()]);
+    /// ```
+    pub fn fill_lowest(self) -> FilledIter<impl FnMut(&ByteClass) -> u8> {
+        self.fill(|class| class.iter().next().expect("classes are not empty"))
+    }
+
+    /// Turn each shape into one match, picking a random byte from each class.
+    ///
+    /// This produces a small set of matches that still covers every path through the regex,
+    /// even when the regex has far too many matches to produce them all.
+    ///
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[0-9]{4}-(?:[0-9]{2}|x)").unwrap();
+    /// let x: Vec<Vec<u8>> = iter.shapes().fill_random(StdRng::seed_from_u64(0)).collect();
+    /// assert_eq!(x.len(), 2);
+    /// assert!(x[0].ends_with(b"-x"));
+    /// assert_eq!(x[1].len(), 7);
+    /// ```
+    pub fn fill_random<R: Rng>(self, mut rng: R) -> FilledIter<impl FnMut(&ByteClass) -> u8> {
+        self.fill(move |class| {
+            let i = rng.gen_range(0..class.len());
+            class.iter().nth(i).expect("classes are not empty")
+        })
+    }
+}
+
+/// `FilledIter` produces one match for each shape of a regex. See [`ShapeIter::fill`]
+pub struct FilledIter<F> {
+    // the paths through the DFA
+    shapes: ShapeIter,
+    // chooses the byte for each class
+    pick: F,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl<F: FnMut(&ByteClass) -> u8> FilledIter<F> {
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        let shape = self.shapes.borrow_next()?;
+        self.str.clear();
+        for class in shape {
+            let b = (self.pick)(class);
+            debug_assert!(class.contains(b), "{b} was picked from outside {class:?}");
+            self.str.push(b);
+        }
+        Some(&self.str)
+    }
+}
+
+impl<F: FnMut(&ByteClass) -> u8> BorrowNext for FilledIter<F> {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        FilledIter::borrow_next(self)
+    }
+}
+
+impl<F: FnMut(&ByteClass) -> u8> Iterator for FilledIter<F> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Iterate over the distinct paths through the DFA that reach a match, as the sequence of
+    /// byte classes that each step of the path takes.
+    ///
+    /// Every byte in a class leads to the same state, so a shape stands for all the matches
+    /// that pick one byte from each of its classes, and every match has exactly one shape.
+    /// Shapes are produced shortest first, and then in order of the lowest byte of each class.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+[0-9]|#").unwrap();
+    /// let shapes: Vec<String> = iter.shapes().take(3).map(|s| format!("{s:?}")).collect();
+    /// assert_eq!(shapes, [
+    ///     "[[#]]",
+    ///     "[[a-z], [0-9]]",
+    ///     "[[a-z], [a-z], [0-9]]",
+    /// ]);
+    /// ```
+    pub fn shapes(&self) -> ShapeIter {
+        ShapeIter::new(self)
+    }
+
+    /// The number of shapes of up to `max_len` bytes, as produced by [`DfaIter::shapes`].
+    ///
+    /// This is at most the number of matches, and usually far fewer, so it tells whether
+    /// covering every shape is feasible when covering every match is not.
+    /// The count saturates at [`u128::MAX`].
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]{1,5}@[a-z]{1,5}\.(?:com|org)").unwrap();
+    /// assert_eq!(iter.count_shapes(20), 5 * 5 * 2);
+    /// ```
+    pub fn count_shapes(&self, max_len: usize) -> u128 {
+        let edges = shape_edges(self);
+        // [state]: the number of shapes of the current length from each state
+        let mut ways: Vec<u128> = self.graph.accept.iter().map(|&a| a as u128).collect();
+        let mut total = ways[0];
+        for _ in 0..max_len {
+            ways = edges
+                .iter()
+                .map(|edges| {
+                    edges
+                        .iter()
+                        .fold(0u128, |sum, &(_, next)| sum.saturating_add(ways[next]))
+                })
+                .collect();
+            total = total.saturating_add(ways[0]);
+        }
+        total
+    }
+
+    /// Iterate over the matches with their character classes left unexpanded, like `[0-9][0-9]-[a-z]`.
+    ///
+    /// Each skeleton is a path through the DFA, with each step written as the class of bytes that
+    /// take it, or as the byte itself if it is the only one. Bytes that form UTF-8 are written as text,
+    /// and others like `\xff`. A regex with millions of matches can have only a handful of skeletons.
+    ///
+    /// Skeletons are produced shortest first, starting from the start of the regex.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[0-9]{4}-(?:[0-9]{2}|x)").unwrap();
+    /// let x: Vec<String> = iter.skeletons().collect();
+    /// assert_eq!(x, [
+    ///     "[0-9][0-9][0-9][0-9]-x",
+    ///     "[0-9][0-9][0-9][0-9]-[0-9][0-9]",
+    /// ]);
+    /// ```
+    pub fn skeletons(&self) -> SkeletonIter {
+        SkeletonIter {
+            shapes: ShapeIter::new(self),
+            str: String::new(),
+        }
+    }
+}
+
+/// `SkeletonIter` produces the matches of a regex with their classes unexpanded.
+/// See [`DfaIter::skeletons`]
+pub struct SkeletonIter {
+    // the paths through the DFA
+    shapes: ShapeIter,
+    // the current skeleton
+    str: String,
+}
+
+impl SkeletonIter {
+    /// Get the next skeleton ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&str> {
+        let shape = self.shapes.borrow_next()?;
+        self.str.clear();
+        write_skeleton(&mut self.str, shape);
+        Some(&self.str)
+    }
+}
+
+impl BorrowNext for SkeletonIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        SkeletonIter::borrow_next(self).map(str::as_bytes)
+    }
+}
+
+impl Iterator for SkeletonIter {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+/// Write the classes of a shape, with runs of single bytes written as text where they are UTF-8
+fn write_skeleton(out: &mut String, shape: &[ByteClass]) {
+    let mut i = 0;
+    while i < shape.len() {
+        if shape[i].len() != 1 {
+            let _ = write!(out, "{:?}", shape[i]);
+            i += 1;
+            continue;
+        }
+
+        let run: Vec<u8> = shape[i..]
+            .iter()
+            .take_while(|class| class.len() == 1)
+            .filter_map(|class| class.iter().next())
+            .collect();
+        i += run.len();
+        for chunk in run.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                // the characters that are special outside of classes
+                if "\\.+*?()|[]{}^$".contains(c) {
+                    out.push('\\');
+                }
+                if c.is_control() {
+                    let _ = write!(out, "{}", c.escape_default());
+                } else {
+                    out.push(c);
+                }
+            }
+            for b in chunk.invalid() {
+                let _ = write!(out, "\\x{b:02x}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+    use regex_automata::dfa::{dense::DFA, regex::Regex};
+
+    use crate::{DenseDfaIter, DfaIter};
+
+    #[test]
+    fn shapes() {
+        let pattern = r"(?:ab|[a-c]x)[0-9]?";
+        let mut x = vec![];
+        for shape in DenseDfaIter::new(pattern).unwrap().shapes() {
+            // expand each shape back into its matches
+            let mut matches = vec![vec![]];
+            for class in shape {
+                matches = matches
+                    .into_iter()
+                    .flat_map(|m: Vec<u8>| class.iter().map(move |b| [&m[..], &[b]].concat()))
+                    .collect();
+            }
+            x.extend(matches);
+        }
+        x.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+        let y: Vec<Vec<u8>> = DenseDfaIter::new(pattern).unwrap().collect();
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn count_shapes() {
+        for pattern in [r"(?:ab|[a-c]x)[0-9]?", r"[a-z]+[0-9]|#", r"(?:a|bc)*", r""] {
+            let iter = DenseDfaIter::new(pattern).unwrap();
+            for max_len in 0..6 {
+                let x = iter.shapes().take_while(|s| s.len() <= max_len).count();
+                assert_eq!(iter.count_shapes(max_len), x as u128, "{pattern}");
+            }
+        }
+        let iter = DenseDfaIter::new(r"[^\s\S]").unwrap();
+        assert_eq!(iter.count_shapes(10), 0);
+        // a class is one shape however many bytes it has
+        let iter = DenseDfaIter::new(r"[ab]*").unwrap();
+        assert_eq!(iter.count_shapes(1000), 1001);
+        let iter = DenseDfaIter::new(r"(?:a|bc)*").unwrap();
+        assert_eq!(iter.count_shapes(1000), u128::MAX);
+    }
+
+    #[test]
+    fn fill() {
+        let pattern = r"(?:[a-z]+|é)@[a-z]{2,}\.(?:com|org)|[а-я]";
+        let re = Regex::new(&format!("^(?:{pattern})$")).unwrap();
+        let dfa = DFA::new(pattern).unwrap();
+        let iter = DfaIter::from(&dfa);
+
+        let x: Vec<Vec<u8>> = iter.shapes().fill_lowest().take(50).collect();
+        let y: Vec<Vec<u8>> = iter
+            .shapes()
+            .fill_random(StdRng::seed_from_u64(0))
+            .take(50)
+            .collect();
+        assert_eq!(x.len(), 50);
+        for (x, y) in x.iter().zip(&y) {
+            assert!(re.is_match(x), "{x:?}");
+            assert!(re.is_match(y), "{y:?}");
+            assert_eq!(x.len(), y.len());
+        }
+        assert_ne!(x, y);
+
+        // every shape is distinct, so are the matches picked from them
+        let mut z = x.clone();
+        z.sort();
+        z.dedup();
+        assert_eq!(z.len(), x.len());
+    }
+
+    #[test]
+    fn skeletons() {
+        let iter = DenseDfaIter::new(r"(?:a|b)\.c+|é[xyz]?").unwrap();
+        let x: Vec<String> = iter.skeletons().take(5).collect();
+        assert_eq!(
+            x,
+            [r"é", r"[a-b]\.c", r"é[x-z]", r"[a-b]\.cc", r"[a-b]\.ccc"]
+        );
+
+        let iter = DenseDfaIter::new(r"[^\s\S]").unwrap();
+        assert_eq!(iter.skeletons().next(), None);
+
+        let iter = DenseDfaIter::new(r"").unwrap();
+        assert_eq!(iter.skeletons().collect::<Vec<_>>(), [""]);
+    }
+
+    #[test]
+    fn finite() {
+        let iter = DenseDfaIter::new(r"[0-9]{1,3}|\n").unwrap();
+        let x: Vec<String> = iter.skeletons().collect();
+        assert_eq!(x, [r"\n", "[0-9]", "[0-9][0-9]", "[0-9][0-9][0-9]"]);
+    }
+
+    #[test]
+    fn unicode() {
+        // multi-byte classes are split into their bytes
+        let iter = DenseDfaIter::new(r"[а-я]").unwrap();
+        let x: Vec<String> = iter.skeletons().collect();
+        assert_eq!(x, [r"\xd0[\xb0-\xbf]", r"\xd1[\x80-\x8f]"]);
+    }
+}
@@ -0,0 +1,81 @@
+use core::fmt::{self, Write};
+
+/// Formats a match for logs and assertion messages, whether or not it is valid UTF-8.
+///
+/// UTF-8 text is shown as is, except for control characters, which are escaped like `\n`.
+/// Bytes that are not valid UTF-8 are shown as `\xff`. [`fmt::Debug`] also quotes the match.
+///
+/// A precision truncates the match to that many characters, each escape counting as one.
+///
+/// ```
+/// use regex_utils::ShowMatch;
+///
+/// assert_eq!(ShowMatch(b"caf\xc3\xa9\n\xff").to_string(), r"café\n\xff");
+/// assert_eq!(format!("{:?}", ShowMatch(b"say \"hi\"")), r#""say \"hi\"""#);
+/// assert_eq!(format!("{:.3}", ShowMatch(b"abcdef")), "abc…");
+/// ```
+#[derive(Clone, Copy)]
+pub struct ShowMatch<'a>(pub &'a [u8]);
+
+impl ShowMatch<'_> {
+    fn write(&self, f: &mut fmt::Formatter<'_>, quoted: bool) -> fmt::Result {
+        let mut remaining = f.precision().unwrap_or(usize::MAX);
+        for chunk in self.0.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if remaining == 0 {
+                    return f.write_char('…');
+                }
+                remaining -= 1;
+                match c {
+                    '"' | '\\' if quoted => write!(f, "\\{c}")?,
+                    c if c.is_control() => write!(f, "{}", c.escape_default())?,
+                    c => f.write_char(c)?,
+                }
+            }
+            for b in chunk.invalid() {
+                if remaining == 0 {
+                    return f.write_char('…');
+                }
+                remaining -= 1;
+                write!(f, "\\x{b:02x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ShowMatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+impl fmt::Debug for ShowMatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        self.write(f, true)?;
+        f.write_char('"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn show() {
+        assert_eq!(ShowMatch(b"").to_string(), "");
+        assert_eq!(ShowMatch(b"\t\x00\x7f").to_string(), r"\t\u{0}\u{7f}");
+        // a truncated utf8 sequence
+        assert_eq!(ShowMatch(b"\xe2\x82").to_string(), r"\xe2\x82");
+        assert_eq!(format!("{:?}", ShowMatch(b"a\\b")), r#""a\\b""#);
+    }
+
+    #[test]
+    fn truncate() {
+        assert_eq!(format!("{:.3}", ShowMatch(b"abc")), "abc");
+        assert_eq!(format!("{:.0}", ShowMatch(b"abc")), "…");
+        assert_eq!(format!("{:.2}", ShowMatch(b"\n\xff\xff")), r"\n\xff…");
+        assert_eq!(format!("{:.1?}", ShowMatch("éé".as_bytes())), "\"é…\"");
+    }
+}
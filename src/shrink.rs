@@ -0,0 +1,286 @@
+use std::{collections::HashSet, sync::Arc};
+
+use regex_automata::dfa::Automaton;
+
+use crate::{graph::Graph, BorrowNext, DfaIter};
+
+/// `ShrinkIter` produces simpler matches derived from a seed match. See [`DfaIter::shrink`]
+pub struct ShrinkIter {
+    // the reachable states of the graph
+    graph: Arc<Graph>,
+    // the fewest bytes needed to reach a match from each state
+    dist: Arc<[usize]>,
+    // the match to shrink
+    seed: Vec<u8>,
+    // [i]: the state after the first `i` bytes of the seed
+    states: Vec<usize>,
+    // the next kind of candidate to try
+    step: Step,
+    // the candidates produced so far
+    seen: HashSet<Vec<u8>>,
+    // the current candidate
+    str: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    // keep the first `i` bytes, then finish with the smallest completion
+    Complete(usize),
+    // remove `size` bytes starting at `start`
+    Delete { size: usize, start: usize },
+    // replace the byte at `i` with the smallest one that still matches
+    Lower(usize),
+    Done,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Produce matches that are simpler than `seed`, to find a minimal input that still fails a test.
+    /// Returns `None` if `seed` is not a match.
+    ///
+    /// Every candidate is a match, and is smaller than `seed`: shorter, or the same length and
+    /// lexicographically smaller. The bigger simplifications come first:
+    /// 1. keeping a prefix of the seed, and finishing it with the smallest completion,
+    /// 2. removing runs of bytes, like repetitions, from the longest runs down to single bytes,
+    /// 3. replacing a byte with the smallest byte that still matches, like an earlier alternative or class byte.
+    ///
+    /// To shrink, take the first candidate that still fails, then shrink again from that,
+    /// until none of the candidates fail.
+    ///
+    /// ```
+    /// use regex_utils::DenseDfaIter;
+    ///
+    /// let iter = DenseDfaIter::new(r"[a-z]+[0-9]+").unwrap();
+    /// // the smallest input that still contains an `l`
+    /// let fails = |s: &[u8]| s.contains(&b'l');
+    ///
+    /// let mut current = b"hello123".to_vec();
+    /// while let Some(next) = iter.shrink(&current).unwrap().find(|s| fails(s)) {
+    ///     current = next;
+    /// }
+    /// assert_eq!(current, b"l0");
+    /// ```
+    pub fn shrink(&self, seed: &[u8]) -> Option<ShrinkIter> {
+        ShrinkIter::new(Arc::clone(&self.graph), Arc::clone(&self.dist), seed)
+    }
+}
+
+/// The state after `b` from `state`, if it isn't dead
+pub(crate) fn step(graph: &Graph, state: usize, b: u8) -> Option<usize> {
+    graph.edges[state]
+        .iter()
+        .find(|&&(start, end, _)| start <= b && b <= end)
+        .map(|&(_, _, next)| next)
+}
+
+/// Whether the whole of `s` is a match
+pub(crate) fn is_match(graph: &Graph, s: &[u8]) -> bool {
+    s.iter()
+        .try_fold(0, |state, &b| step(graph, state, b))
+        .is_some_and(|state| graph.accept[state])
+}
+
+impl ShrinkIter {
+    /// Shrink `seed` through `graph`. Returns `None` if `seed` is not a match
+    pub(crate) fn new(graph: Arc<Graph>, dist: Arc<[usize]>, seed: &[u8]) -> Option<Self> {
+        let mut states = vec![0];
+        for &b in seed {
+            states.push(step(&graph, *states.last()?, b)?);
+        }
+        if !graph.accept[*states.last()?] {
+            return None;
+        }
+
+        Some(Self {
+            graph,
+            dist,
+            seed: seed.to_vec(),
+            states,
+            step: Step::Complete(0),
+            seen: HashSet::new(),
+            str: vec![],
+        })
+    }
+
+    /// The match that is being shrunk
+    pub fn seed(&self) -> &[u8] {
+        &self.seed
+    }
+
+    /// Get the next candidate ref from this iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            if !self.next_candidate() {
+                return None;
+            }
+            if self.is_smaller() && self.seen.insert(self.str.clone()) {
+                return Some(&self.str);
+            }
+        }
+    }
+
+    /// Write the next candidate to try into `str`. Returns `false` once there are no more.
+    fn next_candidate(&mut self) -> bool {
+        let n = self.seed.len();
+        loop {
+            match self.step {
+                Step::Complete(i) if i < n => {
+                    self.step = Step::Complete(i + 1);
+                    self.str.clear();
+                    self.str.extend_from_slice(&self.seed[..i]);
+                    if self.complete(self.states[i]) {
+                        return true;
+                    }
+                }
+                Step::Complete(_) => {
+                    self.step = Step::Delete { size: n, start: 0 };
+                }
+                Step::Delete { size: 0, .. } => self.step = Step::Lower(0),
+                Step::Delete { size, start } if start + size > n => {
+                    self.step = Step::Delete {
+                        size: size / 2,
+                        start: 0,
+                    };
+                }
+                Step::Delete { size, start } => {
+                    self.step = Step::Delete {
+                        size,
+                        start: start + 1,
+                    };
+                    self.str.clear();
+                    self.str.extend_from_slice(&self.seed[..start]);
+                    self.str.extend_from_slice(&self.seed[start + size..]);
+                    if self.is_match() {
+                        return true;
+                    }
+                }
+                Step::Lower(i) if i < n => {
+                    self.step = Step::Lower(i + 1);
+                    if self.lower(i) {
+                        return true;
+                    }
+                }
+                Step::Lower(_) => self.step = Step::Done,
+                Step::Done => return false,
+            }
+        }
+    }
+
+    /// Extend `str` from `state` with the smallest path to a match
+    fn complete(&mut self, mut state: usize) -> bool {
+        if self.dist[state] == usize::MAX {
+            return false;
+        }
+        while self.dist[state] > 0 {
+            let (b, next) = self.graph.edges[state]
+                .iter()
+                .find(|&&(_, _, t)| self.dist[t] + 1 == self.dist[state])
+                .map(|&(start, _, t)| (start, t))
+                .expect("a live state should have a path to an accepting state");
+            self.str.push(b);
+            state = next;
+        }
+        true
+    }
+
+    /// Write the seed with the byte at `i` replaced by the smallest byte that keeps it a match
+    fn lower(&mut self, i: usize) -> bool {
+        let rest = &self.seed[i + 1..];
+        let Some(b) = self.graph.edges[self.states[i]]
+            .iter()
+            .take_while(|&&(start, _, _)| start < self.seed[i])
+            .find(|&&(_, _, t)| {
+                self.dist[t] != usize::MAX
+                    && rest
+                        .iter()
+                        .try_fold(t, |s, &b| step(&self.graph, s, b))
+                        .is_some_and(|s| self.graph.accept[s])
+            })
+            .map(|&(start, _, _)| start)
+        else {
+            return false;
+        };
+        self.str.clear();
+        self.str.extend_from_slice(&self.seed);
+        self.str[i] = b;
+        true
+    }
+
+    fn is_match(&self) -> bool {
+        is_match(&self.graph, &self.str)
+    }
+
+    /// Whether `str` is shorter than the seed, or the same length and lexicographically smaller
+    fn is_smaller(&self) -> bool {
+        (self.str.len(), &self.str) < (self.seed.len(), &self.seed)
+    }
+}
+
+impl BorrowNext for ShrinkIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        ShrinkIter::borrow_next(self)
+    }
+}
+
+impl Iterator for ShrinkIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex_automata::dfa::regex::Regex;
+
+    use crate::DenseDfaIter;
+
+    #[test]
+    fn candidates() {
+        let pattern = r"(?:foo|bar)+-[a-z]{2,}";
+        let iter = DenseDfaIter::new(pattern).unwrap();
+        let re = Regex::new(&format!("^(?:{pattern})$")).unwrap();
+
+        let seed = b"barfoobar-xyz";
+        let x: Vec<Vec<u8>> = iter.shrink(seed).unwrap().collect();
+        // the first alternative, fewest repetitions, and smallest class bytes
+        assert_eq!(x[0], b"bar-aa");
+        for s in &x {
+            assert!(re.is_match(s), "{s:?}");
+            assert!((s.len(), s) < (seed.len(), &seed.to_vec()), "{s:?}");
+        }
+        // a repetition is removed
+        assert!(x.contains(&b"barbar-xyz".to_vec()));
+        // the rest is finished as simply as possible
+        assert!(x.contains(&b"barfoobar-aa".to_vec()));
+        // a class byte is lowered
+        assert!(x.contains(&b"barfoobar-ayz".to_vec()));
+
+        let mut y = x.clone();
+        y.sort();
+        y.dedup();
+        assert_eq!(x.len(), y.len());
+    }
+
+    #[test]
+    fn minimal() {
+        let iter = DenseDfaIter::new(r"(?:foo|bar)+-[a-z]{2,}").unwrap();
+        assert_eq!(iter.shrink(b"bar-aa").unwrap().next(), None);
+        assert!(iter.shrink(b"foo-a").is_none());
+        assert!(iter.shrink(b"").is_none());
+    }
+
+    #[test]
+    fn shrink() {
+        let iter = DenseDfaIter::new(r"(?:[a-z]+,)*[a-z]+").unwrap();
+        // fails whenever there are at least 2 fields and one contains a `q`
+        let fails = |s: &[u8]| s.contains(&b',') && s.contains(&b'q');
+
+        let mut current = b"the,quick,brown,fox".to_vec();
+        while let Some(next) = iter.shrink(&current).unwrap().find(|s| fails(s)) {
+            assert!(iter.shrink(&next).is_some());
+            current = next;
+        }
+        assert_eq!(current, b"a,q");
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// (graph index, edge, depth)
+type Frame = (usize, u8, usize);
+const FRAME_SIZE: usize = 17;
+
+/// The bottom of a search stack, kept in a temporary file.
+///
+/// The file is itself a stack of chunks, so the frames that are needed next are always at the end.
+pub(crate) struct Spill {
+    // the most frames to keep in memory
+    limit: usize,
+    file: File,
+    path: PathBuf,
+    // the offset of each chunk in the file
+    chunks: Vec<u64>,
+    // the length of the file
+    len: u64,
+}
+
+impl Spill {
+    /// Create a new temporary file in `dir`
+    pub(crate) fn new(limit: usize, dir: &Path) -> io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "regex-utils-{}-{}.frontier",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self {
+            limit: limit.max(2),
+            file,
+            path,
+            chunks: vec![],
+            len: 0,
+        })
+    }
+
+    /// Move the bottom half of the stack to the file, if the stack is over the limit
+    pub(crate) fn spill(&mut self, stack: &mut Vec<Frame>) {
+        if stack.len() <= self.limit {
+            return;
+        }
+        let mut buf = Vec::with_capacity(stack.len() / 2 * FRAME_SIZE);
+        for (index, b, depth) in stack.drain(..stack.len() / 2) {
+            buf.extend_from_slice(&(index as u64).to_le_bytes());
+            buf.push(b);
+            buf.extend_from_slice(&(depth as u64).to_le_bytes());
+        }
+
+        self.file
+            .seek(SeekFrom::Start(self.len))
+            .and_then(|_| self.file.write_all(&buf))
+            .expect("could not spill the search frontier to disk");
+        self.chunks.push(self.len);
+        self.len += buf.len() as u64;
+    }
+
+    /// Move the last chunk from the file back onto the empty stack.
+    /// Returns `false` if there is nothing left in the file.
+    pub(crate) fn unspill(&mut self, stack: &mut Vec<Frame>) -> bool {
+        let Some(offset) = self.chunks.pop() else {
+            return false;
+        };
+        let mut buf = vec![0; (self.len - offset) as usize];
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.file.read_exact(&mut buf))
+            .and_then(|_| self.file.set_len(offset))
+            .expect("could not read the search frontier from disk");
+        self.len = offset;
+        stack.extend(buf.chunks_exact(FRAME_SIZE).map(|frame| {
+            let index = u64::from_le_bytes(frame[..8].try_into().unwrap());
+            let depth = u64::from_le_bytes(frame[9..].try_into().unwrap());
+            (index as usize, frame[8], depth as usize)
+        }));
+        true
+    }
+
+    /// Forget every chunk in the file
+    pub(crate) fn clear(&mut self) {
+        self.chunks.clear();
+        self.len = 0;
+        self.file
+            .set_len(0)
+            .expect("could not clear the search frontier on disk");
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
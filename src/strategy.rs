@@ -0,0 +1,221 @@
+#![allow(clippy::result_large_err)]
+
+use std::{fmt, sync::Arc};
+
+use proptest::{
+    prelude::Rng as _,
+    strategy::{NewTree, Strategy, ValueTree},
+    test_runner::TestRunner,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use regex_automata::dfa::{dense, Automaton};
+
+use crate::{graph::Graph, DenseDfaIter, DfaIter, Sampler, ShrinkIter};
+
+/// A [`proptest`] strategy for strings that match a regex.
+///
+/// Values are drawn with a [`Sampler`], and shrink through the DFA with [`DfaIter::shrink`],
+/// so a failing input shrinks towards the smallest match that still fails.
+///
+/// ```
+/// use proptest::{strategy::Strategy, test_runner::{TestError, TestRunner}};
+/// use regex_utils::RegexStrategy;
+///
+/// let strategy = RegexStrategy::new(r"[a-z]{1,8}(?:,[a-z]{1,8})*").unwrap();
+///
+/// // a property that fails for lists with a `q` in them
+/// let result = TestRunner::default().run(&strategy, |list| {
+///     proptest::prop_assert!(!list.contains(&b'q'));
+///     Ok(())
+/// });
+/// let Err(TestError::Fail(_, list)) = result else { panic!("{result:?}") };
+/// assert_eq!(list, b"q");
+/// ```
+#[derive(Clone)]
+pub struct RegexStrategy {
+    // the reachable states of the graph, to shrink with
+    graph: Arc<Graph>,
+    // the fewest bytes needed to reach a match from each state
+    dist: Arc<[usize]>,
+    // draws the values
+    sampler: Arc<Sampler>,
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// A [`proptest`] strategy for the matches of this regex. See [`RegexStrategy`]
+    pub fn strategy(&self) -> RegexStrategy {
+        RegexStrategy {
+            graph: Arc::clone(&self.graph),
+            dist: Arc::clone(&self.dist),
+            sampler: Arc::new(Sampler::from(&self.regex)),
+        }
+    }
+}
+
+impl RegexStrategy {
+    /// Parse the given regular expression using a default configuration and
+    /// return the corresponding `RegexStrategy`.
+    ///
+    /// If you want a non-default configuration, then see [`DfaIter::strategy`].
+    pub fn new(pattern: &str) -> Result<Self, dense::BuildError> {
+        DenseDfaIter::new(pattern).map(|iter| iter.strategy())
+    }
+
+    /// Draw values with `sampler` instead, like one with byte weights.
+    ///
+    /// The sampler should be for the same regex, since values still shrink through this one.
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = Arc::new(sampler);
+        self
+    }
+}
+
+impl fmt::Debug for RegexStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegexStrategy")
+            .field("states", &self.graph.states.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Strategy for RegexStrategy {
+    type Tree = RegexValueTree;
+    type Value = Vec<u8>;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        let mut rng = StdRng::seed_from_u64(runner.rng().next_u64());
+        let value = self
+            .sampler
+            .sample(&mut rng)
+            .ok_or("the regex matches no strings")?;
+        let candidates = ShrinkIter::new(Arc::clone(&self.graph), Arc::clone(&self.dist), &value)
+            .ok_or("the sampler produced a string that the regex doesn't match")?;
+        Ok(RegexValueTree {
+            graph: Arc::clone(&self.graph),
+            dist: Arc::clone(&self.dist),
+            failing: value.clone(),
+            current: value,
+            candidates,
+        })
+    }
+}
+
+/// The [`ValueTree`] of a [`RegexStrategy`].
+///
+/// Simplifying tries the candidates of [`DfaIter::shrink`] for the last value that failed,
+/// and moves on to shrinking a candidate once it fails too.
+pub struct RegexValueTree {
+    // the reachable states of the graph
+    graph: Arc<Graph>,
+    // the fewest bytes needed to reach a match from each state
+    dist: Arc<[usize]>,
+    // the simplest value known to fail
+    failing: Vec<u8>,
+    // the value being tested
+    current: Vec<u8>,
+    // the simpler values to try after `failing`
+    candidates: ShrinkIter,
+}
+
+impl ValueTree for RegexValueTree {
+    type Value = Vec<u8>;
+
+    fn current(&self) -> Self::Value {
+        self.current.clone()
+    }
+
+    fn simplify(&mut self) -> bool {
+        if self.current != self.failing {
+            // the candidate failed as well, so shrink from there
+            self.failing = self.current.clone();
+            self.candidates = ShrinkIter::new(
+                Arc::clone(&self.graph),
+                Arc::clone(&self.dist),
+                &self.failing,
+            )
+            .expect("candidates should be matches");
+        }
+        self.next_candidate()
+    }
+
+    fn complicate(&mut self) -> bool {
+        // the candidate passed, so try the next one
+        self.next_candidate()
+    }
+}
+
+impl RegexValueTree {
+    fn next_candidate(&mut self) -> bool {
+        match self.candidates.next() {
+            Some(next) => {
+                self.current = next;
+                true
+            }
+            None => {
+                self.current = self.failing.clone();
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{
+        strategy::{Strategy, ValueTree},
+        test_runner::{TestError, TestRunner},
+    };
+    use regex_automata::dfa::regex::Regex;
+
+    use crate::{DenseDfaIter, Sampler};
+
+    use super::RegexStrategy;
+
+    #[test]
+    fn values() {
+        let pattern = r"[a-z]+@[a-z]+\.(?:com|org)";
+        let re = Regex::new(&format!("^(?:{pattern})$")).unwrap();
+        let strategy = RegexStrategy::new(pattern).unwrap();
+        let mut runner = TestRunner::deterministic();
+        for _ in 0..100 {
+            let value = strategy.new_tree(&mut runner).unwrap().current();
+            assert!(re.is_match(&value), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn shrinks() {
+        let strategy = DenseDfaIter::new(r"(?:[a-z]+,)*[a-z]+")
+            .unwrap()
+            .strategy()
+            .with_sampler(Sampler::new(r"(?:[a-z]+,)*[a-z]+").unwrap().max_len(30));
+        let result = TestRunner::deterministic().run(&strategy, |s| {
+            proptest::prop_assert!(!(s.contains(&b',') && s.contains(&b'q')));
+            Ok(())
+        });
+        let Err(TestError::Fail(_, value)) = result else {
+            panic!("{result:?}")
+        };
+        assert_eq!(value, b"a,q");
+    }
+
+    #[test]
+    fn complicate() {
+        let strategy = RegexStrategy::new(r"[0-9]{2,}").unwrap();
+        let mut runner = TestRunner::deterministic();
+        let mut tree = strategy.new_tree(&mut runner).unwrap();
+        let seed = tree.current();
+
+        // every candidate passes, so each complicate moves on to the next candidate
+        let mut seen = vec![];
+        assert!(tree.simplify());
+        seen.push(tree.current());
+        while tree.complicate() {
+            seen.push(tree.current());
+        }
+        assert_eq!(tree.current(), seed);
+        assert_eq!(seen[0], b"00");
+        assert!(seen.iter().all(|s| (s.len(), s) < (seed.len(), &seed)));
+        assert!(!tree.simplify());
+    }
+}
@@ -0,0 +1,634 @@
+#![allow(clippy::result_large_err)]
+
+use core::fmt;
+use std::{
+    collections::{HashMap, VecDeque},
+    error,
+};
+
+use regex_syntax::{
+    hir::{Class, Hir, HirKind, Look},
+    utf8::Utf8Sequences,
+};
+
+use crate::ByteClass;
+
+/// Regex could not be used by the derivative engines
+#[derive(Debug)]
+pub enum DerivBuildError {
+    /// The pattern could not be parsed
+    Syntax(regex_syntax::Error),
+    /// Unicode word boundaries depend on the whole of the next character, not just the next byte.
+    /// Use an ASCII word boundary (`(?-u:\b)`) instead
+    UnicodeWordBoundary,
+}
+
+impl fmt::Display for DerivBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerivBuildError::Syntax(err) => err.fmt(f),
+            DerivBuildError::UnicodeWordBoundary => {
+                f.write_str("unicode word boundaries are not supported")
+            }
+        }
+    }
+}
+
+impl error::Error for DerivBuildError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DerivBuildError::Syntax(err) => Some(err),
+            DerivBuildError::UnicodeWordBoundary => None,
+        }
+    }
+}
+
+impl From<regex_syntax::Error> for DerivBuildError {
+    fn from(err: regex_syntax::Error) -> Self {
+        DerivBuildError::Syntax(err)
+    }
+}
+
+/// An index into [`Terms`]
+pub(crate) type TermId = usize;
+
+/// The term that matches nothing
+pub(crate) const NOTHING: TermId = 0;
+/// The term that matches only the empty string
+pub(crate) const EMPTY: TermId = 1;
+
+/// A regular expression over bytes.
+///
+/// Terms are only built through [`Terms`], which keeps them normalised, so that equal languages
+/// usually get the same [`TermId`] and the derivatives of a term are finite.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Term {
+    Nothing,
+    Empty,
+    // a single byte from the class
+    Bytes(ByteClass),
+    // the repr of a look-around assertion
+    Look(u16),
+    // always right-nested
+    Concat(TermId, TermId),
+    // flattened, sorted and deduplicated
+    Alt(Vec<TermId>),
+    Repeat {
+        sub: TermId,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+struct Info {
+    // whether the term matches the empty string, assuming every look-around holds
+    nullable: bool,
+    // whether the term contains any look-arounds
+    has_looks: bool,
+    // the bytes that the derivative might not be nothing for
+    first: ByteClass,
+    // the fewest bytes in a match, assuming every look-around holds
+    min_len: usize,
+}
+
+/// A hash-consed arena of [`Term`]s, with their derivatives cached.
+pub(crate) struct Terms {
+    terms: Vec<Term>,
+    info: Vec<Info>,
+    ids: HashMap<Term, TermId>,
+    // (term, previous byte if the term has look-arounds, byte) -> derivative
+    derivs: HashMap<(TermId, Option<u8>, u8), TermId>,
+    // (term, previous byte if the term has look-arounds, byte) -> sorted partial derivatives
+    partial_derivs: HashMap<(TermId, Option<u8>, u8), Vec<TermId>>,
+}
+
+/// The (term, previous byte) states that can be reached from a term by taking derivatives.
+/// The previous byte is only kept for terms with look-arounds, so there are finitely many.
+pub(crate) struct Reachable {
+    index: HashMap<(TermId, Option<u8>), usize>,
+    // the states after each byte from each state
+    edges: Vec<Vec<usize>>,
+    // the fewest bytes from each state to the end of a match. `usize::MAX` if it never matches
+    dist: Vec<usize>,
+}
+
+impl Reachable {
+    /// The fewest bytes needed to finish a match from `t` when following `prev`.
+    /// `usize::MAX` if no match can be finished
+    pub(crate) fn dist(&self, terms: &Terms, t: TermId, prev: Option<u8>) -> usize {
+        self.index
+            .get(&terms.state(t, prev))
+            .map_or(usize::MAX, |&i| self.dist[i])
+    }
+
+    /// The most bytes in a match from the first state, 0 if there are no matches,
+    /// or `None` if matches can be arbitrarily long
+    pub(crate) fn longest(&self) -> Option<usize> {
+        let n = self.dist.len();
+        let live = |i: usize| self.dist[i] != usize::MAX;
+        if !live(0) {
+            return Some(0);
+        }
+
+        // topologically sort the live states, which fails if they have a cycle
+        let mut indegree = vec![0; n];
+        for i in (0..n).filter(|&i| live(i)) {
+            for &j in self.edges[i].iter().filter(|&&j| live(j)) {
+                indegree[j] += 1;
+            }
+        }
+        let mut queue: Vec<usize> = (0..n).filter(|&i| live(i) && indegree[i] == 0).collect();
+        let mut order = vec![];
+        while let Some(i) = queue.pop() {
+            order.push(i);
+            for &j in self.edges[i].iter().filter(|&&j| live(j)) {
+                indegree[j] -= 1;
+                if indegree[j] == 0 {
+                    queue.push(j);
+                }
+            }
+        }
+        if order.len() < (0..n).filter(|&i| live(i)).count() {
+            return None;
+        }
+
+        let mut longest = vec![0; n];
+        for &i in order.iter().rev() {
+            longest[i] = self.edges[i]
+                .iter()
+                .filter(|&&j| live(j))
+                .map(|&j| longest[j] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+        Some(longest[0])
+    }
+}
+
+impl Terms {
+    pub(crate) fn new() -> Self {
+        let mut terms = Self {
+            terms: vec![],
+            info: vec![],
+            ids: HashMap::new(),
+            derivs: HashMap::new(),
+            partial_derivs: HashMap::new(),
+        };
+        terms.intern(Term::Nothing);
+        terms.intern(Term::Empty);
+        terms
+    }
+
+    fn intern(&mut self, term: Term) -> TermId {
+        if let Some(&id) = self.ids.get(&term) {
+            return id;
+        }
+
+        let info = match &term {
+            Term::Nothing => Info {
+                nullable: false,
+                has_looks: false,
+                first: ByteClass::empty(),
+                min_len: usize::MAX,
+            },
+            Term::Empty => Info {
+                nullable: true,
+                has_looks: false,
+                first: ByteClass::empty(),
+                min_len: 0,
+            },
+            Term::Bytes(class) => Info {
+                nullable: false,
+                has_looks: false,
+                first: *class,
+                min_len: 1,
+            },
+            Term::Look(_) => Info {
+                nullable: true,
+                has_looks: true,
+                first: ByteClass::empty(),
+                min_len: 0,
+            },
+            &Term::Concat(a, b) => {
+                let (a, b) = (&self.info[a], &self.info[b]);
+                Info {
+                    nullable: a.nullable && b.nullable,
+                    has_looks: a.has_looks || b.has_looks,
+                    first: if a.nullable {
+                        a.first.union(&b.first)
+                    } else {
+                        a.first
+                    },
+                    min_len: a.min_len.saturating_add(b.min_len),
+                }
+            }
+            Term::Alt(terms) => terms.iter().map(|&t| &self.info[t]).fold(
+                Info {
+                    nullable: false,
+                    has_looks: false,
+                    first: ByteClass::empty(),
+                    min_len: usize::MAX,
+                },
+                |acc, info| Info {
+                    nullable: acc.nullable || info.nullable,
+                    has_looks: acc.has_looks || info.has_looks,
+                    first: acc.first.union(&info.first),
+                    min_len: acc.min_len.min(info.min_len),
+                },
+            ),
+            &Term::Repeat { sub, min, .. } => {
+                let sub = &self.info[sub];
+                Info {
+                    nullable: min == 0 || sub.nullable,
+                    has_looks: sub.has_looks,
+                    first: sub.first,
+                    min_len: sub.min_len.saturating_mul(min as usize),
+                }
+            }
+        };
+
+        let id = self.terms.len();
+        self.terms.push(term.clone());
+        self.info.push(info);
+        self.ids.insert(term, id);
+        id
+    }
+
+    /// Convert the regex into a term
+    pub(crate) fn hir(&mut self, hir: &Hir) -> Result<TermId, DerivBuildError> {
+        let term = match hir.kind() {
+            HirKind::Empty => EMPTY,
+            HirKind::Literal(lit) => lit.0.iter().rev().fold(EMPTY, |acc, &b| {
+                let b = self.bytes(ByteClass::from_iter([b]));
+                self.concat(b, acc)
+            }),
+            HirKind::Class(Class::Bytes(class)) => self.bytes(
+                class
+                    .ranges()
+                    .iter()
+                    .flat_map(|r| r.start()..=r.end())
+                    .collect(),
+            ),
+            HirKind::Class(Class::Unicode(class)) => {
+                let mut alts = vec![];
+                for range in class.iter() {
+                    for seq in Utf8Sequences::new(range.start(), range.end()) {
+                        let term = seq.as_slice().iter().rev().fold(EMPTY, |acc, r| {
+                            let b = self.bytes((r.start..=r.end).collect());
+                            self.concat(b, acc)
+                        });
+                        alts.push(term);
+                    }
+                }
+                self.alt(alts)
+            }
+            HirKind::Look(Look::WordUnicode | Look::WordUnicodeNegate) => {
+                return Err(DerivBuildError::UnicodeWordBoundary)
+            }
+            HirKind::Look(look) => self.intern(Term::Look(look.as_repr())),
+            HirKind::Repetition(rep) => {
+                let sub = self.hir(&rep.sub)?;
+                self.repeat(sub, rep.min, rep.max)
+            }
+            HirKind::Capture(cap) => self.hir(&cap.sub)?,
+            HirKind::Concat(hirs) => {
+                let terms = hirs
+                    .iter()
+                    .map(|hir| self.hir(hir))
+                    .collect::<Result<Vec<_>, _>>()?;
+                terms
+                    .into_iter()
+                    .rev()
+                    .fold(EMPTY, |acc, t| self.concat(t, acc))
+            }
+            HirKind::Alternation(hirs) => {
+                let terms = hirs
+                    .iter()
+                    .map(|hir| self.hir(hir))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.alt(terms)
+            }
+        };
+        Ok(term)
+    }
+
+    pub(crate) fn bytes(&mut self, class: ByteClass) -> TermId {
+        if class.is_empty() {
+            NOTHING
+        } else {
+            self.intern(Term::Bytes(class))
+        }
+    }
+
+    pub(crate) fn concat(&mut self, a: TermId, b: TermId) -> TermId {
+        match (a, b) {
+            (NOTHING, _) | (_, NOTHING) => NOTHING,
+            (EMPTY, t) | (t, EMPTY) => t,
+            _ => match self.terms[a] {
+                Term::Concat(x, y) => {
+                    let rest = self.concat(y, b);
+                    self.concat(x, rest)
+                }
+                _ => self.intern(Term::Concat(a, b)),
+            },
+        }
+    }
+
+    pub(crate) fn alt(&mut self, terms: impl IntoIterator<Item = TermId>) -> TermId {
+        let mut flat = vec![];
+        for t in terms {
+            match &self.terms[t] {
+                Term::Nothing => {}
+                Term::Alt(ts) => flat.extend_from_slice(ts),
+                _ => flat.push(t),
+            }
+        }
+        flat.sort_unstable();
+        flat.dedup();
+        match flat[..] {
+            [] => NOTHING,
+            [t] => t,
+            _ => self.intern(Term::Alt(flat)),
+        }
+    }
+
+    pub(crate) fn repeat(&mut self, sub: TermId, min: u32, max: Option<u32>) -> TermId {
+        match (sub, min, max) {
+            (_, _, Some(0)) | (EMPTY, _, _) | (NOTHING, 0, _) => EMPTY,
+            (NOTHING, _, _) => NOTHING,
+            (_, 1, Some(1)) => sub,
+            _ => self.intern(Term::Repeat { sub, min, max }),
+        }
+    }
+
+    /// The bytes that the derivative might not be nothing for
+    pub(crate) fn first(&self, t: TermId) -> ByteClass {
+        self.info[t].first
+    }
+
+    /// A lower bound on the number of bytes in a match
+    pub(crate) fn min_len(&self, t: TermId) -> usize {
+        self.info[t].min_len
+    }
+
+    /// Whether the term matches the empty string between the `prev` and `next` bytes.
+    /// `None` is the start or end of the input
+    pub(crate) fn nullable(&self, t: TermId, prev: Option<u8>, next: Option<u8>) -> bool {
+        let info = &self.info[t];
+        if !info.has_looks || !info.nullable {
+            return info.nullable;
+        }
+        match &self.terms[t] {
+            &Term::Look(look) => look_holds(Look::from_repr(look).unwrap(), prev, next),
+            &Term::Concat(a, b) => self.nullable(a, prev, next) && self.nullable(b, prev, next),
+            Term::Alt(terms) => terms.iter().any(|&t| self.nullable(t, prev, next)),
+            &Term::Repeat { sub, min, .. } => min == 0 || self.nullable(sub, prev, next),
+            Term::Nothing | Term::Empty | Term::Bytes(_) => unreachable!("term has no looks"),
+        }
+    }
+
+    /// The term matching the rest of each match that starts with `b`, when following `prev`
+    pub(crate) fn deriv(&mut self, t: TermId, prev: Option<u8>, b: u8) -> TermId {
+        if !self.info[t].first.contains(b) {
+            return NOTHING;
+        }
+        // the previous byte only matters for look-arounds
+        let prev = prev.filter(|_| self.info[t].has_looks);
+        if let Some(&d) = self.derivs.get(&(t, prev, b)) {
+            return d;
+        }
+
+        let d = match self.terms[t].clone() {
+            Term::Nothing | Term::Empty | Term::Look(_) => NOTHING,
+            // `first` already checked the byte is in the class
+            Term::Bytes(_) => EMPTY,
+            Term::Concat(a, rest) => {
+                let da = self.deriv(a, prev, b);
+                let da = self.concat(da, rest);
+                if self.nullable(a, prev, Some(b)) {
+                    let drest = self.deriv(rest, prev, b);
+                    self.alt([da, drest])
+                } else {
+                    da
+                }
+            }
+            Term::Alt(terms) => {
+                let ds: Vec<_> = terms.iter().map(|&t| self.deriv(t, prev, b)).collect();
+                self.alt(ds)
+            }
+            Term::Repeat { sub, min, max } => {
+                let d = self.deriv(sub, prev, b);
+                let rest = self.repeat(sub, min.saturating_sub(1), max.map(|max| max - 1));
+                self.concat(d, rest)
+            }
+        };
+
+        self.derivs.insert((t, prev, b), d);
+        d
+    }
+
+    /// The terms whose union matches the rest of each match that starts with `b`, when following `prev`.
+    ///
+    /// These are the [Antimirov](https://doi.org/10.1016/0304-3975(95)00182-4) partial derivatives,
+    /// which split the alternatives of the derivative up instead of keeping them together.
+    pub(crate) fn partial_derivs(&mut self, t: TermId, prev: Option<u8>, b: u8) -> &[TermId] {
+        if !self.info[t].first.contains(b) {
+            return &[];
+        }
+        let prev = prev.filter(|_| self.info[t].has_looks);
+        let key = (t, prev, b);
+        if !self.partial_derivs.contains_key(&key) {
+            let mut pd = match self.terms[t].clone() {
+                Term::Nothing | Term::Empty | Term::Look(_) => vec![],
+                Term::Bytes(_) => vec![EMPTY],
+                Term::Concat(a, rest) => {
+                    let mut pd = self.partial_derivs(a, prev, b).to_vec();
+                    for p in &mut pd {
+                        *p = self.concat(*p, rest);
+                    }
+                    if self.nullable(a, prev, Some(b)) {
+                        pd.extend_from_slice(self.partial_derivs(rest, prev, b));
+                    }
+                    pd
+                }
+                Term::Alt(terms) => {
+                    let mut pd = vec![];
+                    for t in terms {
+                        pd.extend_from_slice(self.partial_derivs(t, prev, b));
+                    }
+                    pd
+                }
+                Term::Repeat { sub, min, max } => {
+                    let rest = self.repeat(sub, min.saturating_sub(1), max.map(|max| max - 1));
+                    let mut pd = self.partial_derivs(sub, prev, b).to_vec();
+                    for p in &mut pd {
+                        *p = self.concat(*p, rest);
+                    }
+                    pd
+                }
+            };
+            pd.sort_unstable();
+            pd.dedup();
+            pd.retain(|&p| p != NOTHING);
+            self.partial_derivs.insert(key, pd);
+        }
+        &self.partial_derivs[&key]
+    }
+
+    fn state(&self, t: TermId, prev: Option<u8>) -> (TermId, Option<u8>) {
+        (t, prev.filter(|_| self.info[t].has_looks))
+    }
+
+    /// Every state that can be reached from `t` when following `prev`, by taking derivatives,
+    /// or partial derivatives if `partial` is set.
+    ///
+    /// Unlike [`Terms::min_len`], this knows which look-arounds can hold.
+    pub(crate) fn reachable(&mut self, t: TermId, prev: Option<u8>, partial: bool) -> Reachable {
+        let mut states = vec![self.state(t, prev)];
+        let mut index = HashMap::from([(states[0], 0)]);
+        let mut edges = vec![];
+
+        let mut i = 0;
+        while i < states.len() {
+            let (t, prev) = states[i];
+            let first = self.first(t);
+            let mut next = vec![];
+            for b in (0..=255).filter(|&b| first.contains(b)) {
+                let ds = if partial {
+                    self.partial_derivs(t, prev, b).to_vec()
+                } else {
+                    vec![self.deriv(t, prev, b)]
+                };
+                for d in ds.into_iter().filter(|&d| d != NOTHING) {
+                    let key = self.state(d, Some(b));
+                    next.push(*index.entry(key).or_insert_with(|| {
+                        states.push(key);
+                        states.len() - 1
+                    }));
+                }
+            }
+            edges.push(next);
+            i += 1;
+        }
+
+        // search backwards from the states that can end a match
+        let mut reverse = vec![vec![]; states.len()];
+        for (i, edges) in edges.iter().enumerate() {
+            for &j in edges {
+                reverse[j].push(i);
+            }
+        }
+        let mut dist = vec![usize::MAX; states.len()];
+        let mut queue = VecDeque::new();
+        for (i, &(t, prev)) in states.iter().enumerate() {
+            if self.nullable(t, prev, None) {
+                dist[i] = 0;
+                queue.push_back(i);
+            }
+        }
+        while let Some(i) = queue.pop_front() {
+            for &j in &reverse[i] {
+                if dist[j] == usize::MAX {
+                    dist[j] = dist[i] + 1;
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        Reachable { index, edges, dist }
+    }
+}
+
+fn look_holds(look: Look, prev: Option<u8>, next: Option<u8>) -> bool {
+    let is_word = |b: Option<u8>| b.is_some_and(regex_syntax::is_word_byte);
+    match look {
+        Look::Start => prev.is_none(),
+        Look::End => next.is_none(),
+        Look::StartLF => matches!(prev, None | Some(b'\n')),
+        Look::EndLF => matches!(next, None | Some(b'\n')),
+        Look::StartCRLF => match prev {
+            None | Some(b'\n') => true,
+            Some(b'\r') => next != Some(b'\n'),
+            _ => false,
+        },
+        Look::EndCRLF => match next {
+            None | Some(b'\r') => true,
+            Some(b'\n') => prev != Some(b'\r'),
+            _ => false,
+        },
+        Look::WordAscii => is_word(prev) != is_word(next),
+        Look::WordAsciiNegate => is_word(prev) == is_word(next),
+        Look::WordUnicode | Look::WordUnicodeNegate => {
+            unreachable!("unicode word boundaries are rejected when building terms")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(terms: &mut Terms, pattern: &str) -> TermId {
+        terms.hir(&regex_syntax::parse(pattern).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn normalise() {
+        let mut terms = Terms::new();
+        let a = term(&mut terms, r"ab|cd|ab");
+        let b = term(&mut terms, r"(?:cd)|a(?:b)");
+        assert_eq!(a, b);
+
+        // derivatives of loops converge
+        let t = term(&mut terms, r"(?:a*)*");
+        let d = terms.deriv(t, None, b'a');
+        assert_eq!(terms.deriv(d, Some(b'a'), b'a'), d);
+        assert_eq!(terms.deriv(t, None, b'b'), NOTHING);
+
+        assert_eq!(term(&mut terms, r"[a&&b]"), NOTHING);
+    }
+
+    #[test]
+    fn partial_derivs() {
+        let mut terms = Terms::new();
+        // a*(?:ab|ac), b, c
+        let t = term(&mut terms, r"a*(?:ab|ac)");
+        assert_eq!(terms.partial_derivs(t, None, b'a').len(), 3);
+        assert_eq!(terms.partial_derivs(t, None, b'b'), []);
+
+        // the alternatives are deduplicated
+        let t = term(&mut terms, r"a|[ab]");
+        assert_eq!(terms.partial_derivs(t, None, b'a'), [EMPTY]);
+    }
+
+    #[test]
+    fn looks() {
+        let mut terms = Terms::new();
+        let t = term(&mut terms, r"(?m)a$(?-u:\b)");
+        let d = terms.deriv(t, None, b'a');
+        assert!(terms.nullable(d, Some(b'a'), None));
+        assert!(terms.nullable(d, Some(b'a'), Some(b'\n')));
+        assert!(!terms.nullable(d, Some(b'a'), Some(b'b')));
+
+        let t = term(&mut terms, r"a$");
+        let d = terms.deriv(t, None, b'a');
+        assert!(!terms.nullable(d, Some(b'a'), Some(b'\n')));
+
+        let err = terms.hir(&regex_syntax::parse(r"\b").unwrap());
+        assert!(matches!(err, Err(DerivBuildError::UnicodeWordBoundary)));
+    }
+
+    #[test]
+    fn longest() {
+        let mut terms = Terms::new();
+        let longest = |terms: &mut Terms, pattern| {
+            let t = term(terms, pattern);
+            terms.reachable(t, None, false).longest()
+        };
+        assert_eq!(longest(&mut terms, r"ab|c{0,3}"), Some(3));
+        assert_eq!(longest(&mut terms, r"ab*"), None);
+        // the repetition can't continue after the end
+        assert_eq!(longest(&mut terms, r"a(?:b$)*"), Some(2));
+        assert_eq!(longest(&mut terms, r"a*$b"), Some(0));
+    }
+}
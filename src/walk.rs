@@ -0,0 +1,166 @@
+use regex_automata::{dfa::Automaton, util::primitives::StateID};
+
+use crate::DfaIter;
+
+/// Receives the events of a depth first traversal of a regex. See [`DfaIter::walk`]
+///
+/// Every method does nothing by default, so visitors only need to implement the events they use.
+pub trait Visitor {
+    /// The traversal reached `state`, after `depth` bytes
+    fn enter_state(&mut self, state: StateID, depth: usize) {
+        let _ = (state, depth);
+    }
+
+    /// The traversal added `byte` to the current path
+    fn take_byte(&mut self, byte: u8) {
+        let _ = byte;
+    }
+
+    /// The traversal removed the last byte from the current path
+    fn backtrack(&mut self) {}
+
+    /// The current path is a matching string
+    fn found_match(&mut self, str: &[u8]) {
+        let _ = str;
+    }
+}
+
+impl<A: Automaton> DfaIter<A> {
+    /// Walk every path through the regex of up to `max_len` bytes that can still reach a match,
+    /// reporting each step to the visitor.
+    ///
+    /// Paths are walked depth first in lexicographic byte order, so unlike the iterator, longer
+    /// matches can be found before shorter ones. Every [`Visitor::take_byte`] is followed by a
+    /// [`Visitor::enter_state`], and later undone by a [`Visitor::backtrack`].
+    ///
+    /// ```
+    /// use regex_utils::{DenseDfaIter, Visitor};
+    ///
+    /// // count the matches of each length
+    /// struct Lengths(Vec<usize>);
+    /// impl Visitor for Lengths {
+    ///     fn found_match(&mut self, str: &[u8]) {
+    ///         self.0[str.len()] += 1;
+    ///     }
+    /// }
+    ///
+    /// let iter = DenseDfaIter::new(r"[ab]{1,3}").unwrap();
+    /// let mut lengths = Lengths(vec![0; 4]);
+    /// iter.walk(3, &mut lengths);
+    /// assert_eq!(lengths.0, [0, 2, 4, 8]);
+    /// ```
+    pub fn walk<V: Visitor + ?Sized>(&self, max_len: usize, visitor: &mut V) {
+        let graph = &self.graph;
+        if self.dist[0] > max_len {
+            return;
+        }
+
+        let mut path = vec![];
+        visitor.enter_state(graph.states[0], 0);
+        if graph.accept[0] {
+            visitor.found_match(&path);
+        }
+
+        // (graph index, next byte to take)
+        let mut stack = vec![(0, 0u16)];
+        while let Some((current, next_byte)) = stack.last_mut() {
+            let depth = path.len();
+            let run = graph.edges[*current]
+                .iter()
+                .find(|&&(_, end, _)| end as u16 >= *next_byte)
+                .filter(|_| depth < max_len);
+            let Some(&(start, end, next)) = run else {
+                stack.pop();
+                if !stack.is_empty() {
+                    path.pop();
+                    visitor.backtrack();
+                }
+                continue;
+            };
+
+            // skip the whole run if its target can't reach a match in time
+            if self.dist[next] == usize::MAX || depth + 1 + self.dist[next] > max_len {
+                *next_byte = end as u16 + 1;
+                continue;
+            }
+
+            let b = u16::max(*next_byte, start as u16) as u8;
+            *next_byte = b as u16 + 1;
+
+            path.push(b);
+            visitor.take_byte(b);
+            visitor.enter_state(graph.states[next], depth + 1);
+            if graph.accept[next] {
+                visitor.found_match(&path);
+            }
+            stack.push((next, 0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::DenseDfaIter;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        path: Vec<u8>,
+        depth: usize,
+        states: HashSet<StateID>,
+        matches: Vec<Vec<u8>>,
+    }
+
+    impl Visitor for Recorder {
+        fn enter_state(&mut self, state: StateID, depth: usize) {
+            assert_eq!(depth, self.path.len());
+            self.depth = usize::max(self.depth, depth);
+            self.states.insert(state);
+        }
+
+        fn take_byte(&mut self, byte: u8) {
+            self.path.push(byte);
+        }
+
+        fn backtrack(&mut self) {
+            self.path.pop().unwrap();
+        }
+
+        fn found_match(&mut self, str: &[u8]) {
+            assert_eq!(str, self.path);
+            self.matches.push(str.to_vec());
+        }
+    }
+
+    #[test]
+    fn walk() {
+        let iter = DenseDfaIter::new(r"(ab|b)*c|d").unwrap();
+        let mut recorder = Recorder::default();
+        iter.walk(4, &mut recorder);
+
+        // every byte taken was backtracked
+        assert!(recorder.path.is_empty());
+        assert_eq!(recorder.depth, 4);
+
+        let mut x = recorder.matches;
+        x.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+        let y: Vec<Vec<u8>> = iter.take_while(|s| s.len() <= 4).collect();
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn empty() {
+        let iter = DenseDfaIter::new(r"abc").unwrap();
+        let mut recorder = Recorder::default();
+        // no match is short enough
+        iter.walk(2, &mut recorder);
+        assert!(recorder.states.is_empty());
+
+        iter.walk(3, &mut recorder);
+        assert_eq!(recorder.states.len(), 4);
+        assert_eq!(recorder.matches, [b"abc".to_vec()]);
+    }
+}
@@ -0,0 +1,179 @@
+#![allow(clippy::result_large_err)]
+
+use regex_automata::{
+    nfa::thompson::{BuildError, NFA},
+    util::syntax,
+};
+use regex_syntax::hir::HirKind;
+
+use crate::{BorrowNext, NfaIter};
+
+/// `WeightedIter` will produce every possible string value that will match with the given regex,
+/// favouring the top-level alternation arms with higher weights.
+///
+/// Each arm of the top-level alternation is explored independently, and the outputs are interleaved
+/// in proportion to the arm weights. If one arm has weight 9 and another has weight 1, then
+/// 9 out of every 10 outputs will come from the first arm, until it runs out of matches.
+///
+/// ```
+/// use regex_utils::WeightedIter;
+///
+/// let iter = WeightedIter::new(r"[a-c]{2}|[0-9]", &[1, 3]).unwrap();
+/// let x: Vec<Vec<u8>> = iter.take(8).collect();
+/// assert_eq!(x, [
+///     b"0".to_vec(),
+///     b"aa".to_vec(),
+///     b"1".to_vec(),
+///     b"2".to_vec(),
+///     b"3".to_vec(),
+///     b"ab".to_vec(),
+///     b"4".to_vec(),
+///     b"5".to_vec(),
+/// ]);
+/// ```
+pub struct WeightedIter {
+    // (iterator, weight, current weight, its next match) for each arm.
+    // the next match is `None` once the arm has run out
+    arms: Vec<(NfaIter, u32, i64, Option<Vec<u8>>)>,
+    // the current match
+    str: Vec<u8>,
+}
+
+impl WeightedIter {
+    /// Parse the given regular expression using a default configuration and
+    /// weight each arm of its top-level alternation by index.
+    ///
+    /// Arms without a given weight default to a weight of 1. Arms with a weight of 0
+    /// are never produced.
+    ///
+    /// Note that single character arms like `a|b` are merged into one class `[ab]` by
+    /// the parser, so they count as a single arm.
+    pub fn new(pattern: &str, weights: &[u32]) -> Result<Self, BuildError> {
+        // report any syntax errors in the same way as the other iterators
+        NFA::new(pattern)?;
+        let hir = syntax::parse(pattern).expect("pattern was already parsed successfully");
+
+        let arms = match hir.kind() {
+            HirKind::Alternation(arms) => arms.as_slice(),
+            _ => std::slice::from_ref(&hir),
+        };
+        let arms = arms
+            .iter()
+            .enumerate()
+            .map(|(i, arm)| (arm, weights.get(i).copied().unwrap_or(1)))
+            // the round robin could still pick these once another arm runs out
+            .filter(|&(_, weight)| weight > 0)
+            .map(|(arm, weight)| {
+                let nfa = NFA::compiler().build_from_hir(arm)?;
+                let mut iter = NfaIter::from(nfa);
+                let next = iter.borrow_next().map(<[u8]>::to_vec);
+                Ok((iter, weight, 0, next))
+            })
+            .collect::<Result<_, BuildError>>()?;
+
+        Ok(Self { arms, str: vec![] })
+    }
+
+    /// Get the next matching string ref from this regex iterator
+    pub fn borrow_next(&mut self) -> Option<&[u8]> {
+        loop {
+            // smooth weighted round robin
+            let total: i64 = self.arms.iter().map(|(_, w, _, _)| *w as i64).sum();
+            if total == 0 {
+                break None;
+            }
+            for (_, weight, current, _) in &mut self.arms {
+                *current += *weight as i64;
+            }
+            let (i, _) = self
+                .arms
+                .iter()
+                .enumerate()
+                .max_by_key(|&(i, (_, _, current, _))| (*current, std::cmp::Reverse(i)))?;
+            self.arms[i].2 -= total;
+
+            let (iter, _, _, next) = &mut self.arms[i];
+            let Some(next_str) = next else {
+                // this arm has no more matches
+                self.arms.remove(i);
+                continue;
+            };
+            std::mem::swap(&mut self.str, next_str);
+            match iter.borrow_next() {
+                Some(s) => {
+                    next_str.clear();
+                    next_str.extend_from_slice(s);
+                }
+                None => *next = None,
+            }
+            break Some(&self.str);
+        }
+    }
+}
+
+impl BorrowNext for WeightedIter {
+    fn borrow_next(&mut self) -> Option<&[u8]> {
+        WeightedIter::borrow_next(self)
+    }
+}
+
+impl Iterator for WeightedIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.borrow_next().map(ToOwned::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skewed() {
+        let iter = WeightedIter::new(r"a+|b+", &[9, 1]).unwrap();
+
+        let x: Vec<Vec<u8>> = iter.take(100).collect();
+        let a = x.iter().filter(|s| s[0] == b'a').count();
+        assert_eq!(a, 90);
+    }
+
+    #[test]
+    fn exhausted() {
+        // once an arm runs out, the rest take over
+        let iter = WeightedIter::new(r"foo|[0-9]", &[1, 1]).unwrap();
+
+        let x: Vec<Vec<u8>> = iter.collect();
+        assert_eq!(x.len(), 11);
+        assert_eq!(x[0], b"foo");
+    }
+
+    #[test]
+    fn disabled() {
+        let iter = WeightedIter::new(r"a+|b+|c+", &[1, 0]).unwrap();
+
+        let x: Vec<Vec<u8>> = iter.take(4).collect();
+        assert_eq!(
+            x,
+            [b"a".to_vec(), b"c".to_vec(), b"aa".to_vec(), b"cc".to_vec()]
+        );
+    }
+
+    #[test]
+    fn disabled_after_exhausted() {
+        let iter = WeightedIter::new(r"z+|a{1,3}|b+", &[0, 5, 1]).unwrap();
+
+        let x: Vec<Vec<u8>> = iter.take(6).collect();
+        assert_eq!(
+            x,
+            [
+                b"a".to_vec(),
+                b"aa".to_vec(),
+                b"aaa".to_vec(),
+                b"b".to_vec(),
+                b"bb".to_vec(),
+                b"bbb".to_vec(),
+            ]
+        );
+    }
+}